@@ -1,49 +1,383 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum};
+
+use crate::{
+    gpu::GpuOptions,
+    output::{self, Aov, OutputFormat, ToneMapper, ToneMapping},
+    scene::{Background, Camera},
+};
 
 #[allow(clippy::module_name_repetitions)]
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Parser, Debug)]
 #[command(about, version)]
 pub struct CliArgs {
-    /// chunk size (width:height)
-    #[arg(long, default_value = "64:64")]
-    pub chunk_size: String,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// adapter name filter (case insensitive substring)
+    #[arg(long)]
+    pub adapter: Option<String>,
+
+    /// background (gradient, black, sky, a solid r:g:b colour or an .hdr or .exr environment),
+    /// overrides the scene
+    #[arg(long, value_parser = str_to_background)]
+    pub background: Option<Background>,
+
+    /// auxiliary images of the first hits to write next to the output (comma separated list)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub aov: Vec<Aov>,
+
+    /// backends (comma separated list of vulkan, metal, dx12, dx11, gl or webgpu)
+    #[arg(long, value_parser = str_to_backends)]
+    pub backend: Option<wgpu::Backends>,
+
+    /// test every primitive for every ray instead of traversing the bounding volume hierarchy
+    #[arg(long)]
+    pub brute_force: bool,
+
+    /// chunk size (width:height), the radiance of every chunk stays on the graphics card until the
+    /// image is done, so memory use grows with the whole image rather than the chunk
+    #[arg(long, default_value = "64:64", value_parser = str_to_vec2)]
+    pub chunk_size: glam::UVec2,
+
+    /// camera aperture (lens diameter)
+    #[arg(long)]
+    pub aperture: Option<f32>,
+
+    /// render on the cpu instead of the gpu
+    #[arg(long)]
+    pub cpu: bool,
+
+    /// smooth out the noise of the image, guided by the normals, albedo and depth of the first hits
+    #[arg(long)]
+    pub denoise: bool,
+
+    /// write the scene being rendered to a file (json or toml)
+    #[arg(long, value_hint = clap::ValueHint::FilePath)]
+    pub dump_scene: Option<PathBuf>,
+
+    /// exposure in stops applied before tone mapping 8 bit images (+1 doubles the brightness)
+    #[arg(long, default_value = "0", allow_hyphen_values = true)]
+    pub exposure: f32,
+
+    /// camera focus distance
+    #[arg(long)]
+    pub focus_distance: Option<f32>,
+
+    /// only use fallback (software) adapters
+    #[arg(long)]
+    pub force_fallback_adapter: bool,
+
+    /// light transport integrator
+    #[arg(long, value_enum, default_value_t = Integrator::NextEventEstimation)]
+    pub integrator: Integrator,
+
+    /// camera look at (x:y:z)
+    #[arg(long, allow_hyphen_values = true, value_parser = str_to_vec3)]
+    pub look_at: Option<glam::Vec3>,
+
+    /// camera look from (x:y:z)
+    #[arg(long, allow_hyphen_values = true, value_parser = str_to_vec3)]
+    pub look_from: Option<glam::Vec3>,
+
+    /// bounces after which a path is terminated
+    #[arg(long, default_value = "50")]
+    pub max_depth: u32,
 
-    /// output
-    #[arg(long, default_value = "image.ppm", value_hint = clap::ValueHint::DirPath)]
+    /// samples after which a pixel stops even when it is still noisy, defaults to four times the samples per pixel
+    #[arg(long)]
+    pub max_samples: Option<u32>,
+
+    /// keep sampling pixels whose relative standard error is above this past the samples per pixel
+    #[arg(long, value_parser = str_to_positive)]
+    pub noise_threshold: Option<f32>,
+
+    /// output (png, ppm, ascii.ppm, exr, hdr or pfm)
+    #[arg(long, default_value = "image.png", value_hint = clap::ValueHint::FilePath)]
     pub output: PathBuf,
 
+    /// power preference
+    #[arg(long, value_enum, default_value_t = PowerPreference::High)]
+    pub power_preference: PowerPreference,
+
+    /// randomly terminate dim paths after this many bounces (Russian roulette)
+    #[arg(long)]
+    pub russian_roulette: Option<u32>,
+
+    /// samples added to every pixel by one dispatch (lower keeps each dispatch short)
+    #[arg(long, default_value = "16")]
+    pub samples_per_dispatch: u32,
+
+    /// write the number of samples taken of each pixel as a heatmap image
+    #[arg(long, value_hint = clap::ValueHint::FilePath)]
+    pub samples_heatmap: Option<PathBuf>,
+
     /// samples per pixel
     #[arg(long, default_value = "500")]
     pub samples_per_pixel: u32,
 
+    /// scene file (json or toml), defaults to a random scene
+    #[arg(long, value_hint = clap::ValueHint::FilePath)]
+    pub scene: Option<PathBuf>,
+
+    /// seed for scene generation and sampling, random when not given
+    #[arg(long)]
+    pub seed: Option<u32>,
+
+    /// camera shutter interval (open:close), moving spheres are at their center at 0 and their end center at 1
+    #[arg(long, value_parser = str_to_interval)]
+    pub shutter: Option<(f32, f32)>,
+
     /// screen size (width:height)
-    #[arg(long, default_value = "1920:1080")]
-    pub screen_size: String,
+    #[arg(long, default_value = "1920:1080", value_parser = str_to_vec2)]
+    pub screen_size: glam::UVec2,
 
-    /// view box position (x_offset:y_offset)
+    /// stop adding samples after this many seconds, the image averages the samples taken so far
     #[arg(long)]
-    pub view_box_position: Option<String>,
+    pub time_limit: Option<f32>,
 
-    /// view box size (width:height)
+    /// curve compressing the radiance of 8 bit images, which are then sRGB encoded
+    #[arg(long, value_enum, default_value_t = ToneMapper::Clamp)]
+    pub tone_mapper: ToneMapper,
+
+    /// camera up (x:y:z)
+    #[arg(long, allow_hyphen_values = true, value_parser = str_to_vec3)]
+    pub up: Option<glam::Vec3>,
+
+    /// camera vertical field of view in degrees
     #[arg(long)]
-    pub view_box_size: Option<String>,
+    pub vertical_fov: Option<f32>,
+
+    /// view box position (`x_offset:y_offset`)
+    #[arg(long, value_parser = str_to_vec2)]
+    pub view_box_position: Option<glam::UVec2>,
+
+    /// view box size (width:height)
+    #[arg(long, value_parser = str_to_vec2)]
+    pub view_box_size: Option<glam::UVec2>,
+}
+
+impl CliArgs {
+    /// Overrides the fields of `camera` which were given on the command line.
+    #[must_use]
+    pub fn camera(&self, camera: &Camera) -> Camera {
+        let (shutter_open, shutter_close) = self
+            .shutter
+            .unwrap_or((camera.shutter_open, camera.shutter_close));
+        Camera {
+            look_from: self.look_from.unwrap_or(camera.look_from),
+            look_at: self.look_at.unwrap_or(camera.look_at),
+            up: self.up.unwrap_or(camera.up),
+            vertical_fov: self.vertical_fov.unwrap_or(camera.vertical_fov),
+            aperture: self.aperture.unwrap_or(camera.aperture),
+            focus_distance: self.focus_distance.unwrap_or(camera.focus_distance),
+            shutter_open,
+            shutter_close,
+        }
+    }
+
+    /// Overrides `background` when one was given on the command line.
+    #[must_use]
+    pub fn background(&self, background: &Background) -> Background {
+        self.background
+            .clone()
+            .unwrap_or_else(|| background.clone())
+    }
+
+    #[must_use]
+    pub fn tone_mapping(&self) -> ToneMapping {
+        ToneMapping {
+            tone_mapper: self.tone_mapper,
+            exposure: self.exposure,
+        }
+    }
+
+    #[must_use]
+    pub fn gpu_options(&self) -> GpuOptions {
+        GpuOptions {
+            backends: self.backend.unwrap_or(wgpu::Backends::all()),
+            power_preference: match self.power_preference {
+                PowerPreference::Low => wgpu::PowerPreference::LowPower,
+                PowerPreference::High => wgpu::PowerPreference::HighPerformance,
+            },
+            force_fallback_adapter: self.force_fallback_adapter,
+            adapter_name: self.adapter.clone(),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// list the adapters available to the selected backends
+    ListAdapters,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Integrator {
+    /// follow bounces until they escape or hit a light
+    Naive,
+    /// also sample lights at diffuse bounces, weighted by multiple importance sampling
+    NextEventEstimation,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum PowerPreference {
+    Low,
+    High,
 }
 
+/// Parses the command line, exiting with an error when an image would be written in a format
+/// which is not supported, so that it is reported before anything is rendered.
 #[must_use]
 pub fn parse() -> CliArgs {
-    CliArgs::parse()
+    let cli = CliArgs::parse();
+
+    let aovs = cli
+        .aov
+        .iter()
+        .map(|aov| output::with_name(&cli.output, aov.name()));
+    let paths = std::iter::once(cli.output.clone())
+        .chain(cli.samples_heatmap.clone())
+        .chain(aovs);
+    for path in paths {
+        if OutputFormat::from_path(&path).is_err() {
+            CliArgs::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!(
+                        "unsupported image format of `{}` (expected png, ppm, ascii.ppm, exr, hdr or pfm)",
+                        path.display()
+                    ),
+                )
+                .exit();
+        }
+    }
+
+    cli
 }
 
-/// # Panics
+/// Parses a comma separated list of backend names, rejecting names wgpu does not know.
 ///
-/// Panics if value is not in format `u32:u32`.
-#[must_use]
-pub fn str_to_vec2(value: &str) -> glam::UVec2 {
-    let (x, y) = value.split_once(':').unwrap();
-    glam::UVec2 {
-        x: x.parse().unwrap(),
-        y: y.parse().unwrap(),
+/// # Errors
+///
+/// Returns an error naming the first unknown backend.
+pub fn str_to_backends(value: &str) -> Result<wgpu::Backends, String> {
+    value
+        .split(',')
+        .try_fold(wgpu::Backends::empty(), |backends, backend| {
+            Ok(backends
+                | match backend.trim().to_lowercase().as_str() {
+                    "vulkan" | "vk" => wgpu::Backends::VULKAN,
+                    "dx12" | "d3d12" => wgpu::Backends::DX12,
+                    "dx11" | "d3d11" => wgpu::Backends::DX11,
+                    "metal" | "mtl" => wgpu::Backends::METAL,
+                    "opengl" | "gles" | "gl" => wgpu::Backends::GL,
+                    "webgpu" => wgpu::Backends::BROWSER_WEBGPU,
+                    _ => {
+                        return Err(format!(
+                        "unknown backend `{}` (expected vulkan, metal, dx12, dx11, gl or webgpu)",
+                        backend.trim()
+                    ))
+                    }
+                })
+        })
+}
+
+/// Parses a background keyword, an .hdr or .exr environment path or an `r:g:b` colour.
+///
+/// # Errors
+///
+/// Returns an error if value is none of these.
+pub fn str_to_background(value: &str) -> Result<Background, String> {
+    Ok(match value {
+        "gradient" => Background::Gradient,
+        "black" => Background::Black,
+        "sky" => Background::sky(),
+        path if [".hdr", ".exr"]
+            .iter()
+            .any(|extension| path.to_lowercase().ends_with(extension)) =>
+        {
+            // made absolute so that it is not resolved against the directory of the scene file
+            Background::Environment {
+                path: std::path::absolute(path).map_err(|error| format!("`{path}`: {error}"))?,
+                rotation: 0.0,
+                intensity: 1.0,
+            }
+        }
+        color => Background::Solid {
+            color: str_to_vec3(color).map_err(|_| {
+                format!(
+                    "expected gradient, black, sky, an .hdr or .exr path or r:g:b, got `{value}`"
+                )
+            })?,
+        },
+    })
+}
+
+/// # Errors
+///
+/// Returns an error if value is not in format `u32:u32`.
+pub fn str_to_vec2(value: &str) -> Result<glam::UVec2, String> {
+    let (x, y) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected width:height, got `{value}`"))?;
+    Ok(glam::UVec2 {
+        x: x.trim()
+            .parse()
+            .map_err(|error| format!("`{x}`: {error}"))?,
+        y: y.trim()
+            .parse()
+            .map_err(|error| format!("`{y}`: {error}"))?,
+    })
+}
+
+/// # Errors
+///
+/// Returns an error if value is not in format `f32:f32:f32`.
+pub fn str_to_vec3(value: &str) -> Result<glam::Vec3, String> {
+    let values = value
+        .split(':')
+        .map(|v| v.trim().parse().map_err(|error| format!("`{v}`: {error}")))
+        .collect::<Result<Vec<f32>, _>>()?;
+    match values[..] {
+        [x, y, z] => Ok(glam::Vec3 { x, y, z }),
+        _ => Err(format!("expected x:y:z, got `{value}`")),
+    }
+}
+
+/// # Errors
+///
+/// Returns an error if value is not in format `f32:f32`.
+pub fn str_to_interval(value: &str) -> Result<(f32, f32), String> {
+    let (start, end) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected start:end, got `{value}`"))?;
+    Ok((
+        start
+            .trim()
+            .parse()
+            .map_err(|error| format!("`{start}`: {error}"))?,
+        end.trim()
+            .parse()
+            .map_err(|error| format!("`{end}`: {error}"))?,
+    ))
+}
+
+/// # Errors
+///
+/// Returns an error if value is not a number greater than zero.
+pub fn str_to_positive(value: &str) -> Result<f32, String> {
+    let number: f32 = value
+        .trim()
+        .parse()
+        .map_err(|error| format!("`{value}`: {error}"))?;
+    if number > 0.0 {
+        Ok(number)
+    } else {
+        Err(format!(
+            "expected a number greater than zero, got `{value}`"
+        ))
     }
 }
@@ -2,34 +2,88 @@ use std::sync::Arc;
 
 use crate::Error;
 
+#[derive(Clone, Debug)]
+pub struct GpuOptions {
+    /// backends the instance is allowed to use
+    pub backends: wgpu::Backends,
+
+    /// power preference used when no adapter name is given
+    pub power_preference: wgpu::PowerPreference,
+
+    /// only consider fallback (software) adapters
+    pub force_fallback_adapter: bool,
+
+    /// case insensitive substring the adapter name must contain
+    pub adapter_name: Option<String>,
+}
+
+impl Default for GpuOptions {
+    fn default() -> Self {
+        Self {
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            adapter_name: None,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct GPU {
+    adapter_info: wgpu::AdapterInfo,
     device: Arc<wgpu::Device>,
     queue: Arc<wgpu::Queue>,
 }
 
 impl GPU {
+    #[must_use]
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
+    }
+
     #[must_use]
     pub fn device(&self) -> &wgpu::Device {
         &self.device
     }
 
+    /// Lists every adapter available to the backends in `options`.
+    #[must_use]
+    pub fn adapters(options: &GpuOptions) -> Vec<wgpu::Adapter> {
+        Self::instance(options)
+            .enumerate_adapters(options.backends)
+            .collect()
+    }
+
     /// # Errors
     ///
     /// Will return `Err` if a GPU device cannot be found or a connection cannot be made.
-    pub async fn new() -> crate::Result<Self> {
+    pub async fn new(options: &GpuOptions) -> crate::Result<Self> {
         // create a wgpu instance
-        let instance = wgpu::Instance::default();
+        let instance = Self::instance(options);
 
         // create a handle to the graphics card
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: None,
-                force_fallback_adapter: false,
-            })
-            .await
-            .ok_or(Error::WgpuDeviceNotFound)?;
+        let adapter = match &options.adapter_name {
+            Some(adapter_name) => {
+                let adapter_name = adapter_name.to_lowercase();
+                instance
+                    .enumerate_adapters(options.backends)
+                    .find(|adapter| {
+                        let info = adapter.get_info();
+                        info.name.to_lowercase().contains(&adapter_name)
+                            && (!options.force_fallback_adapter
+                                || info.device_type == wgpu::DeviceType::Cpu)
+                    })
+                    .ok_or(Error::WgpuDeviceNotFound)?
+            }
+            None => instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: options.power_preference,
+                    compatible_surface: None,
+                    force_fallback_adapter: options.force_fallback_adapter,
+                })
+                .await
+                .ok_or(Error::WgpuDeviceNotFound)?,
+        };
 
         // create a connection to the graphics card
         let (device, queue) = adapter
@@ -39,7 +93,16 @@ impl GPU {
                     limits: if cfg!(target_arch = "wasm32") {
                         wgpu::Limits::downlevel_webgl2_defaults()
                     } else {
-                        wgpu::Limits::default()
+                        // the ray tracer binds a storage buffer for every kind of primitive, and
+                        // environment maps and images easily exceed the default buffer sizes
+                        let limits = adapter.limits();
+                        wgpu::Limits {
+                            max_storage_buffers_per_shader_stage: limits
+                                .max_storage_buffers_per_shader_stage,
+                            max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size,
+                            max_buffer_size: limits.max_buffer_size,
+                            ..wgpu::Limits::default()
+                        }
                     },
                     label: None,
                 },
@@ -49,11 +112,19 @@ impl GPU {
             .map_err(Error::WgpuRequestDeviceError)?;
 
         Ok(Self {
+            adapter_info: adapter.get_info(),
             device: Arc::new(device),
             queue: Arc::new(queue),
         })
     }
 
+    fn instance(options: &GpuOptions) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: options.backends,
+            ..Default::default()
+        })
+    }
+
     /// # Errors
     ///
     /// Will return `Err` if task failed to execute to completion.
@@ -2,11 +2,34 @@
 
 pub mod cli;
 pub mod gpu;
+pub mod obj;
+pub mod output;
+pub mod scene;
 pub mod shaders;
 
 #[derive(Debug)]
 pub enum Error {
+    Image(image::ImageError),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Obj(tobj::LoadError),
+    OutputFormat(std::path::PathBuf),
+    SceneFormat(std::path::PathBuf),
+    SceneObject(String),
+    SceneTexture(u32),
+    SceneVersion(u32),
+    TomlDe(toml::de::Error),
+    TomlSer(toml::ser::Error),
     Wgpu(wgpu::Error),
+    WgpuBufferSize {
+        label: &'static str,
+        size: u64,
+        limit: u64,
+    },
+    WgpuStorageBuffers {
+        required: u32,
+        limit: u32,
+    },
     WgpuDeviceNotFound,
     WgpuRequestDeviceError(wgpu::RequestDeviceError),
 }
@@ -1,31 +1,108 @@
 use chrono::Utc;
-use rand::{rngs::ThreadRng, Rng};
-use ray_tracing_in_one_weekend_webgpu::{cli, gpu, shaders::ray_tracer};
-use tokio::io::AsyncWriteExt;
+use ray_tracing_in_one_weekend_webgpu::{
+    cli, gpu, output,
+    scene::Scene,
+    shaders::{
+        denoiser,
+        ray_tracer::{self, bvh::Bvh},
+    },
+};
 
 #[tokio::main]
 async fn main() {
     let cli = cli::parse();
 
-    let input = ray_tracer::InputType {
+    let gpu_options = cli.gpu_options();
+    let tone_mapping = cli.tone_mapping();
+
+    if let Some(cli::Command::ListAdapters) = cli.command {
+        list_adapters(&gpu_options);
+        return;
+    }
+
+    let seed = cli.seed.unwrap_or_else(rand::random);
+
+    let mut scene = match &cli.scene {
+        Some(path) => {
+            println!("[{:?}] loading scene {path:?}", Utc::now().to_string());
+            Scene::load(path).await.unwrap()
+        }
+        None => Scene::random(seed),
+    };
+    scene.camera = cli.camera(&scene.camera);
+    scene.background = cli.background(&scene.background);
+
+    if let Some(path) = &cli.dump_scene {
+        println!("[{:?}] dumping scene {path:?}", Utc::now().to_string());
+        scene.save(path).await.unwrap();
+    }
+
+    let mesh = scene.meshes().unwrap();
+    let (textures, texels) = scene.textures().unwrap();
+
+    let mut input = ray_tracer::InputType {
         samples_per_pixel: cli.samples_per_pixel,
-        screen_size: cli::str_to_vec2(&cli.screen_size),
-        view_box_position: cli
-            .view_box_position
-            .map_or(glam::UVec2 { x: 0, y: 0 }, |f| cli::str_to_vec2(&f)),
-        view_box_size: cli.view_box_size.map_or_else(
-            || cli::str_to_vec2(&cli.screen_size),
-            |f| cli::str_to_vec2(&f),
-        ),
-        spheres: random_scene(),
+        noise_threshold: cli.noise_threshold,
+        max_samples: cli
+            .max_samples
+            .unwrap_or(cli.samples_per_pixel.saturating_mul(4)),
+        samples_per_dispatch: cli.samples_per_dispatch,
+        time_limit: cli.time_limit.map(std::time::Duration::from_secs_f32),
+        max_depth: cli.max_depth,
+        russian_roulette_depth: cli.russian_roulette,
+        seed,
+        screen_size: cli.screen_size,
+        view_box_position: cli.view_box_position.unwrap_or(glam::UVec2 { x: 0, y: 0 }),
+        view_box_size: cli.view_box_size.unwrap_or(cli.screen_size),
+        camera: (&scene.camera).into(),
+        background: (&scene.background).into(),
+        environment: scene.environment().unwrap(),
+        spheres: scene.spheres(),
+        vertices: mesh.vertices,
+        triangles: mesh.triangles,
+        planes: scene.planes(),
+        quads: scene.quads(),
+        disks: scene.disks(),
+        volumes: scene.volumes(),
+        textures,
+        texels,
+        objects: Vec::new(),
+        instances: Vec::new(),
+        bvh: None,
+        next_event_estimation: cli.integrator == cli::Integrator::NextEventEstimation,
+        lights: Vec::new(),
     };
-    let chunk_size = cli::str_to_vec2(&cli.chunk_size);
+    scene.add_instances(&mut input).unwrap();
+    input.lights = ray_tracer::lights(&input);
+    if !cli.brute_force {
+        input.bvh = Some(Bvh::new(&input));
+    }
+    let chunk_size = cli.chunk_size;
 
     println!(
         "[{:?}] samples per pixel {:?}",
         Utc::now().to_string(),
         input.samples_per_pixel
     );
+    println!(
+        "[{:?}] noise threshold {:?} (max samples {:?})",
+        Utc::now().to_string(),
+        input.noise_threshold,
+        input.max_samples
+    );
+    println!(
+        "[{:?}] samples per dispatch {:?} (time limit {:?})",
+        Utc::now().to_string(),
+        input.samples_per_dispatch,
+        input.time_limit
+    );
+    println!(
+        "[{:?}] max depth {:?} (russian roulette after {:?})",
+        Utc::now().to_string(),
+        input.max_depth,
+        input.russian_roulette_depth
+    );
+    println!("[{:?}] seed {:?}", Utc::now().to_string(), input.seed);
     println!(
         "[{:?}] screen size {:?}",
         Utc::now().to_string(),
@@ -41,143 +118,119 @@ async fn main() {
         Utc::now().to_string(),
         input.view_box_size
     );
+    println!("[{:?}] camera {:?}", Utc::now().to_string(), input.camera);
+    println!(
+        "[{:?}] background {:?}",
+        Utc::now().to_string(),
+        scene.background
+    );
+    println!(
+        "[{:?}] triangles {:?}",
+        Utc::now().to_string(),
+        input.triangles.len()
+    );
+    println!(
+        "[{:?}] integrator {:?} ({} lights)",
+        Utc::now().to_string(),
+        cli.integrator,
+        input.lights.len()
+    );
+    println!(
+        "[{:?}] bvh nodes {:?}",
+        Utc::now().to_string(),
+        input.bvh.as_ref().map(|bvh| bvh.nodes.len())
+    );
     println!("[{:?}] output {:?}", Utc::now().to_string(), cli.output);
+    println!(
+        "[{:?}] tone mapping {:?}",
+        Utc::now().to_string(),
+        tone_mapping
+    );
 
-    let gpu = gpu::GPU::new().await.unwrap();
-    let shader = ray_tracer::Shader::new(gpu);
+    let gpu = if cli.cpu {
+        None
+    } else {
+        let gpu = gpu::GPU::new(&gpu_options).await.unwrap();
+        println!(
+            "[{:?}] adapter {:?} ({:?})",
+            Utc::now().to_string(),
+            gpu.adapter_info().name,
+            gpu.adapter_info().backend
+        );
+        Some(gpu)
+    };
 
-    let output = shader.execute_in_chunks(&input, chunk_size).await;
+    let output = if let Some(gpu) = &gpu {
+        let shader = ray_tracer::Shader::new(gpu.clone()).unwrap();
 
-    println!("[{:?}] saving image", Utc::now().to_string());
-    let mut file = tokio::fs::File::create("image.ppm").await.unwrap();
-    file.write_all(
-        format!(
-            "P3\n{} {}\n255\n",
-            input.view_box_size.x, input.view_box_size.y
-        )
-        .as_bytes(),
-    )
-    .await
-    .unwrap();
-    for y in (0..input.view_box_size.y).rev() {
-        for x in 0..input.view_box_size.x {
-            let index = y * input.view_box_size.x + x;
-            let pixel = output.pixels[index as usize];
-            file.write_all(format!("{} {} {}\n", pixel.x, pixel.y, pixel.z).as_bytes())
-                .await
-                .unwrap();
+        shader.execute_in_chunks(&input, chunk_size).await.unwrap()
+    } else {
+        let shader = ray_tracer::cpu::Shader::new();
+
+        shader.execute(&input)
+    };
+
+    let pixels = if cli.denoise {
+        let denoiser_input = denoiser::InputType {
+            size: input.view_box_size,
+            pixels: output.pixels.clone(),
+            aovs: output.aovs.clone(),
+            ..Default::default()
+        };
+
+        if let Some(gpu) = gpu {
+            let shader = denoiser::Shader::new(gpu);
+
+            shader.execute(&denoiser_input).await.pixels
+        } else {
+            let shader = denoiser::cpu::Shader::new();
+
+            shader.execute(&denoiser_input).pixels
         }
-    }
-    file.flush().await.unwrap();
+    } else {
+        output.pixels
+    };
+
+    println!("[{:?}] saving image", Utc::now().to_string());
+    output::write(&cli.output, input.view_box_size, &pixels, &tone_mapping)
+        .await
+        .unwrap();
     println!("[{:?}] saved image", Utc::now().to_string());
-}
 
-fn random_scene() -> Vec<ray_tracer::InputTypeSphere> {
-    let mut rng = rand::thread_rng();
-
-    let mut spheres = Vec::new();
-
-    // ground
-    spheres.push(ray_tracer::InputTypeSphere {
-        center: glam::Vec3 {
-            x: 0.0,
-            y: -1000.0,
-            z: 0.0,
-        },
-        radius: 1000.0,
-        material: ray_tracer::InputTypeMaterial::new_lambertian(glam::Vec3 {
-            x: 0.5,
-            y: 0.5,
-            z: 0.5,
-        }),
-    });
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat: f32 = rng.gen();
-            let center = glam::Vec3 {
-                x: a as f32 + 0.9 * rng.gen::<f32>(),
-                y: 0.2,
-                z: b as f32 + 0.9 * rng.gen::<f32>(),
-            };
-
-            if (center
-                - glam::Vec3 {
-                    x: 4.0,
-                    y: 0.2,
-                    z: 0.0,
-                })
-            .length()
-                > 0.9
-            {
-                let material = if choose_mat < 0.8 {
-                    let albedo = random_vec3(&mut rng) * random_vec3(&mut rng);
-                    ray_tracer::InputTypeMaterial::new_lambertian(albedo)
-                } else if choose_mat < 0.95 {
-                    let albedo = random_vec3(&mut rng);
-                    let fuzz = rng.gen();
-                    ray_tracer::InputTypeMaterial::new_metal(albedo, fuzz)
-                } else {
-                    ray_tracer::InputTypeMaterial::new_dielectric(1.5)
-                };
-
-                spheres.push(ray_tracer::InputTypeSphere {
-                    center,
-                    radius: 0.2,
-                    material,
-                })
-            }
-        }
+    if let Some(path) = &cli.samples_heatmap {
+        output::write_data(path, input.view_box_size, &output::heatmap(&output.samples))
+            .await
+            .unwrap();
+        println!("[{:?}] saved samples heatmap", Utc::now().to_string());
     }
 
-    spheres.push(ray_tracer::InputTypeSphere {
-        center: glam::Vec3 {
-            x: 0.0,
-            y: 1.0,
-            z: 0.0,
-        },
-        radius: 1.0,
-        material: ray_tracer::InputTypeMaterial::new_dielectric(1.5),
-    });
-
-    spheres.push(ray_tracer::InputTypeSphere {
-        center: glam::Vec3 {
-            x: -4.0,
-            y: 1.0,
-            z: 0.0,
-        },
-        radius: 1.0,
-        material: ray_tracer::InputTypeMaterial::new_lambertian(glam::Vec3 {
-            x: 0.4,
-            y: 0.2,
-            z: 0.1,
-        }),
-    });
-
-    spheres.push(ray_tracer::InputTypeSphere {
-        center: glam::Vec3 {
-            x: 4.0,
-            y: 1.0,
-            z: 0.0,
-        },
-        radius: 1.0,
-        material: ray_tracer::InputTypeMaterial::new_metal(
-            glam::Vec3 {
-                x: 0.7,
-                y: 0.6,
-                z: 0.5,
-            },
-            0.0,
-        ),
-    });
-
-    spheres
+    for aov in &cli.aov {
+        let path = output::with_name(&cli.output, aov.name());
+        output::write_aov(&path, input.view_box_size, *aov, &output.aovs)
+            .await
+            .unwrap();
+        println!(
+            "[{:?}] saved {} aov {:?}",
+            Utc::now().to_string(),
+            aov.name(),
+            path
+        );
+    }
 }
 
-fn random_vec3(rng: &mut ThreadRng) -> glam::Vec3 {
-    glam::Vec3 {
-        x: rng.gen(),
-        y: rng.gen(),
-        z: rng.gen(),
+fn list_adapters(options: &gpu::GpuOptions) {
+    for (index, adapter) in gpu::GPU::adapters(options).iter().enumerate() {
+        let info = adapter.get_info();
+        println!("[{index}] {} ({:?})", info.name, info.backend);
+        println!("    device type: {:?}", info.device_type);
+        println!(
+            "    vendor: {:#06x}, device: {:#06x}",
+            info.vendor, info.device
+        );
+        println!("    driver: {} {}", info.driver, info.driver_info);
+        println!(
+            "    limits: {}",
+            format!("{:#?}", adapter.limits()).replace('\n', "\n    ")
+        );
     }
 }
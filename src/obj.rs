@@ -0,0 +1,189 @@
+use std::{io::BufRead, path::Path};
+
+use crate::{shaders::ray_tracer, Error};
+
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub vertices: Vec<ray_tracer::InputTypeVertex>,
+    pub triangles: Vec<ray_tracer::InputTypeTriangle>,
+}
+
+impl Mesh {
+    /// Appends `other`, offsetting its indices past the vertices already in the mesh.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn append(&mut self, other: Mesh) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices);
+        self.triangles
+            .extend(
+                other
+                    .triangles
+                    .into_iter()
+                    .map(|triangle| ray_tracer::InputTypeTriangle {
+                        indices: triangle.indices + offset,
+                        ..triangle
+                    }),
+            );
+    }
+}
+
+/// Loads a Wavefront OBJ file, along with the MTL files it references.
+///
+/// Every triangle uses `material` when given, otherwise the MTL material of its face.
+///
+/// # Errors
+///
+/// Will return `Err` if the OBJ or MTL files cannot be read or parsed.
+pub fn load(path: &Path, material: Option<&ray_tracer::InputTypeMaterial>) -> crate::Result<Mesh> {
+    let (models, materials) = tobj::load_obj(path, &load_options()).map_err(Error::Obj)?;
+    Ok(to_mesh(&models, &materials.map_err(Error::Obj)?, material))
+}
+
+/// Parses a Wavefront OBJ file, `material_loader` is called for every MTL file it references.
+///
+/// # Errors
+///
+/// Will return `Err` if the OBJ or MTL files cannot be parsed.
+pub fn parse<B, ML>(
+    reader: &mut B,
+    material_loader: ML,
+    material: Option<&ray_tracer::InputTypeMaterial>,
+) -> crate::Result<Mesh>
+where
+    B: BufRead,
+    ML: Fn(&Path) -> tobj::MTLLoadResult,
+{
+    let (models, materials) =
+        tobj::load_obj_buf(reader, &load_options(), material_loader).map_err(Error::Obj)?;
+    Ok(to_mesh(&models, &materials.map_err(Error::Obj)?, material))
+}
+
+fn load_options() -> tobj::LoadOptions {
+    tobj::LoadOptions {
+        single_index: true,
+        triangulate: true,
+        ignore_points: true,
+        ignore_lines: true,
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn to_mesh(
+    models: &[tobj::Model],
+    materials: &[tobj::Material],
+    material: Option<&ray_tracer::InputTypeMaterial>,
+) -> Mesh {
+    let mut mesh = Mesh::default();
+
+    for model in models {
+        let positions = model.mesh.positions.chunks_exact(3);
+        let normals = model
+            .mesh
+            .normals
+            .chunks_exact(3)
+            .map(glam::Vec3::from_slice)
+            .chain(std::iter::repeat(glam::Vec3::ZERO));
+
+        let material = material.cloned().unwrap_or_else(|| {
+            model
+                .mesh
+                .material_id
+                .and_then(|material_id| materials.get(material_id))
+                .map_or_else(
+                    || ray_tracer::InputTypeMaterial::new_lambertian(glam::Vec3::splat(0.5)),
+                    to_material,
+                )
+        });
+
+        mesh.append(Mesh {
+            vertices: positions
+                .zip(normals)
+                .map(|(position, normal)| ray_tracer::InputTypeVertex {
+                    position: glam::Vec3::from_slice(position),
+                    normal,
+                })
+                .collect(),
+            triangles: model
+                .mesh
+                .indices
+                .chunks_exact(3)
+                .map(|indices| ray_tracer::InputTypeTriangle {
+                    indices: glam::UVec3::from_slice(indices),
+                    material: material.clone(),
+                })
+                .collect(),
+        });
+    }
+
+    mesh
+}
+
+/// Maps an MTL material onto the closest material the shader supports.
+fn to_material(material: &tobj::Material) -> ray_tracer::InputTypeMaterial {
+    let diffuse = material
+        .diffuse
+        .map_or(glam::Vec3::splat(0.5), glam::Vec3::from);
+    let specular = material.specular.map_or(glam::Vec3::ZERO, glam::Vec3::from);
+    let emissive = material.emissive.map_or(glam::Vec3::ZERO, glam::Vec3::from);
+
+    if emissive.max_element() > 0.0 {
+        return ray_tracer::InputTypeMaterial::new_diffuse_light(emissive);
+    }
+
+    // transparent or refractive illumination models
+    if material.dissolve.is_some_and(|dissolve| dissolve < 1.0)
+        || matches!(material.illumination_model, Some(4 | 6 | 7 | 9))
+    {
+        return ray_tracer::InputTypeMaterial::new_dielectric(
+            material.optical_density.unwrap_or(1.5),
+        );
+    }
+
+    // reflective illumination models
+    if matches!(material.illumination_model, Some(3 | 5 | 8)) && specular.max_element() > 0.0 {
+        // approximate the roughness of the phong exponent
+        let shininess = material.shininess.unwrap_or(0.0).max(0.0);
+        let fuzz = (2.0 / (shininess + 2.0)).sqrt();
+        return ray_tracer::InputTypeMaterial::new_metal(specular, fuzz);
+    }
+
+    ray_tracer::InputTypeMaterial::new_lambertian(diffuse)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::parse;
+
+    #[test]
+    fn test() {
+        let mesh = parse(
+            &mut Cursor::new(
+                "mtllib box.mtl\n\
+                 v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                 usemtl red\n\
+                 f 1 2 3 4\n",
+            ),
+            |_| tobj::load_mtl_buf(&mut Cursor::new("newmtl red\nKd 0.8 0.1 0.1\nillum 2\n")),
+            None,
+        )
+        .unwrap();
+
+        // the quad is triangulated
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.triangles.len(), 2);
+        assert_eq!(mesh.triangles[1].indices, glam::UVec3::new(0, 2, 3));
+        assert_eq!(mesh.vertices[2].position, glam::Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(mesh.vertices[2].normal, glam::Vec3::ZERO);
+        assert_eq!(
+            format!("{:?}", mesh.triangles[0].material),
+            format!(
+                "{:?}",
+                crate::shaders::ray_tracer::InputTypeMaterial::new_lambertian(glam::Vec3::new(
+                    0.8, 0.1, 0.1
+                ))
+            )
+        );
+    }
+}
@@ -0,0 +1,705 @@
+use std::{
+    fmt::Write,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use crate::{shaders::ray_tracer::OutputTypeAov, Error};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// openexr, linear radiance
+    Exr,
+    /// radiance rgbe, linear radiance
+    Hdr,
+    /// portable float map, linear radiance
+    Pfm,
+    /// portable network graphics
+    Png,
+    /// binary portable pixmap (P6)
+    Ppm,
+    /// ascii portable pixmap (P3)
+    PpmAscii,
+}
+
+impl OutputFormat {
+    /// Picks the format from the extension of `path`, `.ascii.ppm` selects ascii portable pixmap.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the extension is not supported.
+    pub fn from_path(path: &Path) -> crate::Result<Self> {
+        let extension = |path: &Path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .map(str::to_lowercase)
+        };
+
+        match extension(path).as_deref() {
+            Some("exr") => Ok(Self::Exr),
+            Some("hdr") => Ok(Self::Hdr),
+            Some("pfm") => Ok(Self::Pfm),
+            Some("png") => Ok(Self::Png),
+            Some("ppm") => match path.file_stem().map(Path::new).and_then(extension) {
+                Some(stem_extension) if stem_extension == "ascii" => Ok(Self::PpmAscii),
+                _ => Ok(Self::Ppm),
+            },
+            _ => Err(Error::OutputFormat(path.to_path_buf())),
+        }
+    }
+
+    /// Whether the format stores 32 bit floats rather than 8 bit colours.
+    #[must_use]
+    pub fn is_float(self) -> bool {
+        matches!(self, Self::Exr | Self::Hdr | Self::Pfm)
+    }
+}
+
+/// Curve compressing linear radiance into the displayable range of 8 bit images.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToneMapper {
+    /// cut off everything above one
+    #[default]
+    Clamp,
+    /// x / (1 + x) of every channel
+    Reinhard,
+    /// filmic fit of the academy color encoding system reference rendering transform
+    Aces,
+    /// filmic curve which desaturates bright colours towards white
+    Agx,
+}
+
+impl ToneMapper {
+    /// Maps linear radiance to linear display values in the range 0..1.
+    #[must_use]
+    pub fn apply(self, color: glam::Vec3) -> glam::Vec3 {
+        let color = color.max(glam::Vec3::ZERO);
+        match self {
+            Self::Clamp => color,
+            Self::Reinhard => color / (color + 1.0),
+            Self::Aces => aces(color),
+            Self::Agx => agx(color),
+        }
+        .clamp(glam::Vec3::ZERO, glam::Vec3::ONE)
+    }
+}
+
+/// Stephen Hill's fit of the ACES reference rendering and output device transforms.
+fn aces(color: glam::Vec3) -> glam::Vec3 {
+    let input = glam::Mat3::from_cols_array_2d(&[
+        [0.597_19, 0.354_58, 0.048_23],
+        [0.076_00, 0.908_34, 0.015_66],
+        [0.028_40, 0.133_83, 0.837_77],
+    ])
+    .transpose();
+    let output = glam::Mat3::from_cols_array_2d(&[
+        [1.604_75, -0.531_08, -0.073_67],
+        [-0.102_08, 1.108_13, -0.006_05],
+        [-0.003_27, -0.072_76, 1.076_02],
+    ])
+    .transpose();
+
+    let v = input * color;
+    let v = (v * (v + 0.024_578_6) - 0.000_090_537) / (v * (0.983_729 * v + 0.432_951) + 0.238_081);
+    output * v
+}
+
+/// Benjamin Wrensch's polynomial approximation of the `AgX` curve, without a look applied.
+fn agx(color: glam::Vec3) -> glam::Vec3 {
+    const MIN_EV: f32 = -12.473_931;
+    const MAX_EV: f32 = 4.026_069;
+
+    let inset = glam::Mat3::from_cols_array_2d(&[
+        [0.842_479_06, 0.042_328_24, 0.042_375_655],
+        [0.078_433_6, 0.878_468_6, 0.078_433_6],
+        [0.079_223_745, 0.079_166_13, 0.879_143],
+    ]);
+    let outset = glam::Mat3::from_cols_array_2d(&[
+        [1.196_879, -0.052_896_852, -0.052_971_635],
+        [-0.098_020_88, 1.151_903_1, -0.098_043_45],
+        [-0.099_029_74, -0.098_961_18, 1.151_073_7],
+    ]);
+
+    // encode in log space between the darkest and brightest stops the curve covers
+    let x = (inset * color).max(glam::Vec3::splat(1e-10));
+    let x = (glam::Vec3::new(x.x.log2(), x.y.log2(), x.z.log2())
+        .clamp(glam::Vec3::splat(MIN_EV), glam::Vec3::splat(MAX_EV))
+        - MIN_EV)
+        / (MAX_EV - MIN_EV);
+
+    // sigmoid contrast curve
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    let x =
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.002_32;
+
+    // back to linear values
+    let x = (outset * x).max(glam::Vec3::ZERO);
+    glam::Vec3::new(x.x.powf(2.2), x.y.powf(2.2), x.z.powf(2.2))
+}
+
+/// sRGB opto-electronic transfer function, encodes a linear display value in 0..1.
+#[must_use]
+pub fn srgb_oetf(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// sRGB electro-optical transfer function, decodes an encoded value in 0..1 to a linear one.
+#[must_use]
+pub fn srgb_eotf(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// How linear radiance is turned into the colours of 8 bit images.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMapping {
+    pub tone_mapper: ToneMapper,
+
+    /// Stops the radiance is scaled by before tone mapping, +1 doubles it.
+    pub exposure: f32,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            tone_mapper: ToneMapper::default(),
+            exposure: 0.0,
+        }
+    }
+}
+
+impl ToneMapping {
+    /// Exposes, tone maps and sRGB encodes linear radiance.
+    #[must_use]
+    pub fn apply(&self, color: glam::Vec3) -> glam::Vec3 {
+        let color = self.tone_mapper.apply(color * self.exposure.exp2());
+        glam::Vec3::new(srgb_oetf(color.x), srgb_oetf(color.y), srgb_oetf(color.z))
+    }
+}
+
+/// Auxiliary images of the first hit of every pixel, see [`OutputTypeAov`].
+///
+/// Float images hold the values themselves, 8 bit images colours showing them.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aov {
+    /// shading normal (8 bit: mapped from -1..1 to 0..1)
+    Normal,
+    /// reflectance of the material
+    Albedo,
+    /// distance to the camera (8 bit: the nearest hit is white and the farthest black)
+    Depth,
+    /// material type, zero for the background (8 bit: a colour for each type)
+    Material,
+    /// primitive type and index in red and green (8 bit: a colour for each primitive)
+    Primitive,
+}
+
+impl Aov {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Albedo => "albedo",
+            Self::Depth => "depth",
+            Self::Material => "material",
+            Self::Primitive => "primitive",
+        }
+    }
+
+    /// Values of the `aovs` of every pixel, for float images.
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn values(self, aovs: &[OutputTypeAov]) -> Vec<glam::Vec4> {
+        aovs.iter()
+            .map(|aov| {
+                let value = match self {
+                    // escaped camera rays have no normal or primitive
+                    Self::Normal | Self::Primitive if aov.material == 0 => glam::Vec3::ZERO,
+                    Self::Normal => aov.normal,
+                    Self::Albedo => aov.albedo,
+                    Self::Depth => glam::Vec3::splat(aov.depth),
+                    Self::Material => glam::Vec3::splat(aov.material as f32),
+                    Self::Primitive => {
+                        glam::Vec3::new(aov.primitive.type_ as f32, aov.primitive.index as f32, 0.0)
+                    }
+                };
+                value.extend(1.0)
+            })
+            .collect()
+    }
+
+    /// Colours of the `aovs` of every pixel, in the range 0..1 for 8 bit images.
+    #[must_use]
+    pub fn pixels(self, aovs: &[OutputTypeAov]) -> Vec<glam::Vec4> {
+        let hits = aovs.iter().filter(|aov| aov.depth > 0.0);
+        let near = hits.clone().map(|aov| aov.depth).fold(f32::MAX, f32::min);
+        let far = hits.map(|aov| aov.depth).fold(0.0, f32::max);
+
+        aovs.iter()
+            .map(|aov| {
+                let color = match self {
+                    // escaped camera rays have no normal or primitive
+                    Self::Normal | Self::Primitive if aov.material == 0 => glam::Vec3::ZERO,
+                    Self::Normal => aov.normal * 0.5 + 0.5,
+                    Self::Albedo => aov.albedo,
+                    Self::Depth if aov.depth > 0.0 => {
+                        glam::Vec3::splat(1.0 - (aov.depth - near) / (far - near).max(f32::EPSILON))
+                    }
+                    Self::Depth => glam::Vec3::ZERO,
+                    Self::Material => match aov.material {
+                        1 => glam::Vec3::new(0.9, 0.6, 0.2),
+                        2 => glam::Vec3::new(0.2, 0.6, 0.9),
+                        3 => glam::Vec3::new(0.3, 0.9, 0.5),
+                        4 => glam::Vec3::new(1.0, 1.0, 0.6),
+                        5 => glam::Vec3::new(0.7, 0.5, 0.9),
+                        _ => glam::Vec3::ZERO,
+                    },
+                    Self::Primitive => hash_color(aov.primitive.type_, aov.primitive.index),
+                };
+                color.extend(1.0)
+            })
+            .collect()
+    }
+}
+
+/// A colour which tells neighbouring indices apart, never too dark to be mistaken for the background.
+fn hash_color(type_: u32, index: u32) -> glam::Vec3 {
+    let mut hash = (index + 1).wrapping_mul(0x9e37_79b9) ^ (type_ + 1).wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x7feb_352d);
+    hash ^= hash >> 15;
+    glam::Vec3::new(
+        f32::from((hash & 0xff) as u8),
+        f32::from((hash >> 8 & 0xff) as u8),
+        f32::from((hash >> 16 & 0xff) as u8),
+    ) / 255.0
+        * 0.8
+        + 0.2
+}
+
+/// Inserts `name` before the extension of `path`, `out/image.png` becomes `out/image.normal.png`.
+#[must_use]
+pub fn with_name(path: &Path, name: &str) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    // keep ascii portable pixmaps recognisable
+    let split = if file_name.to_lowercase().ends_with(".ascii.ppm") {
+        Some(file_name.len() - ".ascii.ppm".len())
+    } else {
+        file_name.rfind('.')
+    };
+
+    let file_name = match split {
+        Some(split) => format!("{}.{name}{}", &file_name[..split], &file_name[split..]),
+        None => format!("{file_name}.{name}"),
+    };
+    path.with_file_name(file_name)
+}
+
+/// Writes the pixels of the shader output to `path`, creating parent directories as needed.
+///
+/// The shader stores the bottom row first, images are written top row first. 8 bit images are
+/// tone mapped, float images keep the linear radiance.
+///
+/// # Errors
+///
+/// Will return `Err` if the format is not supported, or the image cannot be encoded or written.
+pub async fn write(
+    path: &Path,
+    size: glam::UVec2,
+    pixels: &[glam::Vec4],
+    tone_mapping: &ToneMapping,
+) -> crate::Result<()> {
+    let format = OutputFormat::from_path(path)?;
+    write_contents(path, encode(format, size, pixels, tone_mapping)?).await
+}
+
+/// Writes pixels which hold data rather than radiance, such as an [`Aov`], like [`write`].
+///
+/// 8 bit images store the values without tone mapping.
+///
+/// # Errors
+///
+/// Will return `Err` if the format is not supported, or the image cannot be encoded or written.
+pub async fn write_data(
+    path: &Path,
+    size: glam::UVec2,
+    pixels: &[glam::Vec4],
+) -> crate::Result<()> {
+    let format = OutputFormat::from_path(path)?;
+    write_contents(path, encode_data(format, size, pixels)?).await
+}
+
+/// Writes `aov` of the `aovs` of every pixel like [`write_data`], as [`Aov::values`] to float
+/// images and as [`Aov::pixels`] to 8 bit images.
+///
+/// # Errors
+///
+/// Will return `Err` if the format is not supported, or the image cannot be encoded or written.
+pub async fn write_aov(
+    path: &Path,
+    size: glam::UVec2,
+    aov: Aov,
+    aovs: &[OutputTypeAov],
+) -> crate::Result<()> {
+    let format = OutputFormat::from_path(path)?;
+    let pixels = if format.is_float() {
+        aov.values(aovs)
+    } else {
+        aov.pixels(aovs)
+    };
+    write_contents(path, encode_data(format, size, &pixels)?).await
+}
+
+async fn write_contents(path: &Path, contents: Vec<u8>) -> crate::Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        tokio::fs::create_dir_all(parent).await.map_err(Error::Io)?;
+    }
+    tokio::fs::write(path, contents).await.map_err(Error::Io)
+}
+
+/// # Errors
+///
+/// Will return `Err` if the image cannot be encoded.
+pub fn encode(
+    format: OutputFormat,
+    size: glam::UVec2,
+    pixels: &[glam::Vec4],
+    tone_mapping: &ToneMapping,
+) -> crate::Result<Vec<u8>> {
+    encode_with(format, size, pixels, Some(tone_mapping))
+}
+
+/// Like [`encode`], without tone mapping 8 bit images.
+///
+/// # Errors
+///
+/// Will return `Err` if the image cannot be encoded.
+pub fn encode_data(
+    format: OutputFormat,
+    size: glam::UVec2,
+    pixels: &[glam::Vec4],
+) -> crate::Result<Vec<u8>> {
+    encode_with(format, size, pixels, None)
+}
+
+fn encode_with(
+    format: OutputFormat,
+    size: glam::UVec2,
+    pixels: &[glam::Vec4],
+    tone_mapping: Option<&ToneMapping>,
+) -> crate::Result<Vec<u8>> {
+    match format {
+        OutputFormat::Exr => {
+            let mut contents = Cursor::new(Vec::new());
+            image::ImageEncoder::write_image(
+                image::codecs::openexr::OpenExrEncoder::new(&mut contents),
+                &to_ne_bytes(&to_rgb32f(size, pixels, true)),
+                size.x,
+                size.y,
+                image::ExtendedColorType::Rgb32F,
+            )
+            .map_err(Error::Image)?;
+            Ok(contents.into_inner())
+        }
+        OutputFormat::Hdr => {
+            let mut contents = Vec::new();
+            image::ImageEncoder::write_image(
+                image::codecs::hdr::HdrEncoder::new(&mut contents),
+                &to_ne_bytes(&to_rgb32f(size, pixels, true)),
+                size.x,
+                size.y,
+                image::ExtendedColorType::Rgb32F,
+            )
+            .map_err(Error::Image)?;
+            Ok(contents)
+        }
+        OutputFormat::Pfm => {
+            // a negative scale marks little endian, rows are stored bottom row first
+            let mut contents = format!("PF\n{} {}\n-1.0\n", size.x, size.y).into_bytes();
+            for value in to_rgb32f(size, pixels, false) {
+                contents.extend_from_slice(&value.to_le_bytes());
+            }
+            Ok(contents)
+        }
+        OutputFormat::Png => {
+            let mut contents = Vec::new();
+            image::ImageEncoder::write_image(
+                image::codecs::png::PngEncoder::new(&mut contents),
+                &to_rgb8(size, pixels, tone_mapping),
+                size.x,
+                size.y,
+                image::ExtendedColorType::Rgb8,
+            )
+            .map_err(Error::Image)?;
+            Ok(contents)
+        }
+        OutputFormat::Ppm => {
+            let mut contents = format!("P6\n{} {}\n255\n", size.x, size.y).into_bytes();
+            contents.extend_from_slice(&to_rgb8(size, pixels, tone_mapping));
+            Ok(contents)
+        }
+        OutputFormat::PpmAscii => {
+            let mut contents = format!("P3\n{} {}\n255\n", size.x, size.y);
+            for pixel in to_rgb8(size, pixels, tone_mapping).chunks_exact(3) {
+                writeln!(contents, "{} {} {}", pixel[0], pixel[1], pixel[2]).unwrap();
+            }
+            Ok(contents.into_bytes())
+        }
+    }
+}
+
+/// Colours the number of samples taken of each pixel from black through red and yellow to white,
+/// relative to the pixel which took the most.
+///
+/// The colours are data, to be written with [`write_data`].
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn heatmap(samples: &[u32]) -> Vec<glam::Vec4> {
+    let max = samples.iter().copied().max().unwrap_or(0).max(1) as f32;
+    samples
+        .iter()
+        .map(|&samples| {
+            let t = 3.0 * samples as f32 / max;
+            glam::Vec3::new(t, t - 1.0, t - 2.0)
+                .clamp(glam::Vec3::ZERO, glam::Vec3::ONE)
+                .extend(1.0)
+        })
+        .collect()
+}
+
+/// Tone maps when `tone_mapping` is set, clamps and quantises linear radiance, top row first.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn to_rgb8(
+    size: glam::UVec2,
+    pixels: &[glam::Vec4],
+    tone_mapping: Option<&ToneMapping>,
+) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity((size.x * size.y * 3) as usize);
+    for y in (0..size.y).rev() {
+        for x in 0..size.x {
+            let color = pixels[(y * size.x + x) as usize].truncate();
+            let color = tone_mapping.map_or(color, |tone_mapping| tone_mapping.apply(color));
+            for value in color.to_array() {
+                bytes.push((255.999 * value.clamp(0.0, 0.999)) as u8);
+            }
+        }
+    }
+    bytes
+}
+
+/// Linear radiance, top row first when `flip` is set.
+fn to_rgb32f(size: glam::UVec2, pixels: &[glam::Vec4], flip: bool) -> Vec<f32> {
+    let mut values = Vec::with_capacity((size.x * size.y * 3) as usize);
+    for row in 0..size.y {
+        let y = if flip { size.y - 1 - row } else { row };
+        for x in 0..size.x {
+            values.extend_from_slice(&pixels[(y * size.x + x) as usize].truncate().to_array());
+        }
+    }
+    values
+}
+
+fn to_ne_bytes(values: &[f32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_ne_bytes())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{
+        encode, encode_data, heatmap, srgb_eotf, srgb_oetf, with_name, Aov, OutputFormat,
+        ToneMapper, ToneMapping,
+    };
+    use crate::shaders::ray_tracer::{InputTypePrimitive, OutputTypeAov};
+
+    #[test]
+    fn format() {
+        assert_eq!(
+            OutputFormat::from_path(Path::new("out/image.PNG")).unwrap(),
+            OutputFormat::Png
+        );
+        assert_eq!(
+            OutputFormat::from_path(Path::new("image.ascii.ppm")).unwrap(),
+            OutputFormat::PpmAscii
+        );
+        assert!(OutputFormat::from_path(Path::new("image.bmp")).is_err());
+    }
+
+    #[test]
+    fn encode_image() {
+        // bottom row first in, top row first out
+        let size = glam::UVec2::new(1, 2);
+        let pixels = [glam::Vec4::new(0.0, 0.25, 1.0, 1.0), glam::Vec4::ONE * 4.0];
+        let tone_mapping = ToneMapping::default();
+
+        assert_eq!(
+            encode(OutputFormat::Ppm, size, &pixels, &tone_mapping).unwrap(),
+            b"P6\n1 2\n255\n\xff\xff\xff\x00\x89\xff"
+        );
+        assert_eq!(
+            encode(OutputFormat::PpmAscii, size, &pixels, &tone_mapping).unwrap(),
+            b"P3\n1 2\n255\n255 255 255\n0 137 255\n"
+        );
+        assert!(encode(OutputFormat::Png, size, &pixels, &tone_mapping)
+            .unwrap()
+            .starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn encode_float() {
+        let size = glam::UVec2::new(1, 2);
+        let pixels = [glam::Vec4::new(0.0, 0.25, 1.0, 1.0), glam::Vec4::ONE * 4.0];
+        let tone_mapping = ToneMapping::default();
+
+        // radiance above one survives in float formats
+        let pfm = encode(OutputFormat::Pfm, size, &pixels, &tone_mapping).unwrap();
+        assert!(pfm.starts_with(b"PF\n1 2\n-1.0\n"));
+        assert!(pfm.ends_with(&4.0_f32.to_le_bytes()));
+        assert!(encode(OutputFormat::Exr, size, &pixels, &tone_mapping)
+            .unwrap()
+            .starts_with(b"\x76\x2f\x31\x01"));
+        assert!(encode(OutputFormat::Hdr, size, &pixels, &tone_mapping)
+            .unwrap()
+            .starts_with(b"#?RADIANCE"));
+    }
+
+    #[test]
+    fn samples_heatmap() {
+        // the most sampled pixel is white, unsampled pixels are black
+        assert_eq!(
+            heatmap(&[0, 4, 12]),
+            [
+                glam::Vec4::W,
+                glam::Vec4::new(1.0, 0.0, 0.0, 1.0),
+                glam::Vec4::ONE
+            ]
+        );
+    }
+
+    #[test]
+    fn aovs() {
+        let size = glam::UVec2::new(1, 2);
+        let pixels = [glam::Vec4::new(0.0, 0.25, 1.0, 1.0), glam::Vec4::ONE * 4.0];
+
+        // data is not tone mapped
+        assert_eq!(
+            encode_data(OutputFormat::Ppm, size, &pixels).unwrap(),
+            b"P6\n1 2\n255\n\xff\xff\xff\x00\x3f\xff"
+        );
+        assert_eq!(
+            with_name(Path::new("out/image.png"), "normal"),
+            Path::new("out/image.normal.png")
+        );
+        assert_eq!(
+            with_name(Path::new("image.ascii.ppm"), "depth"),
+            Path::new("image.depth.ascii.ppm")
+        );
+
+        let aovs = [
+            OutputTypeAov::default(),
+            OutputTypeAov {
+                normal: -glam::Vec3::Z,
+                depth: 12.5,
+                albedo: glam::Vec3::splat(0.5),
+                material: 2,
+                primitive: InputTypePrimitive {
+                    type_: InputTypePrimitive::TRIANGLE,
+                    index: 300,
+                },
+            },
+        ];
+
+        // float images hold the values, 8 bit images colours in range
+        assert_eq!(
+            Aov::Normal.values(&aovs)[1],
+            glam::Vec4::new(0.0, 0.0, -1.0, 1.0)
+        );
+        assert_eq!(
+            Aov::Depth.values(&aovs)[1],
+            glam::Vec4::new(12.5, 12.5, 12.5, 1.0)
+        );
+        assert_eq!(
+            Aov::Material.values(&aovs)[1],
+            glam::Vec4::new(2.0, 2.0, 2.0, 1.0)
+        );
+        assert_eq!(
+            Aov::Primitive.values(&aovs)[1],
+            glam::Vec4::new(1.0, 300.0, 0.0, 1.0)
+        );
+        assert_eq!(Aov::Primitive.values(&aovs)[0], glam::Vec4::W);
+        for aov in [
+            Aov::Normal,
+            Aov::Albedo,
+            Aov::Depth,
+            Aov::Material,
+            Aov::Primitive,
+        ] {
+            assert!(aov
+                .pixels(&aovs)
+                .iter()
+                .all(|pixel| pixel.cmpge(glam::Vec4::ZERO).all()
+                    && pixel.cmple(glam::Vec4::ONE).all()));
+        }
+        assert!(OutputFormat::Exr.is_float() && !OutputFormat::Png.is_float());
+    }
+
+    #[test]
+    fn srgb() {
+        // the sRGB curve is linear near black and meets the ends of the range
+        assert!((srgb_oetf(0.001) - 0.012_92).abs() < 1e-6);
+        assert!((srgb_oetf(1.0) - 1.0).abs() < 1e-6);
+        for value in [0.001, 0.2, 0.5, 1.0] {
+            assert!((srgb_eotf(srgb_oetf(value)) - value).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn tone_mapping() {
+        // exposure scales the radiance before tone mapping, every curve stays in range and in order
+        let reinhard = ToneMapping {
+            tone_mapper: ToneMapper::Reinhard,
+            exposure: 1.0,
+        };
+        assert!(reinhard
+            .apply(glam::Vec3::splat(0.5))
+            .abs_diff_eq(glam::Vec3::splat(srgb_oetf(0.5)), 1e-6));
+        for tone_mapper in [
+            ToneMapper::Clamp,
+            ToneMapper::Reinhard,
+            ToneMapper::Aces,
+            ToneMapper::Agx,
+        ] {
+            let values = [0.0, 0.01, 0.1, 0.5, 1.0, 4.0, 100.0]
+                .map(|value| tone_mapper.apply(glam::Vec3::splat(value)).x);
+            assert!(values[0] < 0.01, "{tone_mapper:?} {values:?}");
+            assert!(
+                values.windows(2).all(|pair| pair[0] <= pair[1]),
+                "{tone_mapper:?} {values:?}"
+            );
+            assert!(
+                values[6] <= 1.0 && values[6] > 0.9,
+                "{tone_mapper:?} {values:?}"
+            );
+        }
+    }
+}
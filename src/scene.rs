@@ -0,0 +1,1250 @@
+use std::path::{Path, PathBuf};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{obj, shaders::ray_tracer, Error};
+
+/// Version of the scene file format written by this build.
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneFormat {
+    Json,
+    Toml,
+}
+
+impl SceneFormat {
+    /// # Errors
+    ///
+    /// Will return `Err` if the extension of `path` is not `json` or `toml`.
+    pub fn from_path(path: &Path) -> crate::Result<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => Ok(Self::Json),
+            Some(extension) if extension.eq_ignore_ascii_case("toml") => Ok(Self::Toml),
+            _ => Err(Error::SceneFormat(path.to_path_buf())),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub version: u32,
+
+    #[serde(default)]
+    pub camera: Camera,
+
+    #[serde(default)]
+    pub background: Background,
+
+    #[serde(default)]
+    pub spheres: Vec<Sphere>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub planes: Vec<Plane>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quads: Vec<Quad>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disks: Vec<Disk>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub boxes: Vec<Cuboid>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub volumes: Vec<Volume>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub textures: Vec<Texture>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub meshes: Vec<Mesh>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub objects: Vec<Object>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub instances: Vec<Instance>,
+
+    /// Directory relative asset paths are resolved against when they are read, the directory
+    /// of the scene file.
+    #[serde(skip)]
+    pub directory: PathBuf,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Camera {
+    pub look_from: glam::Vec3,
+    pub look_at: glam::Vec3,
+    pub up: glam::Vec3,
+    pub vertical_fov: f32,
+    pub aperture: f32,
+    pub focus_distance: f32,
+
+    /// Moving spheres are at their `center` at time 0 and their `end_center` at time 1, the
+    /// shutter stays open in between these two times.
+    pub shutter_open: f32,
+    pub shutter_close: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            look_from: glam::Vec3::new(13.0, 2.0, 3.0),
+            look_at: glam::Vec3::new(0.0, 0.0, 0.0),
+            up: glam::Vec3::new(0.0, 1.0, 0.0),
+            vertical_fov: 20.0,
+            aperture: 0.1,
+            focus_distance: 10.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+}
+
+/// Radiance of rays which leave the scene.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Background {
+    /// white at the horizon to light blue overhead
+    #[default]
+    Gradient,
+    Solid {
+        color: glam::Vec3,
+    },
+    Black,
+    /// equirectangular `.hdr` or `.exr` image, relative paths are resolved against the directory
+    /// of the scene file
+    Environment {
+        path: PathBuf,
+
+        /// degrees the image is turned by around the up axis
+        #[serde(default)]
+        rotation: f32,
+
+        /// multiplies the radiance of the image
+        #[serde(default = "default_intensity")]
+        intensity: f32,
+    },
+    /// analytic daylight sky with a sun disk, and the ground below the horizon
+    Sky {
+        /// towards the sun
+        #[serde(default = "default_sun_direction")]
+        sun_direction: glam::Vec3,
+
+        /// haziness, from 2 for a clear sky to 10
+        #[serde(default = "default_turbidity")]
+        turbidity: f32,
+
+        /// reflectance of the ground, lit by the sky and the sun
+        #[serde(default = "default_ground_albedo")]
+        ground_albedo: glam::Vec3,
+    },
+}
+
+impl Background {
+    /// A sky with the sun in the afternoon.
+    #[must_use]
+    pub fn sky() -> Self {
+        Self::Sky {
+            sun_direction: default_sun_direction(),
+            turbidity: default_turbidity(),
+            ground_albedo: default_ground_albedo(),
+        }
+    }
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+fn default_sun_direction() -> glam::Vec3 {
+    glam::Vec3::new(-0.4, 0.6, 0.7)
+}
+
+fn default_turbidity() -> f32 {
+    3.0
+}
+
+fn default_ground_albedo() -> glam::Vec3 {
+    glam::Vec3::splat(0.3)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sphere {
+    pub center: glam::Vec3,
+    pub radius: f32,
+    pub material: Material,
+
+    /// Where the sphere has moved to from `center` at time 1, blurred over the camera shutter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_center: Option<glam::Vec3>,
+}
+
+/// Infinite plane through `point`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Plane {
+    pub point: glam::Vec3,
+    pub normal: glam::Vec3,
+    pub material: Material,
+}
+
+/// Parallelogram with the corners `corner`, `corner + u`, `corner + u + v` and `corner + v`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Quad {
+    pub corner: glam::Vec3,
+    pub u: glam::Vec3,
+    pub v: glam::Vec3,
+    pub material: Material,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Disk {
+    pub center: glam::Vec3,
+    pub normal: glam::Vec3,
+    pub radius: f32,
+    pub material: Material,
+}
+
+/// Axis-aligned box between the corners `min` and `max`, made of six quads facing outwards.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cuboid {
+    pub min: glam::Vec3,
+    pub max: glam::Vec3,
+    pub material: Material,
+}
+
+impl Cuboid {
+    #[must_use]
+    pub fn quads(&self) -> [ray_tracer::InputTypeQuad; 6] {
+        let (min, max) = (self.min.min(self.max), self.min.max(self.max));
+        let dx = glam::Vec3::new(max.x - min.x, 0.0, 0.0);
+        let dy = glam::Vec3::new(0.0, max.y - min.y, 0.0);
+        let dz = glam::Vec3::new(0.0, 0.0, max.z - min.z);
+        let material: ray_tracer::InputTypeMaterial = (&self.material).into();
+
+        [
+            // front, right, back, left, top and bottom
+            (glam::Vec3::new(min.x, min.y, max.z), dx, dy),
+            (glam::Vec3::new(max.x, min.y, max.z), -dz, dy),
+            (glam::Vec3::new(max.x, min.y, min.z), -dx, dy),
+            (min, dz, dy),
+            (glam::Vec3::new(min.x, max.y, max.z), dx, -dz),
+            (min, dx, dz),
+        ]
+        .map(|(corner, u, v)| ray_tracer::InputTypeQuad {
+            corner,
+            u,
+            v,
+            material: material.clone(),
+        })
+    }
+}
+
+/// Fog or smoke filling `boundary`, scattering light equally in every direction.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Volume {
+    pub boundary: Boundary,
+
+    /// Chance of scattering per unit of distance, thin fog is around 0.01 and thick smoke 1.
+    pub density: f32,
+
+    pub albedo: glam::Vec3,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Boundary {
+    Sphere { center: glam::Vec3, radius: f32 },
+    Box { min: glam::Vec3, max: glam::Vec3 },
+}
+
+/// A Wavefront OBJ file, relative paths are resolved against the directory of the scene file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Mesh {
+    pub path: PathBuf,
+
+    /// Overrides the MTL materials of every face.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<Material>,
+
+    #[serde(default)]
+    pub translation: glam::Vec3,
+
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+/// Primitives in a space of their own, which are only drawn where instances place them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Object {
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spheres: Vec<Sphere>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quads: Vec<Quad>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disks: Vec<Disk>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub boxes: Vec<Cuboid>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub meshes: Vec<Mesh>,
+}
+
+/// Places the object named `object` in the world, scaled, then rotated and then translated.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Instance {
+    pub object: String,
+
+    #[serde(default)]
+    pub translation: glam::Vec3,
+
+    /// Degrees around the x, then the y and then the z axis.
+    #[serde(default)]
+    pub rotation: glam::Vec3,
+
+    #[serde(default = "default_instance_scale")]
+    pub scale: glam::Vec3,
+}
+
+fn default_instance_scale() -> glam::Vec3 {
+    glam::Vec3::ONE
+}
+
+impl Instance {
+    #[must_use]
+    pub fn object_to_world(&self) -> glam::Mat4 {
+        let rotation = glam::Quat::from_euler(
+            glam::EulerRot::ZYX,
+            self.rotation.z.to_radians(),
+            self.rotation.y.to_radians(),
+            self.rotation.x.to_radians(),
+        );
+        glam::Mat4::from_scale_rotation_translation(self.scale, rotation, self.translation)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Material {
+    Lambertian {
+        #[serde(default = "default_albedo")]
+        albedo: glam::Vec3,
+
+        /// Index into `Scene::textures` the albedo is multiplied by.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        texture: Option<u32>,
+    },
+    Metal {
+        #[serde(default = "default_albedo")]
+        albedo: glam::Vec3,
+
+        fuzz: f32,
+
+        /// Index into `Scene::textures` the albedo is multiplied by.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        texture: Option<u32>,
+    },
+    Dielectric {
+        index_of_refraction: f32,
+    },
+    DiffuseLight {
+        emit: glam::Vec3,
+    },
+}
+
+impl Material {
+    fn texture(&self) -> Option<u32> {
+        match *self {
+            Self::Lambertian { texture, .. } | Self::Metal { texture, .. } => texture,
+            Self::Dielectric { .. } | Self::DiffuseLight { .. } => None,
+        }
+    }
+}
+
+fn default_albedo() -> glam::Vec3 {
+    glam::Vec3::ONE
+}
+
+/// Colour varying over the surfaces of the materials referring to it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Texture {
+    Solid {
+        color: glam::Vec3,
+    },
+    /// Cubes `1 / scale` across in space, alternating between `even` and `odd`.
+    Checker {
+        even: glam::Vec3,
+        odd: glam::Vec3,
+        #[serde(default = "default_scale")]
+        scale: f32,
+    },
+    /// Squares `1 / scale` across over the uv coordinates, alternating between `even` and `odd`.
+    UvChecker {
+        even: glam::Vec3,
+        odd: glam::Vec3,
+        #[serde(default = "default_scale")]
+        scale: f32,
+    },
+    /// Perlin noise, with features about `1 / scale` apart.
+    Noise {
+        #[serde(default = "default_albedo")]
+        color: glam::Vec3,
+        #[serde(default = "default_scale")]
+        scale: f32,
+    },
+    /// Perlin noise summed over `octaves`, each with features half as far apart as the one before.
+    Turbulence {
+        #[serde(default = "default_albedo")]
+        color: glam::Vec3,
+        #[serde(default = "default_scale")]
+        scale: f32,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+    },
+    /// A PNG or JPEG file, relative paths are resolved against the directory of the scene file.
+    Image {
+        path: PathBuf,
+    },
+}
+
+fn default_octaves() -> u32 {
+    7
+}
+
+impl Scene {
+    /// # Errors
+    ///
+    /// Will return `Err` if the file cannot be read, parsed or has an unsupported version.
+    pub async fn load(path: &Path) -> crate::Result<Self> {
+        let format = SceneFormat::from_path(path)?;
+        let contents = tokio::fs::read_to_string(path).await.map_err(Error::Io)?;
+        let mut scene = Self::parse(&contents, format)?;
+        scene.directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(scene)
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the contents cannot be parsed or has an unsupported version.
+    pub fn parse(contents: &str, format: SceneFormat) -> crate::Result<Self> {
+        let scene: Self = match format {
+            SceneFormat::Json => serde_json::from_str(contents).map_err(Error::Json)?,
+            SceneFormat::Toml => toml::from_str(contents).map_err(Error::TomlDe)?,
+        };
+
+        if scene.version != VERSION {
+            return Err(Error::SceneVersion(scene.version));
+        }
+
+        Ok(scene)
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the scene cannot be serialized or the file cannot be written.
+    pub async fn save(&self, path: &Path) -> crate::Result<()> {
+        let contents = self.to_string(SceneFormat::from_path(path)?)?;
+        tokio::fs::write(path, contents).await.map_err(Error::Io)
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the scene cannot be serialized.
+    pub fn to_string(&self, format: SceneFormat) -> crate::Result<String> {
+        match format {
+            SceneFormat::Json => serde_json::to_string_pretty(self).map_err(Error::Json),
+            SceneFormat::Toml => toml::to_string(self).map_err(Error::TomlSer),
+        }
+    }
+
+    #[must_use]
+    pub fn spheres(&self) -> Vec<ray_tracer::InputTypeSphere> {
+        self.spheres.iter().map(Into::into).collect()
+    }
+
+    #[must_use]
+    pub fn planes(&self) -> Vec<ray_tracer::InputTypePlane> {
+        self.planes.iter().map(Into::into).collect()
+    }
+
+    /// Every quad of the scene, followed by the sides of every box.
+    #[must_use]
+    pub fn quads(&self) -> Vec<ray_tracer::InputTypeQuad> {
+        self.quads
+            .iter()
+            .map(Into::into)
+            .chain(self.boxes.iter().flat_map(Cuboid::quads))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn disks(&self) -> Vec<ray_tracer::InputTypeDisk> {
+        self.disks.iter().map(Into::into).collect()
+    }
+
+    #[must_use]
+    pub fn volumes(&self) -> Vec<ray_tracer::InputTypeVolume> {
+        self.volumes.iter().map(Into::into).collect()
+    }
+
+    /// Loads the image of every texture into a single list of sRGB texels, packed as rgba8.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an image cannot be read or decoded, or a material refers to a texture
+    /// which does not exist.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn textures(&self) -> crate::Result<(Vec<ray_tracer::InputTypeTexture>, Vec<u32>)> {
+        if let Some(texture) = self
+            .materials()
+            .into_iter()
+            .filter_map(Material::texture)
+            .find(|&texture| texture as usize >= self.textures.len())
+        {
+            return Err(Error::SceneTexture(texture));
+        }
+
+        let mut texels = Vec::new();
+        let textures = self
+            .textures
+            .iter()
+            .map(|texture| {
+                Ok(match *texture {
+                    Texture::Solid { color } => ray_tracer::InputTypeTexture::new_solid(color),
+                    Texture::Checker { even, odd, scale } => {
+                        ray_tracer::InputTypeTexture::new_checker(even, odd, scale)
+                    }
+                    Texture::UvChecker { even, odd, scale } => {
+                        ray_tracer::InputTypeTexture::new_uv_checker(even, odd, scale)
+                    }
+                    Texture::Noise { color, scale } => {
+                        ray_tracer::InputTypeTexture::new_noise(color, scale)
+                    }
+                    Texture::Turbulence {
+                        color,
+                        scale,
+                        octaves,
+                    } => ray_tracer::InputTypeTexture::new_turbulence(color, scale, octaves),
+                    Texture::Image { ref path } => {
+                        let image = image::open(self.directory.join(path))
+                            .map_err(Error::Image)?
+                            .into_rgba8();
+                        let offset = texels.len() as u32;
+                        texels.extend(image.pixels().map(|pixel| u32::from_le_bytes(pixel.0)));
+                        ray_tracer::InputTypeTexture::new_image(
+                            glam::UVec2::new(image.width(), image.height()),
+                            offset,
+                        )
+                    }
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        Ok((textures, texels))
+    }
+
+    /// Material of every primitive of the scene and of its objects, and of meshes overriding theirs.
+    fn materials(&self) -> Vec<&Material> {
+        let groups = std::iter::once((
+            &self.spheres,
+            &self.quads,
+            &self.disks,
+            &self.boxes,
+            &self.meshes,
+        ))
+        .chain(self.objects.iter().map(|object| {
+            (
+                &object.spheres,
+                &object.quads,
+                &object.disks,
+                &object.boxes,
+                &object.meshes,
+            )
+        }));
+
+        let mut materials = self
+            .planes
+            .iter()
+            .map(|plane| &plane.material)
+            .collect::<Vec<_>>();
+        for (spheres, quads, disks, boxes, meshes) in groups {
+            materials.extend(spheres.iter().map(|sphere| &sphere.material));
+            materials.extend(quads.iter().map(|quad| &quad.material));
+            materials.extend(disks.iter().map(|disk| &disk.material));
+            materials.extend(boxes.iter().map(|cuboid| &cuboid.material));
+            materials.extend(meshes.iter().filter_map(|mesh| mesh.material.as_ref()));
+        }
+        materials
+    }
+
+    /// Loads every mesh of the scene into a single vertex and triangle list.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an OBJ or MTL file cannot be read or parsed.
+    pub fn meshes(&self) -> crate::Result<obj::Mesh> {
+        load_meshes(&self.directory, &self.meshes)
+    }
+
+    /// Appends the primitives of every object to `input`, along with the instances placing them.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an OBJ or MTL file cannot be read or parsed, or an instance names an
+    /// object which does not exist.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn add_instances(&self, input: &mut ray_tracer::InputType) -> crate::Result<()> {
+        for object in &self.objects {
+            let mut primitives = Vec::new();
+            let mut reference = |type_, index: usize| {
+                primitives.push(ray_tracer::InputTypePrimitive {
+                    type_,
+                    index: index as u32,
+                });
+            };
+
+            for sphere in &object.spheres {
+                reference(ray_tracer::InputTypePrimitive::SPHERE, input.spheres.len());
+                input.spheres.push(sphere.into());
+            }
+
+            let boxes = object.boxes.iter().flat_map(Cuboid::quads);
+            for quad in object.quads.iter().map(Into::into).chain(boxes) {
+                reference(ray_tracer::InputTypePrimitive::QUAD, input.quads.len());
+                input.quads.push(quad);
+            }
+
+            for disk in &object.disks {
+                reference(ray_tracer::InputTypePrimitive::DISK, input.disks.len());
+                input.disks.push(disk.into());
+            }
+
+            let mesh = load_meshes(&self.directory, &object.meshes)?;
+            let offset = glam::UVec3::splat(input.vertices.len() as u32);
+            for mut triangle in mesh.triangles {
+                reference(
+                    ray_tracer::InputTypePrimitive::TRIANGLE,
+                    input.triangles.len(),
+                );
+                triangle.indices += offset;
+                input.triangles.push(triangle);
+            }
+            input.vertices.extend(mesh.vertices);
+
+            input
+                .objects
+                .push(ray_tracer::InputTypeObject { primitives });
+        }
+
+        for instance in &self.instances {
+            let object = self
+                .objects
+                .iter()
+                .position(|object| object.name == instance.object)
+                .ok_or_else(|| Error::SceneObject(instance.object.clone()))?;
+            input.instances.push(ray_tracer::InputTypeInstance::new(
+                object as u32,
+                instance.object_to_world(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Loads the image of an environment background, which is empty for other backgrounds.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the image cannot be read or decoded.
+    pub fn environment(&self) -> crate::Result<ray_tracer::InputTypeEnvironment> {
+        let Background::Environment { path, .. } = &self.background else {
+            return Ok(ray_tracer::InputTypeEnvironment::default());
+        };
+
+        let image = image::open(self.directory.join(path))
+            .map_err(Error::Image)?
+            .into_rgb32f();
+        let radiance = image
+            .pixels()
+            .map(|pixel| glam::Vec3::from_array(pixel.0))
+            .collect::<Vec<_>>();
+
+        Ok(ray_tracer::InputTypeEnvironment::new(
+            glam::UVec2::new(image.width(), image.height()),
+            &radiance,
+        ))
+    }
+
+    /// The final scene of Ray Tracing in One Weekend, the same `seed` always generates the same scene.
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn random(seed: u32) -> Self {
+        let mut rng = StdRng::seed_from_u64(u64::from(seed));
+
+        let mut spheres = Vec::new();
+
+        let ground = Plane {
+            point: glam::Vec3::ZERO,
+            normal: glam::Vec3::Y,
+            material: Material::Lambertian {
+                albedo: glam::Vec3::new(0.5, 0.5, 0.5),
+                texture: None,
+            },
+        };
+
+        for a in -11..11 {
+            for b in -11..11 {
+                let choose_mat: f32 = rng.gen();
+                let center = glam::Vec3::new(
+                    a as f32 + 0.9 * rng.gen::<f32>(),
+                    0.2,
+                    b as f32 + 0.9 * rng.gen::<f32>(),
+                );
+
+                if (center - glam::Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                    let material = if choose_mat < 0.8 {
+                        Material::Lambertian {
+                            albedo: random_vec3(&mut rng) * random_vec3(&mut rng),
+                            texture: None,
+                        }
+                    } else if choose_mat < 0.95 {
+                        Material::Metal {
+                            albedo: random_vec3(&mut rng),
+                            fuzz: rng.gen(),
+                            texture: None,
+                        }
+                    } else {
+                        Material::Dielectric {
+                            index_of_refraction: 1.5,
+                        }
+                    };
+
+                    spheres.push(Sphere {
+                        center,
+                        radius: 0.2,
+                        material,
+                        end_center: None,
+                    });
+                }
+            }
+        }
+
+        spheres.push(Sphere {
+            center: glam::Vec3::new(0.0, 1.0, 0.0),
+            radius: 1.0,
+            material: Material::Dielectric {
+                index_of_refraction: 1.5,
+            },
+            end_center: None,
+        });
+
+        spheres.push(Sphere {
+            center: glam::Vec3::new(-4.0, 1.0, 0.0),
+            radius: 1.0,
+            material: Material::Lambertian {
+                albedo: glam::Vec3::new(0.4, 0.2, 0.1),
+                texture: None,
+            },
+            end_center: None,
+        });
+
+        spheres.push(Sphere {
+            center: glam::Vec3::new(4.0, 1.0, 0.0),
+            radius: 1.0,
+            material: Material::Metal {
+                albedo: glam::Vec3::new(0.7, 0.6, 0.5),
+                fuzz: 0.0,
+                texture: None,
+            },
+            end_center: None,
+        });
+
+        Self {
+            version: VERSION,
+            camera: Camera::default(),
+            background: Background::default(),
+            spheres,
+            planes: vec![ground],
+            quads: Vec::new(),
+            disks: Vec::new(),
+            boxes: Vec::new(),
+            volumes: Vec::new(),
+            textures: Vec::new(),
+            meshes: Vec::new(),
+            objects: Vec::new(),
+            instances: Vec::new(),
+            directory: PathBuf::new(),
+        }
+    }
+}
+
+/// Loads `meshes` into a single vertex and triangle list, resolving their paths against `directory`.
+fn load_meshes(directory: &Path, meshes: &[Mesh]) -> crate::Result<obj::Mesh> {
+    let mut loaded_meshes = obj::Mesh::default();
+
+    for mesh in meshes {
+        let material = mesh.material.as_ref().map(Into::into);
+        let mut loaded = obj::load(&directory.join(&mesh.path), material.as_ref())?;
+
+        for vertex in &mut loaded.vertices {
+            vertex.position = vertex.position * mesh.scale + mesh.translation;
+        }
+
+        loaded_meshes.append(loaded);
+    }
+
+    Ok(loaded_meshes)
+}
+
+impl From<&Camera> for ray_tracer::InputTypeCamera {
+    fn from(value: &Camera) -> Self {
+        Self {
+            look_from: value.look_from,
+            look_at: value.look_at,
+            up: value.up,
+            vertical_fov: value.vertical_fov,
+            aperture: value.aperture,
+            focus_distance: value.focus_distance,
+            shutter_open: value.shutter_open,
+            shutter_close: value.shutter_close,
+        }
+    }
+}
+
+impl From<&Background> for ray_tracer::InputTypeBackground {
+    fn from(value: &Background) -> Self {
+        match *value {
+            Background::Gradient => Self::new_gradient(),
+            Background::Solid { color } => Self::new_solid(color),
+            Background::Black => Self::new_black(),
+            Background::Environment {
+                rotation,
+                intensity,
+                ..
+            } => Self::new_environment(intensity, rotation.to_radians()),
+            Background::Sky {
+                sun_direction,
+                turbidity,
+                ground_albedo,
+            } => Self::new_sky(ray_tracer::InputTypeSky::new(
+                sun_direction,
+                turbidity,
+                ground_albedo,
+            )),
+        }
+    }
+}
+
+impl From<&Sphere> for ray_tracer::InputTypeSphere {
+    fn from(value: &Sphere) -> Self {
+        Self {
+            center: value.center,
+            radius: value.radius,
+            end_center: value.end_center.unwrap_or(value.center),
+            material: (&value.material).into(),
+        }
+    }
+}
+
+impl From<&Plane> for ray_tracer::InputTypePlane {
+    fn from(value: &Plane) -> Self {
+        Self {
+            point: value.point,
+            normal: value.normal,
+            material: (&value.material).into(),
+        }
+    }
+}
+
+impl From<&Quad> for ray_tracer::InputTypeQuad {
+    fn from(value: &Quad) -> Self {
+        Self {
+            corner: value.corner,
+            u: value.u,
+            v: value.v,
+            material: (&value.material).into(),
+        }
+    }
+}
+
+impl From<&Disk> for ray_tracer::InputTypeDisk {
+    fn from(value: &Disk) -> Self {
+        Self {
+            center: value.center,
+            radius: value.radius,
+            normal: value.normal,
+            material: (&value.material).into(),
+        }
+    }
+}
+
+impl From<&Volume> for ray_tracer::InputTypeVolume {
+    fn from(value: &Volume) -> Self {
+        match value.boundary {
+            Boundary::Sphere { center, radius } => {
+                Self::new_sphere(center, radius, value.density, value.albedo)
+            }
+            Boundary::Box { min, max } => Self::new_box(min, max, value.density, value.albedo),
+        }
+    }
+}
+
+impl From<&Material> for ray_tracer::InputTypeMaterial {
+    fn from(value: &Material) -> Self {
+        let material = match *value {
+            Material::Lambertian { albedo, .. } => Self::new_lambertian(albedo),
+            Material::Metal { albedo, fuzz, .. } => Self::new_metal(albedo, fuzz),
+            Material::Dielectric {
+                index_of_refraction,
+            } => Self::new_dielectric(index_of_refraction),
+            Material::DiffuseLight { emit } => Self::new_diffuse_light(emit),
+        };
+        match value.texture() {
+            Some(texture) => material.with_texture(texture),
+            None => material,
+        }
+    }
+}
+
+fn random_vec3(rng: &mut StdRng) -> glam::Vec3 {
+    glam::Vec3 {
+        x: rng.gen(),
+        y: rng.gen(),
+        z: rng.gen(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Background, Boundary, Error, Material, Scene, SceneFormat};
+    use crate::shaders::ray_tracer;
+
+    #[test]
+    fn parse() {
+        let scene = Scene::parse(
+            r#"
+            version = 1
+
+            [camera]
+            look_from = [0.0, 0.0, 5.0]
+
+            [[spheres]]
+            center = [0.0, 0.0, -1.0]
+            radius = 0.5
+            material = { type = "metal", albedo = [0.8, 0.6, 0.2], fuzz = 0.3 }
+            "#,
+            SceneFormat::Toml,
+        )
+        .unwrap();
+
+        assert_eq!(scene.camera.look_from, glam::Vec3::new(0.0, 0.0, 5.0));
+        assert!((scene.camera.vertical_fov - 20.0).abs() < f32::EPSILON);
+        assert_eq!(
+            scene.spheres[0].material,
+            Material::Metal {
+                albedo: glam::Vec3::new(0.8, 0.6, 0.2),
+                fuzz: 0.3,
+                texture: None
+            }
+        );
+
+        assert!(Scene::parse("version = 0", SceneFormat::Toml).is_err());
+    }
+
+    #[test]
+    fn background() {
+        let scene = Scene::parse(
+            r#"
+            version = 1
+
+            [background]
+            type = "solid"
+            color = [0.1, 0.1, 0.1]
+
+            [[spheres]]
+            center = [0.0, 2.0, -1.0]
+            radius = 0.5
+            material = { type = "diffuse_light", emit = [4.0, 4.0, 4.0] }
+            "#,
+            SceneFormat::Toml,
+        )
+        .unwrap();
+        assert_eq!(
+            scene.background,
+            Background::Solid {
+                color: glam::Vec3::splat(0.1)
+            }
+        );
+        assert_eq!(
+            scene.spheres[0].material,
+            Material::DiffuseLight {
+                emit: glam::Vec3::splat(4.0)
+            }
+        );
+        // the gradient is the default
+        assert_eq!(
+            Scene::parse("version = 1", SceneFormat::Toml)
+                .unwrap()
+                .background,
+            Background::Gradient
+        );
+    }
+
+    #[test]
+    fn random() {
+        let scene = Scene::random(0);
+        assert_eq!(Scene::random(0), scene);
+        for format in [SceneFormat::Json, SceneFormat::Toml] {
+            let contents = scene.to_string(format).unwrap();
+            assert_eq!(Scene::parse(&contents, format).unwrap(), scene);
+        }
+    }
+
+    #[test]
+    fn environment() {
+        let scene = Scene::parse(
+            r#"
+            version = 1
+
+            [background]
+            type = "environment"
+            path = "studio.hdr"
+            rotation = 90.0
+            "#,
+            SceneFormat::Toml,
+        )
+        .unwrap();
+        assert_eq!(
+            scene.background,
+            Background::Environment {
+                path: "studio.hdr".into(),
+                rotation: 90.0,
+                intensity: 1.0
+            }
+        );
+    }
+
+    #[test]
+    fn sky() {
+        let scene = Scene::parse(
+            r#"
+            version = 1
+
+            [background]
+            type = "sky"
+            turbidity = 5.0
+            "#,
+            SceneFormat::Toml,
+        )
+        .unwrap();
+        assert_eq!(
+            scene.background,
+            Background::Sky {
+                sun_direction: glam::Vec3::new(-0.4, 0.6, 0.7),
+                turbidity: 5.0,
+                ground_albedo: glam::Vec3::splat(0.3)
+            }
+        );
+    }
+
+    #[test]
+    fn primitives() {
+        let scene = Scene::parse(
+            r#"
+            version = 1
+
+            [[boxes]]
+            min = [0.0, 0.0, 0.0]
+            max = [1.0, 2.0, 3.0]
+            material = { type = "lambertian", albedo = [0.5, 0.5, 0.5] }
+
+            [[disks]]
+            center = [0.0, 5.0, 0.0]
+            normal = [0.0, -1.0, 0.0]
+            radius = 1.0
+            material = { type = "diffuse_light", emit = [4.0, 4.0, 4.0] }
+            "#,
+            SceneFormat::Toml,
+        )
+        .unwrap();
+        assert!((scene.disks[0].radius - 1.0).abs() < f32::EPSILON);
+        // the sides of a box face outwards
+        let quads = scene.quads();
+        assert_eq!(quads.len(), 6);
+        let center = glam::Vec3::new(0.5, 1.0, 1.5);
+        for quad in &quads {
+            let middle = quad.corner + (quad.u + quad.v) * 0.5;
+            assert!(quad.u.cross(quad.v).dot(middle - center) > 0.0);
+        }
+    }
+
+    #[test]
+    fn instances() {
+        let mut scene = Scene::parse(
+            r#"
+            version = 1
+
+            [[objects]]
+            name = "pair"
+
+            [[objects.spheres]]
+            center = [0.0, 0.0, 0.0]
+            radius = 0.5
+            material = { type = "lambertian", albedo = [0.5, 0.5, 0.5] }
+
+            [[objects.boxes]]
+            min = [0.0, 0.0, 0.0]
+            max = [1.0, 1.0, 1.0]
+            material = { type = "lambertian", albedo = [0.5, 0.5, 0.5] }
+
+            [[instances]]
+            object = "pair"
+            translation = [1.0, 2.0, 3.0]
+            rotation = [0.0, 90.0, 0.0]
+            "#,
+            SceneFormat::Toml,
+        )
+        .unwrap();
+        assert_eq!(scene.instances[0].scale, glam::Vec3::ONE);
+        // rotated a quarter turn around y, then translated
+        assert!(scene.instances[0]
+            .object_to_world()
+            .transform_point3(glam::Vec3::X)
+            .abs_diff_eq(glam::Vec3::new(1.0, 2.0, 2.0), 1e-5));
+
+        let mut input = ray_tracer::InputType::default();
+        scene.add_instances(&mut input).unwrap();
+        assert_eq!(input.objects[0].primitives.len(), 1 + 6);
+        assert_eq!(input.spheres.len(), 1);
+        assert_eq!(input.quads.len(), 6);
+        assert_eq!(input.instances[0].object, 0);
+        // the primitives of objects are only drawn through instances
+        assert!(input.primitives().is_empty());
+
+        scene.instances[0].object = "missing".into();
+        assert!(scene
+            .add_instances(&mut ray_tracer::InputType::default())
+            .is_err());
+    }
+
+    #[test]
+    fn motion_blur() {
+        let scene = Scene::parse(
+            r#"
+            version = 1
+
+            [camera]
+            shutter_close = 1.0
+
+            [[spheres]]
+            center = [0.0, 0.0, -1.0]
+            radius = 0.5
+            material = { type = "lambertian", albedo = [0.5, 0.5, 0.5] }
+
+            [[spheres]]
+            center = [1.0, 0.0, -1.0]
+            end_center = [1.0, 0.5, -1.0]
+            radius = 0.5
+            material = { type = "lambertian", albedo = [0.5, 0.5, 0.5] }
+            "#,
+            SceneFormat::Toml,
+        )
+        .unwrap();
+        // spheres without an end center stand still
+        let spheres = scene.spheres();
+        assert_eq!(spheres[0].end_center, spheres[0].center);
+        assert_eq!(spheres[1].end_center, glam::Vec3::new(1.0, 0.5, -1.0));
+        assert!((scene.camera.shutter_close - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn volumes() {
+        let scene = Scene::parse(
+            r#"
+            version = 1
+
+            [[volumes]]
+            boundary = { type = "sphere", center = [0.0, 1.0, 0.0], radius = 2.0 }
+            density = 0.5
+            albedo = [0.9, 0.9, 0.9]
+            "#,
+            SceneFormat::Toml,
+        )
+        .unwrap();
+        assert_eq!(
+            scene.volumes[0].boundary,
+            Boundary::Sphere {
+                center: glam::Vec3::Y,
+                radius: 2.0
+            }
+        );
+        assert_eq!(scene.volumes().len(), 1);
+    }
+
+    #[test]
+    fn textures() {
+        let mut scene = Scene::parse(
+            r#"
+            version = 1
+
+            [[textures]]
+            type = "checker"
+            even = [1.0, 1.0, 1.0]
+            odd = [0.0, 0.0, 0.0]
+
+            [[textures]]
+            type = "noise"
+            scale = 4.0
+
+            [[spheres]]
+            center = [0.0, 0.0, 0.0]
+            radius = 1.0
+            material = { type = "lambertian", texture = 1 }
+            "#,
+            SceneFormat::Toml,
+        )
+        .unwrap();
+        assert_eq!(
+            scene.spheres[0].material,
+            Material::Lambertian {
+                albedo: glam::Vec3::ONE,
+                texture: Some(1)
+            }
+        );
+        assert_eq!(scene.textures().unwrap().0.len(), 2);
+
+        scene.textures.pop();
+        assert!(matches!(scene.textures(), Err(Error::SceneTexture(1))));
+    }
+
+    #[tokio::test]
+    async fn load() {
+        let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+        let scene = Scene::load(&directory.join("tetrahedron.toml"))
+            .await
+            .unwrap();
+
+        // paths are kept as written and only resolved when the meshes are read
+        assert_eq!(scene.directory, directory);
+        assert_eq!(
+            scene.meshes[0].path,
+            std::path::Path::new("models/tetrahedron.obj")
+        );
+        assert!(!scene.meshes().unwrap().triangles.is_empty());
+        let contents = scene.to_string(SceneFormat::Toml).unwrap();
+        assert!(contents.contains("path = \"models/tetrahedron.obj\""));
+    }
+}
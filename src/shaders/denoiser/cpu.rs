@@ -0,0 +1,195 @@
+//! CPU reference implementation of `shader.wgsl`.
+//!
+//! Every function mirrors its WGSL counterpart so that output produced here can be diffed
+//! against output produced on the graphics card.
+
+use chrono::Utc;
+
+use super::{InputType, OutputType, OutputTypeAov, ParameterType};
+
+#[derive(Default)]
+pub struct Shader;
+
+impl Shader {
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+
+    #[must_use]
+    pub fn execute(&self, in_value: &InputType) -> OutputType {
+        println!(
+            "[{:?}] denoising with {} iterations on the cpu",
+            Utc::now().to_string(),
+            in_value.iterations
+        );
+
+        let pixel_count = (in_value.size.x * in_value.size.y) as usize;
+        let mut pixels = in_value.pixels[..pixel_count].to_vec();
+        let mut out = vec![glam::Vec4::ZERO; pixel_count];
+
+        for iteration in 0..in_value.iterations {
+            let parameter = in_value.parameter(iteration);
+            for y in 0..in_value.size.y {
+                for x in 0..in_value.size.x {
+                    main(
+                        &parameter,
+                        &pixels,
+                        &in_value.aovs,
+                        &mut out,
+                        glam::UVec2::new(x, y),
+                    );
+                }
+            }
+            std::mem::swap(&mut pixels, &mut out);
+        }
+
+        println!("[{:?}] denoised", Utc::now().to_string());
+
+        OutputType { pixels }
+    }
+}
+
+/* === Filter === */
+
+fn kernel(offset: i32) -> f32 {
+    match offset.abs() {
+        0 => 0.375,
+        1 => 0.25,
+        _ => 0.0625,
+    }
+}
+
+fn edge_weight(
+    parameter: &ParameterType,
+    color: glam::Vec3,
+    aov: &OutputTypeAov,
+    other_color: glam::Vec3,
+    other_aov: &OutputTypeAov,
+) -> f32 {
+    let color_difference = color - other_color;
+    let normal_difference = aov.normal - other_aov.normal;
+    let albedo_difference = aov.albedo - other_aov.albedo;
+    let depth_difference = (aov.depth - other_aov.depth).abs() / aov.depth.max(0.001);
+
+    (-color_difference.length_squared() / (parameter.sigma_color * parameter.sigma_color)
+        - normal_difference.length_squared() / (parameter.sigma_normal * parameter.sigma_normal)
+        - albedo_difference.length_squared() / (parameter.sigma_albedo * parameter.sigma_albedo)
+        - depth_difference * depth_difference / (parameter.sigma_depth * parameter.sigma_depth))
+        .exp()
+}
+
+/* === Main === */
+
+#[allow(clippy::cast_possible_wrap)]
+#[allow(clippy::cast_sign_loss)]
+fn main(
+    parameter: &ParameterType,
+    pixels: &[glam::Vec4],
+    aovs: &[OutputTypeAov],
+    out: &mut [glam::Vec4],
+    global_id: glam::UVec2,
+) {
+    // Invocation
+    let size = parameter.size.as_ivec2();
+    let position = global_id.as_ivec2();
+    let index = (size.x * position.y + position.x) as usize;
+    let color = pixels[index];
+    let aov = &aovs[index];
+
+    // Calculate
+    let mut sum = glam::Vec3::ZERO;
+    let mut weight_sum = 0.0;
+
+    for y in -2..=2 {
+        for x in -2..=2 {
+            let other = position + glam::IVec2::new(x, y) * parameter.step as i32;
+            if other.x < 0 || other.y < 0 || other.x >= size.x || other.y >= size.y {
+                continue;
+            }
+
+            let other_index = (size.x * other.y + other.x) as usize;
+            let other_color = pixels[other_index].truncate();
+            let weight = kernel(x)
+                * kernel(y)
+                * edge_weight(
+                    parameter,
+                    color.truncate(),
+                    aov,
+                    other_color,
+                    &aovs[other_index],
+                );
+            sum += weight * other_color;
+            weight_sum += weight;
+        }
+    }
+
+    // Save
+    out[index] = (sum / weight_sum).extend(color.w);
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+
+    use crate::shaders::{denoiser, ray_tracer::OutputTypeAov};
+
+    #[test]
+    fn test() {
+        // two walls meeting in the middle of the image, lit to the same grey plus noise
+        let size = glam::UVec2::new(32, 16);
+        let mut random = rand::rngs::StdRng::seed_from_u64(0);
+        let mut pixels = Vec::new();
+        let mut aovs = Vec::new();
+        for _ in 0..size.y {
+            for x in 0..size.x {
+                let left = x < size.x / 2;
+                let grey = if left { 0.2 } else { 0.8 };
+                pixels.push(glam::Vec4::new(
+                    grey + random.gen_range(-0.2..0.2),
+                    grey + random.gen_range(-0.2..0.2),
+                    grey + random.gen_range(-0.2..0.2),
+                    1.0,
+                ));
+                aovs.push(OutputTypeAov {
+                    normal: if left { glam::Vec3::X } else { glam::Vec3::Z },
+                    depth: 10.0,
+                    albedo: glam::Vec3::splat(0.5),
+                    material: 1,
+                    ..Default::default()
+                });
+            }
+        }
+        let input = denoiser::InputType {
+            size,
+            pixels,
+            aovs,
+            ..Default::default()
+        };
+
+        let output = denoiser::cpu::Shader::new().execute(&input);
+        assert_eq!(output.pixels.len(), 32 * 16);
+
+        let error = |pixels: &[glam::Vec4]| {
+            pixels
+                .iter()
+                .enumerate()
+                .map(|(index, pixel)| {
+                    let grey = if index % 32 < 16 { 0.2 } else { 0.8 };
+                    (pixel.truncate() - glam::Vec3::splat(grey)).length_squared()
+                })
+                .sum::<f32>()
+        };
+        // the noise is smoothed out without blurring the walls into each other
+        assert!(error(&output.pixels) < error(&input.pixels) * 0.1);
+        assert!(output.pixels[8 * 32 + 15].x < 0.3);
+        assert!(output.pixels[8 * 32 + 16].x > 0.7);
+
+        // no iterations leave the image as it is
+        let output = denoiser::cpu::Shader::new().execute(&denoiser::InputType {
+            iterations: 0,
+            ..input.clone()
+        });
+        assert_eq!(output.pixels, input.pixels);
+    }
+}
@@ -0,0 +1,363 @@
+//! Edge-avoiding à-trous wavelet filter, guided by the first hits the ray tracer records.
+//!
+//! Every iteration blurs with a 5x5 B3 spline whose taps are twice as far apart as in the previous
+//! one, weighted down where the radiance, normal, albedo or depth of a tap differs from the pixel.
+
+pub mod cpu;
+mod types;
+
+use chrono::Utc;
+use encase::ShaderType;
+use wgpu::util::DeviceExt;
+
+use super::{
+    ray_tracer::OutputTypeAov, storage_buffer_layout_entry, uniform_buffer_layout_entry,
+    uniform_contents,
+};
+use crate::gpu::GPU;
+
+use types::{AovType, ParameterType, PixelType};
+
+#[derive(Clone, Debug)]
+pub struct InputType {
+    pub size: glam::UVec2,
+
+    /// Linear radiance of `size.x * size.y` pixels, bottom row first.
+    pub pixels: Vec<glam::Vec4>,
+
+    /// First hit of each pixel, see [`crate::shaders::ray_tracer::OutputType::aovs`].
+    pub aovs: Vec<OutputTypeAov>,
+
+    /// Every iteration doubles the distance between taps, five reach 64 pixels apart.
+    pub iterations: u32,
+
+    /// Radiance difference at which taps lose most of their weight, halved every iteration.
+    pub sigma_color: f32,
+
+    pub sigma_normal: f32,
+
+    pub sigma_albedo: f32,
+
+    /// Depth difference relative to the depth of the pixel.
+    pub sigma_depth: f32,
+}
+
+impl Default for InputType {
+    fn default() -> Self {
+        Self {
+            size: glam::UVec2::ZERO,
+            pixels: Vec::new(),
+            aovs: Vec::new(),
+            iterations: 5,
+            sigma_color: 1.0,
+            sigma_normal: 0.3,
+            sigma_albedo: 0.1,
+            sigma_depth: 0.05,
+        }
+    }
+}
+
+impl InputType {
+    fn parameter(&self, iteration: u32) -> ParameterType {
+        ParameterType {
+            size: self.size,
+            step: 1 << iteration,
+            #[allow(clippy::cast_possible_wrap)]
+            sigma_color: self.sigma_color * 0.5_f32.powi(iteration as i32),
+            sigma_normal: self.sigma_normal,
+            sigma_albedo: self.sigma_albedo,
+            sigma_depth: self.sigma_depth,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct OutputType {
+    pub pixels: Vec<glam::Vec4>,
+}
+
+pub struct Shader {
+    bind_group_layout: wgpu::BindGroupLayout,
+    gpu: GPU,
+    pipeline: wgpu::ComputePipeline,
+    workgroup_size: glam::UVec3,
+}
+
+impl Shader {
+    #[must_use]
+    pub fn new(gpu: GPU) -> Self {
+        // create the shader
+        let workgroup_size = glam::UVec3::new(8, 8, 1);
+
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Denoiser Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("shader.wgsl")
+                        .replace(
+                            "@workgroup_size(1)",
+                            &format!(
+                                "@workgroup_size({}, {}, {})",
+                                workgroup_size.x, workgroup_size.y, workgroup_size.z
+                            ),
+                        )
+                        .into(),
+                ),
+            });
+
+        // create the interface for the shader
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &[
+                        uniform_buffer_layout_entry(0, ParameterType::min_size()),
+                        storage_buffer_layout_entry(1, true, PixelType::min_size()),
+                        storage_buffer_layout_entry(2, true, AovType::min_size()),
+                        storage_buffer_layout_entry(3, false, PixelType::min_size()),
+                    ],
+                });
+
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Denoiser Pipeline Layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+
+        let pipeline = gpu
+            .device()
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Denoiser Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "main",
+            });
+
+        Self {
+            bind_group_layout,
+            gpu,
+            pipeline,
+            workgroup_size,
+        }
+    }
+
+    #[allow(clippy::missing_panics_doc)]
+    #[allow(clippy::too_many_lines)]
+    pub async fn execute(&self, in_value: &InputType) -> OutputType {
+        println!(
+            "[{:?}] denoising with {} iterations",
+            Utc::now().to_string(),
+            in_value.iterations
+        );
+
+        let pixel_count = (in_value.size.x * in_value.size.y) as usize;
+        let size = PixelType::min_size().get() * (pixel_count.max(1) as u64);
+
+        // create two pixel buffers, every iteration reads one and writes the other
+        let pixel_buffers = [
+            self.create_storage_buffer(
+                "Denoiser Pixel Buffer",
+                &PixelType {
+                    pixels: in_value.pixels[..pixel_count].to_vec(),
+                },
+            ),
+            self.create_storage_buffer(
+                "Denoiser Pixel Buffer",
+                &PixelType {
+                    pixels: vec![glam::Vec4::ZERO; pixel_count],
+                },
+            ),
+        ];
+
+        let aov_buffer = self.create_storage_buffer(
+            "Denoiser AOV Buffer",
+            &AovType {
+                aovs: in_value.aovs[..pixel_count].to_vec(),
+            },
+        );
+
+        // create the commands for the graphics card to execute
+        let mut encoder = self
+            .gpu
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+        for iteration in 0..in_value.iterations {
+            let parameter_buffer =
+                self.gpu
+                    .device()
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Denoiser Parameter Buffer"),
+                        contents: &uniform_contents(&in_value.parameter(iteration)),
+                        usage: wgpu::BufferUsages::UNIFORM,
+                    });
+
+            // bind the resources to the interface
+            let bind_group = self
+                .gpu
+                .device()
+                .create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Denoiser Bind Group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: parameter_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: pixel_buffers[iteration as usize % 2].as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: aov_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: pixel_buffers[(iteration as usize + 1) % 2]
+                                .as_entire_binding(),
+                        },
+                    ],
+                });
+
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+
+            let size = in_value.size.extend(1);
+            let mut workgroups = size / self.workgroup_size;
+            if !size.x.is_multiple_of(self.workgroup_size.x) {
+                workgroups.x += 1;
+            }
+            if !size.y.is_multiple_of(self.workgroup_size.y) {
+                workgroups.y += 1;
+            }
+
+            pass.dispatch_workgroups(workgroups.x, workgroups.y, workgroups.z);
+        }
+
+        // create a buffer for the result
+        let mapping_buffer = self.gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Denoiser Mapping Buffer"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        // create the command for the last pixel buffer written to be copied to the mapping buffer
+        encoder.copy_buffer_to_buffer(
+            &pixel_buffers[in_value.iterations as usize % 2],
+            0,
+            &mapping_buffer,
+            0,
+            size,
+        );
+
+        // submit the commands for processing
+        let submission_index = self.gpu.queue().submit(core::iter::once(encoder.finish()));
+
+        // create a future which resolves when the gpu buffer to cpu buffer is complete
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let mapping_slice = mapping_buffer.slice(..);
+        mapping_slice.map_async(wgpu::MapMode::Read, |v| sender.send(v).unwrap());
+
+        // constantly poll the gpu
+        self.gpu.poll(submission_index).await.unwrap();
+
+        // wait for the future to resolve
+        receiver.await.unwrap().unwrap();
+
+        // create a view of the cpu buffer
+        let mapping_slice_buffer_view = mapping_slice.get_mapped_range();
+
+        // read the result from the view
+        let mut out_value = PixelType::default();
+        encase::StorageBuffer::new(mapping_slice_buffer_view.as_ref())
+            .read(&mut out_value)
+            .unwrap();
+
+        // clean up buffer views and cpu buffer
+        drop(mapping_slice_buffer_view);
+        mapping_buffer.unmap();
+
+        out_value.pixels.truncate(pixel_count);
+
+        println!("[{:?}] denoised", Utc::now().to_string());
+
+        OutputType {
+            pixels: out_value.pixels,
+        }
+    }
+
+    /// Creates a storage buffer holding `value`, which can also be copied from.
+    fn create_storage_buffer<T>(&self, label: &str, value: &T) -> wgpu::Buffer
+    where
+        T: ShaderType + encase::internal::WriteInto,
+    {
+        let mut contents = Vec::new();
+        encase::StorageBuffer::new(&mut contents)
+            .write(value)
+            .unwrap();
+
+        self.gpu
+            .device()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: &contents,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        gpu::{GpuOptions, GPU},
+        shaders::{denoiser, ray_tracer::OutputTypeAov},
+    };
+
+    #[tokio::test]
+    async fn test() {
+        let gpu = GPU::new(&GpuOptions::default()).await.unwrap();
+
+        let shader = denoiser::Shader::new(gpu);
+
+        // a ramp over a sphere-like patch of normals, with a sharp edge down the middle
+        let size = glam::UVec2::new(20, 12);
+        let mut pixels = Vec::new();
+        let mut aovs = Vec::new();
+        for y in 0..size.y {
+            for x in 0..size.x {
+                #[allow(clippy::cast_precision_loss)]
+                let value = ((x * 7 + y * 13) % 11) as f32 / 10.0;
+                pixels.push(glam::Vec4::new(value, 1.0 - value, 0.5, 1.0));
+                aovs.push(OutputTypeAov {
+                    normal: if x < 10 { glam::Vec3::Y } else { glam::Vec3::Z },
+                    #[allow(clippy::cast_precision_loss)]
+                    depth: 5.0 + y as f32,
+                    albedo: glam::Vec3::splat(0.5),
+                    material: 1,
+                    ..Default::default()
+                });
+            }
+        }
+        let input = denoiser::InputType {
+            size,
+            pixels,
+            aovs,
+            ..Default::default()
+        };
+
+        let output = shader.execute(&input).await;
+        let expected = denoiser::cpu::Shader::new().execute(&input);
+
+        assert_eq!(output.pixels.len(), 20 * 12);
+        // the graphics card filters the same as the cpu
+        for (pixel, expected) in output.pixels.iter().zip(&expected.pixels) {
+            assert!(pixel.abs_diff_eq(*expected, 1e-4), "{pixel} != {expected}");
+        }
+    }
+}
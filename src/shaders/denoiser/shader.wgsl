@@ -0,0 +1,136 @@
+/*
+ * ============================================================================
+ * Parameter Uniform Buffer
+ * ============================================================================
+ */
+struct ParameterType {
+    size: vec2<u32>,
+    // distance between the taps of this iteration
+    step: u32,
+    sigma_color: f32,
+    sigma_normal: f32,
+    sigma_albedo: f32,
+    sigma_depth: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> parameter: ParameterType;
+
+/*
+ * ============================================================================
+ * Pixel Storage Buffers
+ * ============================================================================
+ */
+struct PixelType {
+    pixels: array<vec4<f32>>,
+}
+
+@group(0) @binding(1)
+var<storage> in: PixelType;
+
+@group(0) @binding(3)
+var<storage, read_write> out: PixelType;
+
+/*
+ * ============================================================================
+ * AOV Storage Buffer
+ * ============================================================================
+ */
+struct Primitive {
+    type_: u32,
+    index: u32,
+}
+
+struct Aov {
+    normal: vec3<f32>,
+    depth: f32,
+    albedo: vec3<f32>,
+    material: u32,
+    primitive: Primitive,
+}
+
+struct AovType {
+    aovs: array<Aov>,
+}
+
+@group(0) @binding(2)
+var<storage> guide: AovType;
+
+/*
+ * ============================================================================
+ * Filter
+ * ============================================================================
+ */
+// B3 spline, 1/16 1/4 3/8 1/4 1/16
+fn kernel(offset: i32) -> f32 {
+    switch abs(offset) {
+        case 0: {
+            return 0.375;
+        }
+        case 1: {
+            return 0.25;
+        }
+        default: {
+            return 0.0625;
+        }
+    }
+}
+
+// how much a neighbour with the given radiance and first hit belongs to the surface of the pixel
+fn edge_weight(color: vec3<f32>, aov: Aov, other_color: vec3<f32>, other_aov: Aov) -> f32 {
+    let color_difference = color - other_color;
+    let normal_difference = aov.normal - other_aov.normal;
+    let albedo_difference = aov.albedo - other_aov.albedo;
+    let depth_difference = abs(aov.depth - other_aov.depth) / max(aov.depth, 0.001);
+
+    return exp(
+        -dot(color_difference, color_difference) / (parameter.sigma_color * parameter.sigma_color)
+        - dot(normal_difference, normal_difference) / (parameter.sigma_normal * parameter.sigma_normal)
+        - dot(albedo_difference, albedo_difference) / (parameter.sigma_albedo * parameter.sigma_albedo)
+        - depth_difference * depth_difference / (parameter.sigma_depth * parameter.sigma_depth)
+    );
+}
+
+/*
+ * ============================================================================
+ * Main
+ * ============================================================================
+ */
+@compute @workgroup_size(1)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    // Exit
+    if global_id.x >= parameter.size.x || global_id.y >= parameter.size.y || global_id.z >= 1u {
+        return;
+    }
+
+    // Invocation
+    let size = vec2<i32>(parameter.size);
+    let position = vec2<i32>(global_id.xy);
+    let index = size.x * position.y + position.x;
+    let color = in.pixels[index];
+    let aov = guide.aovs[index];
+
+    // Calculate
+    var sum = vec3<f32>(0.0, 0.0, 0.0);
+    var weight_sum = 0.0;
+
+    for (var y = -2; y <= 2; y = y + 1) {
+        for (var x = -2; x <= 2; x = x + 1) {
+            let other = position + vec2<i32>(x, y) * i32(parameter.step);
+            if other.x < 0 || other.y < 0 || other.x >= size.x || other.y >= size.y {
+                continue;
+            }
+
+            let other_index = size.x * other.y + other.x;
+            let other_color = in.pixels[other_index].xyz;
+            let weight = kernel(x) * kernel(y) * edge_weight(color.xyz, aov, other_color, guide.aovs[other_index]);
+            sum += weight * other_color;
+            weight_sum += weight;
+        }
+    }
+
+    // Save
+    out.pixels[index] = vec4<f32>(sum / weight_sum, color.w);
+}
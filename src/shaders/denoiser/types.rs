@@ -0,0 +1,29 @@
+//! Structs shared with `shader.wgsl`, laid out in its buffers by `encase::ShaderType`.
+
+// `encase::ShaderType` derives compile time checks which are never called.
+#![allow(dead_code)]
+
+use crate::shaders::ray_tracer::OutputTypeAov;
+
+/// Layout of the parameter uniform buffer of one iteration.
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct ParameterType {
+    pub(super) size: glam::UVec2,
+    pub(super) step: u32,
+    pub(super) sigma_color: f32,
+    pub(super) sigma_normal: f32,
+    pub(super) sigma_albedo: f32,
+    pub(super) sigma_depth: f32,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct PixelType {
+    #[size(runtime)]
+    pub(super) pixels: Vec<glam::Vec4>,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct AovType {
+    #[size(runtime)]
+    pub(super) aovs: Vec<OutputTypeAov>,
+}
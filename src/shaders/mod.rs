@@ -1 +1,47 @@
+pub mod denoiser;
 pub mod ray_tracer;
+
+use encase::ShaderType;
+
+/// Encodes `value` with the layout of a uniform buffer.
+fn uniform_contents<T>(value: &T) -> Vec<u8>
+where
+    T: ShaderType + encase::internal::WriteInto,
+{
+    let mut contents = encase::UniformBuffer::new(Vec::new());
+    contents.write(value).unwrap();
+    contents.into_inner()
+}
+
+fn storage_buffer_layout_entry(
+    binding: u32,
+    read_only: bool,
+    min_binding_size: wgpu::BufferSize,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: Some(min_binding_size),
+        },
+        count: None,
+    }
+}
+
+fn uniform_buffer_layout_entry(
+    binding: u32,
+    min_binding_size: wgpu::BufferSize,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: Some(min_binding_size),
+        },
+        count: None,
+    }
+}
@@ -0,0 +1,693 @@
+//! Bounding volume hierarchy over the primitives of `InputType`, built with binned SAH.
+//!
+//! Nodes are flattened depth first, the first child of an interior node directly follows it.
+//! A scene without primitives has no nodes. Planes are unbounded and left out, every ray tests them.
+//!
+//! The hierarchy of the world references instances as primitives, the subtree of every object
+//! follows it and bounds the primitives of the object in its own space.
+//!
+//! Traversal keeps the nodes still to visit on a stack of `STACK_SIZE` entries, the depth of every
+//! hierarchy is limited so that it never fills up.
+
+pub use super::types::{InputTypeBvhNode, InputTypeBvhObject};
+use super::{InputType, InputTypePrimitive};
+
+/// Number of centroid bins evaluated along each axis.
+const BINS: usize = 16;
+
+/// Leaves are split while they hold more primitives, even when the surface area heuristic disagrees.
+const MAX_LEAF_SIZE: usize = 8;
+
+/// Deepest level of the hierarchy of the world and of every object, below the root at level 0.
+///
+/// The world and an object each wait on at most one sibling per level, and a leaf of the world
+/// adds at most `MAX_LEAF_SIZE` objects, which keeps a traversal within `STACK_SIZE` entries.
+const MAX_DEPTH: usize = 27;
+
+/// Entries of the traversal stack of the shader.
+pub const STACK_SIZE: usize = 64;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Bvh {
+    pub nodes: Vec<InputTypeBvhNode>,
+    pub primitives: Vec<InputTypePrimitive>,
+    /// one for every `InputType::objects`, their primitives follow those of the world
+    pub objects: Vec<InputTypeBvhObject>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Aabb {
+    min: glam::Vec3,
+    max: glam::Vec3,
+}
+
+impl Aabb {
+    const EMPTY: Self = Self {
+        min: glam::Vec3::splat(f32::MAX),
+        max: glam::Vec3::splat(f32::MIN),
+    };
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    fn grow(self, point: glam::Vec3) -> Self {
+        Self {
+            min: self.min.min(point),
+            max: self.max.max(point),
+        }
+    }
+
+    fn surface_area(self) -> f32 {
+        let extent = (self.max - self.min).max(glam::Vec3::ZERO);
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+}
+
+struct Item {
+    primitive: InputTypePrimitive,
+    bounds: Aabb,
+    centroid: glam::Vec3,
+}
+
+impl Item {
+    fn new(primitive: InputTypePrimitive, bounds: Aabb) -> Self {
+        Self {
+            primitive,
+            bounds,
+            centroid: (bounds.min + bounds.max) * 0.5,
+        }
+    }
+}
+
+/// Bounds of a sphere, triangle, quad, disk or volume.
+fn bounds(in_value: &InputType, primitive: InputTypePrimitive) -> Aabb {
+    let index = primitive.index as usize;
+    match primitive.type_ {
+        InputTypePrimitive::SPHERE => {
+            let sphere = &in_value.spheres[index];
+            // hollow spheres have a negative radius, moving spheres are bounded along their path
+            let radius = glam::Vec3::splat(sphere.radius.abs());
+            Aabb {
+                min: sphere.center.min(sphere.end_center) - radius,
+                max: sphere.center.max(sphere.end_center) + radius,
+            }
+        }
+        InputTypePrimitive::TRIANGLE => in_value.triangles[index]
+            .indices
+            .to_array()
+            .into_iter()
+            .fold(Aabb::EMPTY, |bounds, index| {
+                bounds.grow(in_value.vertices[index as usize].position)
+            }),
+        InputTypePrimitive::QUAD => {
+            let quad = &in_value.quads[index];
+            [quad.u, quad.v, quad.u + quad.v]
+                .into_iter()
+                .fold(Aabb::EMPTY.grow(quad.corner), |bounds, edge| {
+                    bounds.grow(quad.corner + edge)
+                })
+        }
+        InputTypePrimitive::DISK => {
+            let disk = &in_value.disks[index];
+            // the disk reaches less far along the axes its normal leans towards
+            let normal = disk.normal.normalize();
+            let extent = disk.radius
+                * (glam::Vec3::ONE - normal * normal)
+                    .max(glam::Vec3::ZERO)
+                    .powf(0.5);
+            Aabb {
+                min: disk.center - extent,
+                max: disk.center + extent,
+            }
+        }
+        InputTypePrimitive::VOLUME => {
+            let volume = &in_value.volumes[index];
+            let extent = if volume.boundary == 1 {
+                volume.half_size
+            } else {
+                glam::Vec3::splat(volume.radius.abs())
+            };
+            Aabb {
+                min: volume.center - extent,
+                max: volume.center + extent,
+            }
+        }
+        _ => Aabb::EMPTY,
+    }
+}
+
+impl Bvh {
+    /// # Panics
+    ///
+    /// Panics if the world or an object has more than `MAX_LEAF_SIZE << MAX_DEPTH` primitives (a
+    /// billion), whose hierarchy could not be traversed within `STACK_SIZE` stack entries.
+    #[allow(clippy::cast_possible_truncation)]
+    #[must_use]
+    pub fn new(in_value: &InputType) -> Self {
+        let objects: Vec<Vec<_>> = in_value
+            .objects
+            .iter()
+            .map(|object| {
+                object
+                    .primitives
+                    .iter()
+                    .map(|&primitive| Item::new(primitive, bounds(in_value, primitive)))
+                    .collect()
+            })
+            .collect();
+
+        // instances are bounded by the corners of the bounds of their object, moved into the world
+        let instances = in_value
+            .instances
+            .iter()
+            .enumerate()
+            .filter_map(|(index, instance)| {
+                let object = &objects[instance.object as usize];
+                if object.is_empty() {
+                    return None;
+                }
+                let bounds = object
+                    .iter()
+                    .fold(Aabb::EMPTY, |bounds, item| bounds.union(item.bounds));
+                let bounds = (0..8)
+                    .map(|corner| {
+                        glam::Vec3::select(
+                            glam::BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+                            bounds.max,
+                            bounds.min,
+                        )
+                    })
+                    .fold(Aabb::EMPTY, |world, corner| {
+                        world.grow(instance.object_to_world.transform_point3(corner))
+                    });
+                Some(Item::new(
+                    InputTypePrimitive {
+                        type_: InputTypePrimitive::INSTANCE,
+                        index: index as u32,
+                    },
+                    bounds,
+                ))
+            });
+
+        let mut items: Vec<_> = in_value
+            .primitives()
+            .into_iter()
+            .map(|primitive| Item::new(primitive, bounds(in_value, primitive)))
+            .chain(instances)
+            .collect();
+
+        if items.is_empty() {
+            return Self::flat(in_value);
+        }
+
+        let mut bvh = Self::default();
+        bvh.build(&mut items, 0, 0);
+        bvh.primitives = items.into_iter().map(|item| item.primitive).collect();
+
+        for mut items in objects {
+            let node = bvh.nodes.len() as u32;
+            let offset = bvh.primitives.len();
+            if !items.is_empty() {
+                bvh.build(&mut items, offset, 0);
+            }
+            bvh.objects.push(InputTypeBvhObject {
+                node,
+                offset: offset as u32,
+                count: items.len() as u32,
+            });
+            bvh.primitives
+                .extend(items.into_iter().map(|item| item.primitive));
+        }
+
+        assert!(
+            bvh.stack_size() <= STACK_SIZE,
+            "the traversal of the hierarchy needs more than {STACK_SIZE} stack entries"
+        );
+        bvh
+    }
+
+    /// Lists the primitives without nodes, for testing every one of them.
+    #[allow(clippy::cast_possible_truncation)]
+    #[must_use]
+    pub fn flat(in_value: &InputType) -> Self {
+        let instances = (0..in_value.instances.len()).map(|index| InputTypePrimitive {
+            type_: InputTypePrimitive::INSTANCE,
+            index: index as u32,
+        });
+        let mut bvh = Self {
+            primitives: in_value.primitives().into_iter().chain(instances).collect(),
+            ..Self::default()
+        };
+
+        for object in &in_value.objects {
+            bvh.objects.push(InputTypeBvhObject {
+                node: 0,
+                offset: bvh.primitives.len() as u32,
+                count: object.primitives.len() as u32,
+            });
+            bvh.primitives.extend(&object.primitives);
+        }
+        bvh
+    }
+
+    /// Primitives and instances of the world, which precede those of the objects.
+    #[must_use]
+    pub fn world_primitives(&self) -> &[InputTypePrimitive] {
+        let length = self
+            .objects
+            .first()
+            .map_or(self.primitives.len(), |object| object.offset as usize);
+        &self.primitives[..length]
+    }
+
+    /// Entries the traversal stack needs at most, for the hierarchy of the world along with the
+    /// objects its leaves place.
+    fn stack_size(&self) -> usize {
+        if self.nodes.is_empty() {
+            return 0;
+        }
+
+        let object = self
+            .objects
+            .iter()
+            .filter(|object| object.count > 0)
+            .map(|object| self.subtree_stack_size(object.node as usize, 0, 0))
+            .max()
+            .unwrap_or(0);
+        self.subtree_stack_size(0, 0, object)
+    }
+
+    /// Entries the traversal stack needs below the node at `index`, `depth` levels below its root,
+    /// where visiting the hierarchy of an object needs `object` entries.
+    fn subtree_stack_size(&self, index: usize, depth: usize, object: usize) -> usize {
+        let node = &self.nodes[index];
+
+        // below the siblings waiting on every level above the node
+        if node.count > 0 {
+            let primitives =
+                &self.primitives[node.offset as usize..(node.offset + node.count) as usize];
+            let instances = primitives
+                .iter()
+                .filter(|primitive| primitive.type_ == InputTypePrimitive::INSTANCE)
+                .count();
+            // the roots of the objects are pushed together and visited one after the other
+            return if instances == 0 {
+                depth + 1
+            } else {
+                depth + instances - 1 + object
+            };
+        }
+
+        // both children are pushed before the nearer one is visited
+        (depth + 2)
+            .max(self.subtree_stack_size(index + 1, depth + 1, object))
+            .max(self.subtree_stack_size(node.offset as usize, depth + 1, object))
+    }
+
+    /// Appends the subtree over `items`, which start at `offset` in the final primitive order, with
+    /// its root `depth` levels below the root of the hierarchy.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn build(&mut self, items: &mut [Item], offset: usize, depth: usize) {
+        let bounds = items
+            .iter()
+            .fold(Aabb::EMPTY, |bounds, item| bounds.union(item.bounds));
+
+        let index = self.nodes.len();
+        self.nodes.push(InputTypeBvhNode {
+            min: bounds.min,
+            offset: offset as u32,
+            max: bounds.max,
+            count: items.len() as u32,
+        });
+
+        if items.len() <= 1 || depth == MAX_DEPTH {
+            return;
+        }
+
+        let Some(mut mid) = split(items, bounds) else {
+            return;
+        };
+
+        // halving the items from here on still ends in small leaves by the deepest level, fall back
+        // to it when the surface area heuristic leaves more on one side
+        let fits = MAX_LEAF_SIZE << (MAX_DEPTH - depth - 1);
+        if mid.max(items.len() - mid) > fits {
+            mid = split_median(items);
+        }
+
+        let (left, right) = items.split_at_mut(mid);
+        self.build(left, offset, depth + 1);
+        self.nodes[index].offset = self.nodes.len() as u32;
+        self.nodes[index].count = 0;
+        self.build(right, offset + mid, depth + 1);
+    }
+}
+
+/// Partitions `items` along the cheapest binned split, returns `None` when a leaf is cheaper.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn split(items: &mut [Item], bounds: Aabb) -> Option<usize> {
+    let centroids = items
+        .iter()
+        .fold(Aabb::EMPTY, |centroids, item| centroids.grow(item.centroid));
+    let extent = centroids.max - centroids.min;
+
+    let bin = |item: &Item, axis: usize| {
+        let position = (item.centroid[axis] - centroids.min[axis]) / extent[axis];
+        ((position * BINS as f32) as usize).min(BINS - 1)
+    };
+
+    // (cost, axis, bin) of the cheapest split
+    let mut best: Option<(f32, usize, usize)> = None;
+
+    for axis in 0..3 {
+        if extent[axis] <= 0.0 {
+            continue;
+        }
+
+        let mut bins = [(Aabb::EMPTY, 0_usize); BINS];
+        for item in items.iter() {
+            let bin = &mut bins[bin(item, axis)];
+            bin.0 = bin.0.union(item.bounds);
+            bin.1 += 1;
+        }
+
+        // sweep from the right to find the area and count right of every split
+        let mut right = [(0.0, 0_usize); BINS];
+        let mut accumulated = (Aabb::EMPTY, 0);
+        for split in (1..BINS).rev() {
+            accumulated = (
+                accumulated.0.union(bins[split].0),
+                accumulated.1 + bins[split].1,
+            );
+            right[split] = (accumulated.0.surface_area(), accumulated.1);
+        }
+
+        let mut accumulated = (Aabb::EMPTY, 0);
+        for split in 1..BINS {
+            accumulated = (
+                accumulated.0.union(bins[split - 1].0),
+                accumulated.1 + bins[split - 1].1,
+            );
+            if accumulated.1 == 0 || right[split].1 == 0 {
+                continue;
+            }
+
+            let cost = accumulated.0.surface_area() * accumulated.1 as f32
+                + right[split].0 * right[split].1 as f32;
+            if best.is_none_or(|(best, _, _)| cost < best) {
+                best = Some((cost, axis, split));
+            }
+        }
+    }
+
+    let area = bounds.surface_area();
+    match best {
+        Some((cost, axis, split)) => {
+            // traversal is as expensive as one intersection
+            let split_cost = 1.0 + if area > 0.0 { cost / area } else { 0.0 };
+            if split_cost >= items.len() as f32 && items.len() <= MAX_LEAF_SIZE {
+                return None;
+            }
+
+            items.sort_unstable_by_key(|item| bin(item, axis) >= split);
+            Some(
+                items
+                    .iter()
+                    .take_while(|item| bin(item, axis) < split)
+                    .count(),
+            )
+        }
+        // every centroid is in the same place, split down the middle
+        None if items.len() > MAX_LEAF_SIZE => Some(items.len() / 2),
+        None => None,
+    }
+}
+
+/// Partitions `items` at the median centroid along the axis the centroids spread most along.
+fn split_median(items: &mut [Item]) -> usize {
+    let centroids = items
+        .iter()
+        .fold(Aabb::EMPTY, |centroids, item| centroids.grow(item.centroid));
+    let extent = centroids.max - centroids.min;
+    let axis = (0..3)
+        .max_by(|&a, &b| extent[a].total_cmp(&extent[b]))
+        .unwrap_or(0);
+
+    let mid = items.len() / 2;
+    items.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+    mid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bvh, MAX_DEPTH, STACK_SIZE};
+    use crate::{
+        scene::Scene,
+        shaders::ray_tracer::{
+            self, InputTypeDisk, InputTypeInstance, InputTypeMaterial, InputTypeObject,
+            InputTypePrimitive, InputTypeQuad, InputTypeSphere, InputTypeTriangle, InputTypeVertex,
+        },
+    };
+
+    /// The final scene of the book along with a triangle, a quad and a disk.
+    fn world() -> ray_tracer::InputType {
+        let scene = Scene::random(0);
+        let vertices = [
+            glam::Vec3::new(-2.0, 0.0, 2.0),
+            glam::Vec3::new(2.0, 0.0, 2.0),
+            glam::Vec3::new(0.0, 3.0, 2.0),
+        ]
+        .map(|position| InputTypeVertex {
+            position,
+            normal: glam::Vec3::ZERO,
+        });
+        let material = InputTypeMaterial::new_lambertian(glam::Vec3::splat(0.5));
+        ray_tracer::InputType {
+            samples_per_pixel: 2,
+            seed: 0,
+            screen_size: glam::UVec2::new(32, 32),
+            view_box_position: glam::UVec2::ZERO,
+            view_box_size: glam::UVec2::new(32, 32),
+            camera: (&scene.camera).into(),
+            background: (&scene.background).into(),
+            spheres: scene.spheres(),
+            vertices: vertices.to_vec(),
+            triangles: vec![InputTypeTriangle {
+                indices: glam::UVec3::new(0, 1, 2),
+                material: material.clone(),
+            }],
+            planes: scene.planes(),
+            quads: vec![InputTypeQuad {
+                corner: glam::Vec3::new(-1.0, 0.5, -3.0),
+                u: glam::Vec3::new(2.0, 0.0, 0.0),
+                v: glam::Vec3::new(0.0, 1.0, 0.5),
+                material: material.clone(),
+            }],
+            disks: vec![InputTypeDisk {
+                center: glam::Vec3::new(1.0, 1.5, -1.5),
+                radius: 0.8,
+                normal: glam::Vec3::new(1.0, 1.0, 0.0),
+                material,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test() {
+        let mut input = world();
+        let bvh = Bvh::new(&input);
+
+        // every bounded primitive is referenced exactly once
+        let mut primitives = bvh.primitives.clone();
+        primitives.sort_unstable_by_key(|primitive| (primitive.type_, primitive.index));
+        assert_eq!(primitives.len(), input.spheres.len() + 3);
+        assert!(primitives
+            .iter()
+            .all(|primitive| primitive.type_ != InputTypePrimitive::PLANE));
+        assert_eq!(
+            primitives.last(),
+            Some(&InputTypePrimitive {
+                type_: InputTypePrimitive::DISK,
+                index: 0
+            })
+        );
+
+        // children are contained by their parent
+        for (index, node) in bvh.nodes.iter().enumerate() {
+            if node.count == 0 {
+                for child in [&bvh.nodes[index + 1], &bvh.nodes[node.offset as usize]] {
+                    assert!(child.min.cmpge(node.min).all() && child.max.cmple(node.max).all());
+                }
+            }
+        }
+
+        // the hierarchy finds the same closest hits as testing every primitive
+        let shader = ray_tracer::cpu::Shader::new();
+        let expected = shader.execute(&input);
+        input.bvh = Some(bvh);
+        assert_eq!(shader.execute(&input).pixels, expected.pixels);
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[test]
+    fn instances() {
+        // an object of a new sphere, moving while the shutter is open, and the quad, placed three
+        // times
+        let mut input = world();
+        input.camera.shutter_close = 1.0;
+        input.spheres.push(InputTypeSphere {
+            center: glam::Vec3::ZERO,
+            radius: 0.5,
+            end_center: glam::Vec3::new(0.0, 0.5, 0.0),
+            material: InputTypeMaterial::new_metal(glam::Vec3::splat(0.8), 0.1),
+        });
+        input.objects = vec![InputTypeObject {
+            primitives: vec![
+                InputTypePrimitive {
+                    type_: InputTypePrimitive::SPHERE,
+                    index: input.spheres.len() as u32 - 1,
+                },
+                InputTypePrimitive {
+                    type_: InputTypePrimitive::QUAD,
+                    index: 0,
+                },
+            ],
+        }];
+        input.instances = [
+            glam::Mat4::from_translation(glam::Vec3::new(0.0, 1.0, 1.0)),
+            glam::Mat4::from_scale_rotation_translation(
+                glam::Vec3::new(1.0, 2.0, 0.5),
+                glam::Quat::from_rotation_y(1.0),
+                glam::Vec3::new(-2.0, 1.0, 0.0),
+            ),
+            glam::Mat4::from_rotation_x(0.5),
+        ]
+        .map(|object_to_world| InputTypeInstance::new(0, object_to_world))
+        .to_vec();
+
+        let bvh = Bvh::new(&input);
+        let world = bvh.world_primitives();
+        assert_eq!(world.len(), input.spheres.len() - 1 + 2 + 3);
+        assert_eq!(
+            world
+                .iter()
+                .filter(|primitive| primitive.type_ == InputTypePrimitive::INSTANCE)
+                .count(),
+            3
+        );
+        // the primitives of the object follow those of the world
+        let mut object = bvh.primitives[bvh.objects[0].offset as usize..].to_vec();
+        object.sort_unstable_by_key(|primitive| (primitive.type_, primitive.index));
+        assert_eq!(object, input.objects[0].primitives);
+
+        // the hierarchies of the world and the object find the same hits as testing every primitive
+        let shader = ray_tracer::cpu::Shader::new();
+        let expected = shader.execute(&input);
+        input.bvh = Some(bvh);
+        assert_eq!(shader.execute(&input).pixels, expected.pixels);
+    }
+
+    /// Levels below the node at `index`.
+    fn depth(bvh: &Bvh, index: usize) -> usize {
+        let node = &bvh.nodes[index];
+        if node.count > 0 {
+            return 0;
+        }
+        1 + depth(bvh, index + 1).max(depth(bvh, node.offset as usize))
+    }
+
+    #[test]
+    fn deep() {
+        // spheres doubling in size and distance from 2^-45 to 2^45, of which the surface area
+        // heuristic only splits off a few of the largest at every level, 36 levels deep without a
+        // limit
+        let spheres = (-45..45)
+            .map(|i| {
+                let center = glam::Vec3::new(2.0_f32.powi(i), 0.0, 0.0);
+                InputTypeSphere {
+                    center,
+                    radius: center.x * 0.1,
+                    end_center: center,
+                    material: InputTypeMaterial::new_lambertian(glam::Vec3::splat(0.5)),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut input = ray_tracer::InputType {
+            samples_per_pixel: 1,
+            seed: 0,
+            screen_size: glam::UVec2::new(16, 16),
+            view_box_position: glam::UVec2::ZERO,
+            view_box_size: glam::UVec2::new(16, 16),
+            camera: (&Scene::random(0).camera).into(),
+            spheres,
+            ..Default::default()
+        };
+        let bvh = Bvh::new(&input);
+        assert_eq!(depth(&bvh, 0), MAX_DEPTH);
+
+        // the limit only changes the order the primitives are tested in
+        let shader = ray_tracer::cpu::Shader::new();
+        let expected = shader.execute(&input);
+        input.bvh = Some(bvh);
+        assert_eq!(shader.execute(&input).pixels, expected.pixels);
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[test]
+    fn deep_instances() {
+        // the same for the world from 2^-60 to 2^60 and an object below that
+        let spheres = (-60..60)
+            .chain(-120..-60)
+            .map(|i| {
+                let center = glam::Vec3::new(2.0_f32.powi(i), 0.0, 0.0);
+                InputTypeSphere {
+                    center,
+                    radius: center.x * 0.1,
+                    end_center: center,
+                    material: InputTypeMaterial::new_lambertian(glam::Vec3::splat(0.5)),
+                }
+            })
+            .collect::<Vec<_>>();
+        let primitives = (120..spheres.len())
+            .map(|index| InputTypePrimitive {
+                type_: InputTypePrimitive::SPHERE,
+                index: index as u32,
+            })
+            .collect::<Vec<_>>();
+
+        // placed in the smallest leaf of the world, without a limit on the depth the traversal
+        // needs 67 stack entries
+        let mut input = ray_tracer::InputType {
+            samples_per_pixel: 1,
+            seed: 0,
+            screen_size: glam::UVec2::new(16, 16),
+            view_box_position: glam::UVec2::ZERO,
+            view_box_size: glam::UVec2::new(16, 16),
+            camera: (&Scene::random(0).camera).into(),
+            spheres,
+            objects: vec![InputTypeObject { primitives }],
+            instances: vec![InputTypeInstance::new(0, glam::Mat4::IDENTITY); 8],
+            ..Default::default()
+        };
+
+        let bvh = Bvh::new(&input);
+        assert!(depth(&bvh, 0) <= MAX_DEPTH);
+        assert!(depth(&bvh, bvh.objects[0].node as usize) <= MAX_DEPTH);
+        assert!(bvh.stack_size() <= STACK_SIZE);
+
+        let shader = ray_tracer::cpu::Shader::new();
+        let expected = shader.execute(&input);
+        input.bvh = Some(bvh);
+        assert_eq!(shader.execute(&input).pixels, expected.pixels);
+    }
+}
@@ -0,0 +1,2278 @@
+//! CPU reference implementation of `shader.wgsl`.
+//!
+//! Every function mirrors its WGSL counterpart so that output produced here can be diffed
+//! against output produced on the graphics card.
+
+use std::{f32::consts::PI, sync::Mutex, time::Instant};
+
+use chrono::Utc;
+
+use crate::output::srgb_eotf;
+
+use super::{
+    bvh::{self, Bvh},
+    AccumulationPixel, DispatchType, InputType, InputTypeDisk, InputTypeInstance,
+    InputTypeMaterial, InputTypePlane, InputTypePrimitive, InputTypeQuad, InputTypeSphere,
+    InputTypeTexture, InputTypeTriangle, InputTypeVertex, InputTypeVolume, OutputType,
+    OutputTypeAov, SKY_SCALE,
+};
+
+pub struct Shader {
+    threads: usize,
+}
+
+impl Default for Shader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shader {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            threads: std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get),
+        }
+    }
+
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn execute(&self, in_value: &InputType) -> OutputType {
+        println!(
+            "[{:?}] executing on {} cpu threads",
+            Utc::now().to_string(),
+            self.threads
+        );
+
+        // without a hierarchy, every primitive in the list of one without nodes is tested
+        let flat;
+        let in_value = if in_value.bvh.is_some() {
+            in_value
+        } else {
+            flat = InputType {
+                bvh: Some(Bvh::flat(in_value)),
+                ..in_value.clone()
+            };
+            &flat
+        };
+
+        let mut pixels = vec![
+            AccumulationPixel::default();
+            (in_value.view_box_size.y * in_value.view_box_size.x) as usize
+        ];
+
+        let start = Instant::now();
+        let mut samples = 0;
+
+        while let Some(count) = super::next_dispatch(in_value, samples, start) {
+            let dispatch = DispatchType {
+                samples: count,
+                view_box_position: in_value.view_box_position,
+                view_box_size: in_value.view_box_size,
+            };
+
+            if in_value.view_box_size.x > 0 {
+                // hand out one row of the view box at a time to whichever thread is free
+                let rows = Mutex::new(
+                    pixels
+                        .chunks_mut(in_value.view_box_size.x as usize)
+                        .enumerate(),
+                );
+
+                std::thread::scope(|scope| {
+                    for _ in 0..self.threads {
+                        scope.spawn(|| loop {
+                            let Some((y, row)) = rows.lock().unwrap().next() else {
+                                break;
+                            };
+                            #[allow(clippy::cast_possible_truncation)]
+                            for (x, pixel) in row.iter_mut().enumerate() {
+                                main(
+                                    in_value,
+                                    &dispatch,
+                                    glam::UVec2::new(x as u32, y as u32),
+                                    pixel,
+                                );
+                            }
+                        });
+                    }
+                });
+            }
+
+            samples += count;
+        }
+
+        println!("[{:?}] executed on cpu", Utc::now().to_string());
+
+        super::estimate(&pixels)
+    }
+}
+
+/*
+ * ============================================================================
+ * Mathematical Functions
+ * ============================================================================
+ */
+fn near_zero(e: glam::Vec3) -> bool {
+    let s = 0.000_000_01;
+    let a = e.abs();
+    a.x < s && a.y < s && a.z < s
+}
+
+fn reflect(v: glam::Vec3, n: glam::Vec3) -> glam::Vec3 {
+    v - 2.0 * v.dot(n) * n
+}
+
+fn reflectance(cosine: f32, ref_idx: f32) -> f32 {
+    let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+}
+
+/// Orthonormal basis around `w`, returns `a * u + b * v + c * w`.
+#[allow(clippy::many_single_char_names)]
+fn onb_local(w: glam::Vec3, a: f32, b: f32, c: f32) -> glam::Vec3 {
+    let up = if w.x.abs() > 0.9 {
+        glam::Vec3::Y
+    } else {
+        glam::Vec3::X
+    };
+    let v = w.cross(up).normalize();
+    let u = w.cross(v);
+    a * u + b * v + c * w
+}
+
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let a = pdf * pdf;
+    let b = other_pdf * other_pdf;
+    a / (a + b)
+}
+
+fn refract(uv: glam::Vec3, n: glam::Vec3, etai_over_etat: f32) -> glam::Vec3 {
+    let cos_theta = (-uv).dot(n).min(1.0);
+    let r_out_perp = etai_over_etat * (uv + cos_theta * n);
+    let r_out_parallel = -(1.0 - r_out_perp.length_squared()).abs().sqrt() * n;
+    r_out_perp + r_out_parallel
+}
+
+/*
+ * ============================================================================
+ * Random Functions
+ * ============================================================================
+ */
+// PCG hash, https://www.reedbeta.com/blog/hash-functions-for-gpu-rendering/
+fn pcg_hash(input: u32) -> u32 {
+    let state = input.wrapping_mul(747_796_405).wrapping_add(2_891_336_453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277_803_737);
+    (word >> 22) ^ word
+}
+
+struct Random {
+    state: u32,
+}
+
+impl Random {
+    fn new(pixel: glam::UVec2, sample: u32, seed: u32) -> Self {
+        Self {
+            state: pcg_hash(pixel.x ^ pcg_hash(pixel.y ^ pcg_hash(sample ^ pcg_hash(seed)))),
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn random(&mut self) -> f32 {
+        self.state = pcg_hash(self.state);
+        (self.state >> 8) as f32 / 16_777_216.0
+    }
+
+    fn random_between(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.random()
+    }
+
+    fn random_in_unit_disk(&mut self) -> glam::Vec3 {
+        loop {
+            let p = glam::Vec3::new(
+                self.random_between(-1.0, 1.0),
+                self.random_between(-1.0, 1.0),
+                0.0,
+            );
+            if p.length_squared() < 1.0 {
+                return p;
+            }
+        }
+    }
+
+    fn random_in_unit_sphere(&mut self) -> glam::Vec3 {
+        loop {
+            let p = self.random_vec3_between(-1.0, 1.0);
+            if p.length_squared() < 1.0 {
+                return p;
+            }
+        }
+    }
+
+    fn random_unit_vector(&mut self) -> glam::Vec3 {
+        self.random_in_unit_sphere().normalize()
+    }
+
+    fn random_vec3_between(&mut self, min: f32, max: f32) -> glam::Vec3 {
+        glam::Vec3::new(
+            self.random_between(min, max),
+            self.random_between(min, max),
+            self.random_between(min, max),
+        )
+    }
+}
+
+/*
+ * ============================================================================
+ * Camera
+ * ============================================================================
+ */
+struct Camera {
+    origin: glam::Vec3,
+    horizontal: glam::Vec3,
+    vertical: glam::Vec3,
+    lower_left_corner: glam::Vec3,
+    u: glam::Vec3,
+    v: glam::Vec3,
+    lens_radius: f32,
+    shutter_open: f32,
+    shutter_close: f32,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn camera_new(
+    lookfrom: glam::Vec3,
+    lookat: glam::Vec3,
+    vup: glam::Vec3,
+    vfov: f32,
+    aspect_ratio: f32,
+    aperture: f32,
+    focus_dist: f32,
+    shutter_open: f32,
+    shutter_close: f32,
+) -> Camera {
+    let theta = vfov.to_radians();
+    let h = (theta / 2.0).tan();
+    let viewport_height = 2.0 * h;
+    let viewport_width = aspect_ratio * viewport_height;
+
+    let w = (lookfrom - lookat).normalize();
+    let u = vup.cross(w).normalize();
+    let v = w.cross(u);
+
+    let origin = lookfrom;
+    let horizontal = focus_dist * viewport_width * u;
+    let vertical = focus_dist * viewport_height * v;
+    let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - focus_dist * w;
+
+    let lens_radius = aperture / 2.0;
+
+    Camera {
+        origin,
+        horizontal,
+        vertical,
+        lower_left_corner,
+        u,
+        v,
+        lens_radius,
+        shutter_open,
+        shutter_close,
+    }
+}
+
+fn camera_get_ray(camera: &Camera, s: f32, t: f32, random: &mut Random) -> Ray {
+    let rd = camera.lens_radius * random.random_in_unit_disk();
+    let offset = camera.u * rd.x + camera.v * rd.y;
+    let time = random.random_between(camera.shutter_open, camera.shutter_close);
+
+    Ray {
+        origin: camera.origin + offset,
+        direction: camera.lower_left_corner + s * camera.horizontal + t * camera.vertical
+            - camera.origin
+            - offset,
+        time,
+    }
+}
+
+/*
+ * ============================================================================
+ * Hit Record
+ * ============================================================================
+ */
+struct HitRecord<'a> {
+    point: glam::Vec3,
+    normal: glam::Vec3,
+    t: f32,
+    front_face: bool,
+    material: &'a InputTypeMaterial,
+    primitive: InputTypePrimitive,
+    /// texture coordinates of the point on the surface
+    uv: glam::Vec2,
+}
+
+impl<'a> HitRecord<'a> {
+    fn new(
+        ray: &Ray,
+        point: glam::Vec3,
+        outward_normal: glam::Vec3,
+        t: f32,
+        material: &'a InputTypeMaterial,
+        primitive: InputTypePrimitive,
+        uv: glam::Vec2,
+    ) -> Self {
+        let front_face = ray.direction.dot(outward_normal) < 0.0;
+        let normal = if front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+        Self {
+            point,
+            normal,
+            t,
+            front_face,
+            material,
+            primitive,
+            uv,
+        }
+    }
+}
+
+/*
+ * ============================================================================
+ * Material
+ * ============================================================================
+ */
+struct MaterialScatterResult {
+    some: bool,
+    attenuation: glam::Vec3,
+    scattered: Ray,
+}
+
+fn material_scatter(
+    in_value: &InputType,
+    material: &InputTypeMaterial,
+    ray_in: &Ray,
+    hit_record: &HitRecord,
+    random: &mut Random,
+) -> MaterialScatterResult {
+    match material.type_ {
+        1 => {
+            let mut scatter_direction = hit_record.normal + random.random_unit_vector();
+
+            if near_zero(scatter_direction) {
+                scatter_direction = hit_record.normal;
+            }
+
+            MaterialScatterResult {
+                some: true,
+                attenuation: material_texture_albedo(in_value, material, hit_record),
+                scattered: Ray {
+                    origin: hit_record.point,
+                    direction: scatter_direction,
+                    time: ray_in.time,
+                },
+            }
+        }
+        2 => {
+            let reflected = reflect(ray_in.direction.normalize(), hit_record.normal);
+            let scattered = Ray {
+                origin: hit_record.point,
+                direction: reflected + material.fuzz * random.random_in_unit_sphere(),
+                time: ray_in.time,
+            };
+            MaterialScatterResult {
+                some: scattered.direction.dot(hit_record.normal) > 0.0,
+                attenuation: material_texture_albedo(in_value, material, hit_record),
+                scattered,
+            }
+        }
+        3 => {
+            let refraction_ratio = if hit_record.front_face {
+                1.0 / material.index_of_refraction
+            } else {
+                material.index_of_refraction
+            };
+
+            let unit_direction = ray_in.direction.normalize();
+            let cos_theta = (-unit_direction).dot(hit_record.normal).min(1.0);
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+            let cannot_refract = refraction_ratio * sin_theta > 1.0;
+            let direction =
+                if cannot_refract || reflectance(cos_theta, refraction_ratio) > random.random() {
+                    reflect(unit_direction, hit_record.normal)
+                } else {
+                    refract(unit_direction, hit_record.normal, refraction_ratio)
+                };
+
+            MaterialScatterResult {
+                some: true,
+                attenuation: glam::Vec3::ONE,
+                scattered: Ray {
+                    origin: hit_record.point,
+                    direction,
+                    time: ray_in.time,
+                },
+            }
+        }
+        5 => MaterialScatterResult {
+            some: true,
+            attenuation: material_texture_albedo(in_value, material, hit_record),
+            scattered: Ray {
+                origin: hit_record.point,
+                direction: random.random_unit_vector(),
+                time: ray_in.time,
+            },
+        },
+        _ => MaterialScatterResult {
+            some: false,
+            attenuation: glam::Vec3::ZERO,
+            scattered: Ray {
+                origin: glam::Vec3::ZERO,
+                direction: glam::Vec3::ZERO,
+                time: 0.0,
+            },
+        },
+    }
+}
+
+fn material_emitted(material: &InputTypeMaterial) -> glam::Vec3 {
+    if material.type_ == 4 {
+        return material.albedo;
+    }
+    glam::Vec3::ZERO
+}
+
+/// Albedo at the hit, multiplied by the texture of the material.
+fn material_texture_albedo(
+    in_value: &InputType,
+    material: &InputTypeMaterial,
+    hit_record: &HitRecord,
+) -> glam::Vec3 {
+    if material.texture == InputTypeMaterial::NO_TEXTURE {
+        return material.albedo;
+    }
+    material.albedo * texture_value(in_value, material.texture, hit_record.uv, hit_record.point)
+}
+
+/// Reflectance seen by the camera, used by the albedo AOV.
+fn material_albedo(
+    in_value: &InputType,
+    material: &InputTypeMaterial,
+    hit_record: &HitRecord,
+) -> glam::Vec3 {
+    match material.type_ {
+        1 | 2 | 5 => material_texture_albedo(in_value, material, hit_record),
+        3 => glam::Vec3::ONE,
+        4 => material.albedo.min(glam::Vec3::ONE),
+        _ => glam::Vec3::ZERO,
+    }
+}
+
+/*
+ * ============================================================================
+ * Texture
+ * ============================================================================
+ */
+#[allow(clippy::cast_sign_loss)]
+fn perlin_hash(cell: glam::IVec3) -> u32 {
+    pcg_hash(cell.x as u32 ^ pcg_hash(cell.y as u32 ^ pcg_hash(cell.z as u32)))
+}
+
+/// Offset to a corner of the lattice along one of the twelve gradients of improved Perlin noise,
+/// picked by the hash of the corner.
+fn perlin_gradient(hash: u32, offset: glam::Vec3) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { offset.x } else { offset.y };
+    let v = if h < 4 {
+        offset.y
+    } else if h == 12 || h == 14 {
+        offset.x
+    } else {
+        offset.z
+    };
+    let u = if h & 1 == 0 { u } else { -u };
+    let v = if h & 2 == 0 { v } else { -v };
+    u + v
+}
+
+/// Gradient noise between -1 and 1, hashed instead of looked up so that it needs no tables.
+#[allow(clippy::cast_possible_truncation)]
+fn perlin_noise(p: glam::Vec3) -> f32 {
+    let floored = p.floor();
+    let cell = floored.as_ivec3();
+    let f = p - floored;
+    // quintic fade, smooth up to the second derivative across cells
+    let w = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+
+    let mut noise = 0.0;
+    for corner in 0..8_u32 {
+        let c = glam::UVec3::new(corner & 1, (corner >> 1) & 1, corner >> 2);
+        let weight = glam::Vec3::select(c.cmpeq(glam::UVec3::ONE), w, 1.0 - w);
+        noise += weight.x
+            * weight.y
+            * weight.z
+            * perlin_gradient(perlin_hash(cell + c.as_ivec3()), f - c.as_vec3());
+    }
+    noise
+}
+
+fn perlin_turbulence(p: glam::Vec3, octaves: u32) -> f32 {
+    let mut turbulence = 0.0;
+    let mut point = p;
+    let mut weight = 1.0;
+    for _ in 0..octaves {
+        turbulence += weight * perlin_noise(point);
+        weight *= 0.5;
+        point *= 2.0;
+    }
+    turbulence.abs()
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn texture_value(
+    in_value: &InputType,
+    index: u32,
+    uv: glam::Vec2,
+    point: glam::Vec3,
+) -> glam::Vec3 {
+    let texture: &InputTypeTexture = &in_value.textures[index as usize];
+    match texture.type_ {
+        0 => texture.color,
+        1 => {
+            // centred on the lattice, so that planes through it are not split between two cubes
+            let cell = (texture.scale * point + 0.5).floor().as_ivec3();
+            if (cell.x + cell.y + cell.z) & 1 == 0 {
+                texture.color
+            } else {
+                texture.odd
+            }
+        }
+        2 => {
+            let cell = (texture.scale * uv).floor().as_ivec2();
+            if (cell.x + cell.y) & 1 == 0 {
+                texture.color
+            } else {
+                texture.odd
+            }
+        }
+        3 => texture.color * 0.5 * (1.0 + perlin_noise(texture.scale * point)),
+        4 => texture.color * perlin_turbulence(texture.scale * point, texture.octaves),
+        5 => {
+            let size = texture.size;
+            if size.x == 0 || size.y == 0 {
+                return glam::Vec3::ZERO;
+            }
+            // repeats, with v going up the image
+            let u = uv.x - uv.x.floor();
+            let v = 1.0 - (uv.y - uv.y.floor());
+            let x = ((u * size.x as f32) as u32).min(size.x - 1);
+            let y = ((v * size.y as f32) as u32).min(size.y - 1);
+            let rgba = in_value.texels[(texture.offset + y * size.x + x) as usize].to_le_bytes();
+            glam::Vec3::new(
+                srgb_eotf(f32::from(rgba[0]) / 255.0),
+                srgb_eotf(f32::from(rgba[1]) / 255.0),
+                srgb_eotf(f32::from(rgba[2]) / 255.0),
+            )
+        }
+        _ => glam::Vec3::ZERO,
+    }
+}
+
+/*
+ * ============================================================================
+ * Background
+ * ============================================================================
+ */
+fn background_color(in_value: &InputType, ray: &Ray) -> glam::Vec3 {
+    match in_value.background.type_ {
+        0 => {
+            let unit_direction = ray.direction.normalize();
+            let t = 0.5 * (unit_direction.y + 1.0);
+            (1.0 - t) * glam::Vec3::ONE + t * glam::Vec3::new(0.5, 0.7, 1.0)
+        }
+        1 => in_value.background.color,
+        3 => environment_color(in_value, ray.direction),
+        4 => sky_color(in_value, ray.direction),
+        _ => glam::Vec3::ZERO,
+    }
+}
+
+fn rotate_y(v: glam::Vec3, angle: f32) -> glam::Vec3 {
+    let c = angle.cos();
+    let s = angle.sin();
+    glam::Vec3::new(c * v.x + s * v.z, v.y, c * v.z - s * v.x)
+}
+
+/// Equirectangular texel seen in `direction`, the middle column looks down -z.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn environment_texel(in_value: &InputType, direction: glam::Vec3) -> usize {
+    let size = in_value.environment.size;
+    let unit_direction = rotate_y(direction.normalize(), -in_value.background.rotation);
+    let u = unit_direction.x.atan2(-unit_direction.z) / (2.0 * PI) + 0.5;
+    let v = unit_direction.y.clamp(-1.0, 1.0).acos() / PI;
+    let x = ((u.max(0.0) * size.x as f32) as u32).min(size.x - 1);
+    let y = ((v.max(0.0) * size.y as f32) as u32).min(size.y - 1);
+    (y * size.x + x) as usize
+}
+
+fn environment_color(in_value: &InputType, direction: glam::Vec3) -> glam::Vec3 {
+    if in_value.environment.size.x == 0 || in_value.environment.size.y == 0 {
+        return glam::Vec3::ZERO;
+    }
+
+    in_value.background.color
+        * in_value.environment.texels[environment_texel(in_value, direction)].radiance
+}
+
+/// Perez function of each channel, for the cosine of the angle to the zenith and to the sun.
+fn sky_perez(in_value: &InputType, cos_theta: f32, cos_gamma: f32) -> glam::Vec3 {
+    let sky = &in_value.background.sky;
+    let gamma = cos_gamma.clamp(-1.0, 1.0).acos();
+    (1.0 + sky.a * (sky.b / cos_theta.max(0.001)).exp())
+        * (1.0 + sky.c * (sky.d * gamma).exp() + sky.e * cos_gamma * cos_gamma)
+}
+
+fn xyz_to_rgb(xyz: glam::Vec3) -> glam::Vec3 {
+    glam::Vec3::new(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    )
+    .max(glam::Vec3::ZERO)
+}
+
+fn sky_color(in_value: &InputType, direction: glam::Vec3) -> glam::Vec3 {
+    let sky = &in_value.background.sky;
+    let unit_direction = direction.normalize();
+    if unit_direction.y < 0.0 {
+        return sky.ground;
+    }
+
+    let cos_gamma = unit_direction.dot(sky.sun_direction);
+    let yxy = sky.zenith * sky_perez(in_value, unit_direction.y, cos_gamma);
+    let mut color = xyz_to_rgb(glam::Vec3::new(
+        yxy.y * yxy.x / yxy.z,
+        yxy.x,
+        (1.0 - yxy.y - yxy.z) * yxy.x / yxy.z,
+    )) * SKY_SCALE;
+    if cos_gamma >= sky.sun_cos_radius {
+        color += sky.sun_radiance;
+    }
+    color
+}
+
+/*
+ * ============================================================================
+ * Ray
+ * ============================================================================
+ */
+#[derive(Clone, Copy)]
+struct Ray {
+    origin: glam::Vec3,
+    direction: glam::Vec3,
+    /// Within the shutter interval of the camera, the same for every bounce of a path.
+    time: f32,
+}
+
+fn ray_at(ray: &Ray, t: f32) -> glam::Vec3 {
+    ray.origin + t * ray.direction
+}
+
+fn ray_color(
+    ray: Ray,
+    in_value: &InputType,
+    random: &mut Random,
+    primary_aov: &mut OutputTypeAov,
+) -> glam::Vec3 {
+    *primary_aov = OutputTypeAov::default();
+
+    let mut current_ray = ray;
+    let mut throughput = glam::Vec3::ONE;
+    let mut color = glam::Vec3::ZERO;
+
+    // probability density of the previous diffuse bounce, zero when emission is not weighted
+    let mut previous_pdf = 0.0;
+    let mut previous_point = glam::Vec3::ZERO;
+
+    for depth in 0..in_value.max_depth {
+        let Some(hit_record) = world_hit(in_value, &current_ray, 0.001, 10000.0, random) else {
+            let mut radiance = background_color(in_value, &current_ray);
+            if previous_pdf > 0.0 {
+                radiance *= power_heuristic(
+                    previous_pdf,
+                    light_pdf_background(in_value, current_ray.direction),
+                );
+            }
+            return color + throughput * radiance;
+        };
+
+        if depth == 0 {
+            *primary_aov = aov_new(in_value, &current_ray, &hit_record);
+        }
+
+        let mut radiance = material_emitted(hit_record.material);
+        if previous_pdf > 0.0 && hit_record.material.type_ == 4 {
+            radiance *= power_heuristic(
+                previous_pdf,
+                light_pdf(in_value, previous_point, current_ray.time, &hit_record),
+            );
+        }
+
+        let next_event_estimation =
+            in_value.next_event_estimation && hit_record.material.type_ == 1;
+        if next_event_estimation {
+            radiance += light_direct(in_value, &hit_record, current_ray.time, random);
+        }
+
+        color += throughput * radiance;
+
+        let material_scatter_result = material_scatter(
+            in_value,
+            hit_record.material,
+            &current_ray,
+            &hit_record,
+            random,
+        );
+
+        if !material_scatter_result.some {
+            // keep the emission of the absorbing hit
+            return color;
+        }
+
+        throughput *= material_scatter_result.attenuation;
+        current_ray = material_scatter_result.scattered;
+        previous_pdf = if next_event_estimation {
+            hit_record
+                .normal
+                .dot(current_ray.direction.normalize())
+                .max(0.0)
+                / PI
+        } else {
+            0.0
+        };
+        previous_point = hit_record.point;
+
+        // terminate dim paths, the survivors carry their energy
+        if in_value
+            .russian_roulette_depth
+            .is_some_and(|russian_roulette_depth| depth + 1 >= russian_roulette_depth)
+        {
+            let survival = throughput.max_element().min(1.0);
+            if random.random() >= survival {
+                return color;
+            }
+            throughput /= survival;
+        }
+    }
+
+    color
+}
+
+/*
+ * ============================================================================
+ * Sphere
+ * ============================================================================
+ */
+/// Moves linearly, and stays put before and after, where the hierarchy stops bounding it.
+fn sphere_center(sphere: &InputTypeSphere, time: f32) -> glam::Vec3 {
+    sphere.center + time.clamp(0.0, 1.0) * (sphere.end_center - sphere.center)
+}
+
+fn sphere_hit<'a>(
+    sphere: &'a InputTypeSphere,
+    primitive: InputTypePrimitive,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    // the zeroed padding of an empty sphere array
+    if sphere.radius == 0.0 {
+        return None;
+    }
+
+    let center = sphere_center(sphere, ray.time);
+    let oc = ray.origin - center;
+    let a = ray.direction.length_squared();
+    let half_b = oc.dot(ray.direction);
+    let c = oc.length_squared() - sphere.radius * sphere.radius;
+    let discriminant = half_b * half_b - a * c;
+
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let sqrtd = discriminant.sqrt();
+    let mut root = (-half_b - sqrtd) / a;
+    if root < t_min || t_max < root {
+        root = (-half_b + sqrtd) / a;
+        if root < t_min || t_max < root {
+            return None;
+        }
+    }
+
+    let point = ray_at(ray, root);
+    let outward_normal = (point - center) / sphere.radius;
+    // longitude from -x around through +z, and latitude from the bottom
+    let uv = glam::Vec2::new(
+        ((-outward_normal.z).atan2(outward_normal.x) + PI) / (2.0 * PI),
+        (-outward_normal.y).clamp(-1.0, 1.0).acos() / PI,
+    );
+    Some(HitRecord::new(
+        ray,
+        point,
+        outward_normal,
+        root,
+        &sphere.material,
+        primitive,
+        uv,
+    ))
+}
+
+/*
+ * ============================================================================
+ * Triangle
+ * ============================================================================
+ */
+// Möller–Trumbore, https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+#[allow(clippy::many_single_char_names)]
+fn triangle_hit<'a>(
+    triangle: &'a InputTypeTriangle,
+    primitive: InputTypePrimitive,
+    vertices: &[InputTypeVertex],
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    let v0 = &vertices[triangle.indices.x as usize];
+    let v1 = &vertices[triangle.indices.y as usize];
+    let v2 = &vertices[triangle.indices.z as usize];
+
+    let edge1 = v1.position - v0.position;
+    let edge2 = v2.position - v0.position;
+    let h = ray.direction.cross(edge2);
+    let a = edge1.dot(h);
+
+    // parallel to the ray, or degenerate
+    if a.abs() < 0.000_000_000_1 {
+        return None;
+    }
+
+    let f = 1.0 / a;
+    let s = ray.origin - v0.position;
+    let u = f * s.dot(h);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+    let v = f * ray.direction.dot(q);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = f * edge2.dot(q);
+    if t < t_min || t_max < t {
+        return None;
+    }
+
+    let mut outward_normal = (1.0 - u - v) * v0.normal + u * v1.normal + v * v2.normal;
+    if near_zero(outward_normal) {
+        outward_normal = edge1.cross(edge2);
+    }
+
+    Some(HitRecord::new(
+        ray,
+        ray_at(ray, t),
+        outward_normal.normalize(),
+        t,
+        &triangle.material,
+        primitive,
+        glam::Vec2::new(u, v),
+    ))
+}
+
+/*
+ * ============================================================================
+ * Plane
+ * ============================================================================
+ */
+/// Distance along the ray to the plane through `point` facing the unit `normal`, unless it is
+/// parallel or outside of `[t_min, t_max]`.
+fn plane_distance(
+    point: glam::Vec3,
+    normal: glam::Vec3,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<f32> {
+    let denominator = normal.dot(ray.direction);
+    if denominator.abs() < 0.000_000_01 {
+        return None;
+    }
+
+    let t = (point - ray.origin).dot(normal) / denominator;
+    if t < t_min || t_max < t {
+        return None;
+    }
+    Some(t)
+}
+
+fn plane_hit<'a>(
+    plane: &'a InputTypePlane,
+    primitive: InputTypePrimitive,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    // the zeroed padding of an empty plane array
+    if near_zero(plane.normal) {
+        return None;
+    }
+
+    let normal = plane.normal.normalize();
+    let t = plane_distance(plane.point, normal, ray, t_min, t_max)?;
+
+    // distances along two axes of the plane
+    let point = ray_at(ray, t);
+    let planar = point - plane.point;
+    let uv = glam::Vec2::new(
+        planar.dot(onb_local(normal, 1.0, 0.0, 0.0)),
+        planar.dot(onb_local(normal, 0.0, 1.0, 0.0)),
+    );
+    Some(HitRecord::new(
+        ray,
+        point,
+        normal,
+        t,
+        &plane.material,
+        primitive,
+        uv,
+    ))
+}
+
+/*
+ * ============================================================================
+ * Quad
+ * ============================================================================
+ */
+fn quad_hit<'a>(
+    quad: &'a InputTypeQuad,
+    primitive: InputTypePrimitive,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    // degenerate, or the zeroed padding of an empty quad array
+    let n = quad.u.cross(quad.v);
+    if near_zero(n) {
+        return None;
+    }
+
+    let normal = n.normalize();
+    let t = plane_distance(quad.corner, normal, ray, t_min, t_max)?;
+
+    // coordinates of the hit along the edges
+    let point = ray_at(ray, t);
+    let planar = point - quad.corner;
+    let w = n / n.dot(n);
+    let alpha = w.dot(planar.cross(quad.v));
+    let beta = w.dot(quad.u.cross(planar));
+    if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+        return None;
+    }
+
+    Some(HitRecord::new(
+        ray,
+        point,
+        normal,
+        t,
+        &quad.material,
+        primitive,
+        glam::Vec2::new(alpha, beta),
+    ))
+}
+
+/*
+ * ============================================================================
+ * Disk
+ * ============================================================================
+ */
+fn disk_hit<'a>(
+    disk: &'a InputTypeDisk,
+    primitive: InputTypePrimitive,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    // the zeroed padding of an empty disk array
+    if disk.radius == 0.0 {
+        return None;
+    }
+
+    let normal = disk.normal.normalize();
+    let t = plane_distance(disk.center, normal, ray, t_min, t_max)?;
+
+    let point = ray_at(ray, t);
+    let planar = point - disk.center;
+    if planar.length_squared() > disk.radius * disk.radius {
+        return None;
+    }
+
+    // the square around the disk spans 0..1
+    let uv = 0.5
+        + 0.5
+            * glam::Vec2::new(
+                planar.dot(onb_local(normal, 1.0, 0.0, 0.0)),
+                planar.dot(onb_local(normal, 0.0, 1.0, 0.0)),
+            )
+            / disk.radius;
+    Some(HitRecord::new(
+        ray,
+        point,
+        normal,
+        t,
+        &disk.material,
+        primitive,
+        uv,
+    ))
+}
+
+/*
+ * ============================================================================
+ * Volume
+ * ============================================================================
+ */
+/// Distances the ray enters and leaves the boundary at, the first is larger when it misses.
+fn volume_boundary(volume: &InputTypeVolume, ray: &Ray) -> (f32, f32) {
+    if volume.boundary == 1 {
+        let inverse_direction = 1.0 / ray.direction;
+        let t0 = (volume.center - volume.half_size - ray.origin) * inverse_direction;
+        let t1 = (volume.center + volume.half_size - ray.origin) * inverse_direction;
+        return (t0.min(t1).max_element(), t0.max(t1).min_element());
+    }
+
+    let oc = ray.origin - volume.center;
+    let a = ray.direction.length_squared();
+    let half_b = oc.dot(ray.direction);
+    let c = oc.length_squared() - volume.radius * volume.radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return (1.0, 0.0);
+    }
+
+    let sqrtd = discriminant.sqrt();
+    ((-half_b - sqrtd) / a, (-half_b + sqrtd) / a)
+}
+
+/// Scatters at an exponentially distributed distance into the volume, rays starting inside it too.
+fn volume_hit<'a>(
+    volume: &'a InputTypeVolume,
+    primitive: InputTypePrimitive,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+    random: &mut Random,
+) -> Option<HitRecord<'a>> {
+    if volume.density <= 0.0 {
+        return None;
+    }
+
+    let (t_enter, t_exit) = volume_boundary(volume, ray);
+    let t_enter = t_enter.max(t_min);
+    let t_exit = t_exit.min(t_max);
+    if t_enter >= t_exit {
+        return None;
+    }
+
+    let ray_length = ray.direction.length();
+    let distance_inside = (t_exit - t_enter) * ray_length;
+    let hit_distance = -(1.0 - random.random()).ln() / volume.density;
+    if hit_distance > distance_inside {
+        return None;
+    }
+
+    // the normal is arbitrary, it faces the ray for the aovs
+    let t = t_enter + hit_distance / ray_length;
+    Some(HitRecord {
+        point: ray_at(ray, t),
+        normal: -ray.direction / ray_length,
+        t,
+        front_face: true,
+        material: &volume.material,
+        primitive,
+        uv: glam::Vec2::ZERO,
+    })
+}
+
+/*
+ * ============================================================================
+ * Instance
+ * ============================================================================
+ */
+/// The direction is left unnormalised, so distances along the ray are the same in both spaces.
+fn instance_ray(instance: &InputTypeInstance, ray: &Ray) -> Ray {
+    Ray {
+        origin: instance.world_to_object.transform_point3(ray.origin),
+        direction: instance.world_to_object.transform_vector3(ray.direction),
+        time: ray.time,
+    }
+}
+
+/// Moves a hit in the space of the object of an instance into the world.
+fn instance_hit_record<'a>(
+    in_value: &'a InputType,
+    hit_record: &HitRecord<'a>,
+    index: u32,
+) -> HitRecord<'a> {
+    let instance = &in_value.instances[index as usize];
+    // the inverse transpose keeps normals perpendicular under scaling and shearing
+    let normal = instance
+        .world_to_object
+        .transpose()
+        .transform_vector3(hit_record.normal)
+        .normalize();
+    HitRecord {
+        point: instance.object_to_world.transform_point3(hit_record.point),
+        normal,
+        primitive: InputTypePrimitive {
+            type_: InputTypePrimitive::INSTANCE,
+            index,
+        },
+        ..*hit_record
+    }
+}
+
+fn instance_hit<'a>(
+    in_value: &'a InputType,
+    bvh: &Bvh,
+    index: u32,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+    random: &mut Random,
+) -> Option<HitRecord<'a>> {
+    let instance = &in_value.instances[index as usize];
+    let object = &bvh.objects[instance.object as usize];
+    let object_ray = instance_ray(instance, ray);
+
+    let mut hit_record = None;
+    let mut closest_so_far = t_max;
+
+    for &primitive in
+        &bvh.primitives[object.offset as usize..(object.offset + object.count) as usize]
+    {
+        if let Some(h) = primitive_hit(
+            in_value,
+            primitive,
+            &object_ray,
+            t_min,
+            closest_so_far,
+            random,
+        ) {
+            closest_so_far = h.t;
+            hit_record = Some(h);
+        }
+    }
+
+    hit_record.map(|h| instance_hit_record(in_value, &h, index))
+}
+
+/*
+ * ============================================================================
+ * Bounding Volume Hierarchy
+ * ============================================================================
+ */
+/// Slab test, returns the distance the ray enters the box when it hits within `[t_min, t_max]`.
+fn aabb_hit(
+    box_min: glam::Vec3,
+    box_max: glam::Vec3,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<f32> {
+    let inverse_direction = 1.0 / ray.direction;
+    let t0 = (box_min - ray.origin) * inverse_direction;
+    let t1 = (box_max - ray.origin) * inverse_direction;
+    let t_small = t0.min(t1);
+    let t_big = t0.max(t1);
+    let t_enter = t_small.max_element().max(t_min);
+    let t_exit = t_big.min_element().min(t_max);
+
+    if t_enter > t_exit {
+        return None;
+    }
+    Some(t_enter)
+}
+
+fn primitive_hit<'a>(
+    in_value: &'a InputType,
+    primitive: InputTypePrimitive,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+    random: &mut Random,
+) -> Option<HitRecord<'a>> {
+    match primitive.type_ {
+        InputTypePrimitive::SPHERE => sphere_hit(
+            &in_value.spheres[primitive.index as usize],
+            primitive,
+            ray,
+            t_min,
+            t_max,
+        ),
+        InputTypePrimitive::TRIANGLE => triangle_hit(
+            &in_value.triangles[primitive.index as usize],
+            primitive,
+            &in_value.vertices,
+            ray,
+            t_min,
+            t_max,
+        ),
+        InputTypePrimitive::QUAD => quad_hit(
+            &in_value.quads[primitive.index as usize],
+            primitive,
+            ray,
+            t_min,
+            t_max,
+        ),
+        InputTypePrimitive::DISK => disk_hit(
+            &in_value.disks[primitive.index as usize],
+            primitive,
+            ray,
+            t_min,
+            t_max,
+        ),
+        InputTypePrimitive::VOLUME => volume_hit(
+            &in_value.volumes[primitive.index as usize],
+            primitive,
+            ray,
+            t_min,
+            t_max,
+            random,
+        ),
+        _ => None,
+    }
+}
+
+fn bvh_hit<'a>(
+    in_value: &'a InputType,
+    bvh: &Bvh,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+    random: &mut Random,
+) -> Option<HitRecord<'a>> {
+    let mut hit_record = None;
+    let mut closest_so_far = t_max;
+
+    // every node along with the instance whose object it belongs to, `None` for the world,
+    // `Bvh::new` limits the depth of the hierarchy so that it never holds more than `STACK_SIZE`
+    let mut stack = Vec::with_capacity(bvh::STACK_SIZE);
+
+    let root = bvh.nodes.first()?;
+    if aabb_hit(root.min, root.max, ray, t_min, closest_so_far).is_some() {
+        stack.push((0, None));
+    }
+
+    // the ray in the space of the nodes being visited
+    let mut instance = None;
+    let mut space_ray = *ray;
+
+    while let Some((index, node_instance)) = stack.pop() {
+        if node_instance != instance {
+            instance = node_instance;
+            space_ray = match instance {
+                None => *ray,
+                Some(instance) => instance_ray(&in_value.instances[instance as usize], ray),
+            };
+        }
+        let node = &bvh.nodes[index];
+
+        if node.count > 0 {
+            for &primitive in
+                &bvh.primitives[node.offset as usize..(node.offset + node.count) as usize]
+            {
+                if primitive.type_ == InputTypePrimitive::INSTANCE {
+                    // visit the hierarchy of the object later, in the space of the object
+                    let object_instance = &in_value.instances[primitive.index as usize];
+                    let object_ray = instance_ray(object_instance, ray);
+                    let object = &bvh.objects[object_instance.object as usize];
+                    let object_root = &bvh.nodes[object.node as usize];
+                    if aabb_hit(
+                        object_root.min,
+                        object_root.max,
+                        &object_ray,
+                        t_min,
+                        closest_so_far,
+                    )
+                    .is_some()
+                    {
+                        stack.push((object.node as usize, Some(primitive.index)));
+                    }
+                    continue;
+                }
+
+                if let Some(h) = primitive_hit(
+                    in_value,
+                    primitive,
+                    &space_ray,
+                    t_min,
+                    closest_so_far,
+                    random,
+                ) {
+                    closest_so_far = h.t;
+                    hit_record = Some(match instance {
+                        None => h,
+                        Some(instance) => instance_hit_record(in_value, &h, instance),
+                    });
+                }
+            }
+            continue;
+        }
+
+        // push the farther child first so the nearer child is visited first
+        let mut first = (index + 1, &bvh.nodes[index + 1]);
+        let mut second = (node.offset as usize, &bvh.nodes[node.offset as usize]);
+        let mut t_first = aabb_hit(first.1.min, first.1.max, &space_ray, t_min, closest_so_far);
+        let mut t_second = aabb_hit(
+            second.1.min,
+            second.1.max,
+            &space_ray,
+            t_min,
+            closest_so_far,
+        );
+        if t_first.is_none()
+            || t_second
+                .zip(t_first)
+                .is_some_and(|(t_second, t_first)| t_second < t_first)
+        {
+            std::mem::swap(&mut first, &mut second);
+            std::mem::swap(&mut t_first, &mut t_second);
+        }
+
+        for (child, t) in [(second.0, t_second), (first.0, t_first)] {
+            if t.is_some() {
+                stack.push((child, instance));
+            }
+        }
+    }
+
+    hit_record
+}
+
+/*
+ * ============================================================================
+ * World
+ * ============================================================================
+ */
+fn world_hit<'a>(
+    in_value: &'a InputType,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+    random: &mut Random,
+) -> Option<HitRecord<'a>> {
+    let mut hit_record = None;
+    let mut closest_so_far = t_max;
+
+    // planes are unbounded, so the hierarchy leaves them out
+    for (index, plane) in (0..).zip(&in_value.planes) {
+        let primitive = InputTypePrimitive {
+            type_: InputTypePrimitive::PLANE,
+            index,
+        };
+        if let Some(h) = plane_hit(plane, primitive, ray, t_min, closest_so_far) {
+            closest_so_far = h.t;
+            hit_record = Some(h);
+        }
+    }
+
+    // `execute` gives every input a hierarchy, which is flat when brute forcing
+    let Some(bvh) = &in_value.bvh else {
+        return hit_record;
+    };
+
+    if !bvh.nodes.is_empty() {
+        return bvh_hit(in_value, bvh, ray, t_min, closest_so_far, random).or(hit_record);
+    }
+
+    for &primitive in bvh.world_primitives() {
+        let h = if primitive.type_ == InputTypePrimitive::INSTANCE {
+            instance_hit(
+                in_value,
+                bvh,
+                primitive.index,
+                ray,
+                t_min,
+                closest_so_far,
+                random,
+            )
+        } else {
+            primitive_hit(in_value, primitive, ray, t_min, closest_so_far, random)
+        };
+        if let Some(h) = h {
+            closest_so_far = h.t;
+            hit_record = Some(h);
+        }
+    }
+
+    hit_record
+}
+
+/*
+ * ============================================================================
+ * Light
+ * ============================================================================
+ */
+struct LightSample {
+    direction: glam::Vec3,
+    /// solid angle probability density, zero when there is no sample
+    pdf: f32,
+    primitive: InputTypePrimitive,
+}
+
+impl LightSample {
+    fn none() -> Self {
+        Self {
+            direction: glam::Vec3::ZERO,
+            pdf: 0.0,
+            primitive: InputTypePrimitive::default(),
+        }
+    }
+}
+
+fn sphere_light_pdf(sphere: &InputTypeSphere, origin: glam::Vec3, time: f32) -> f32 {
+    let distance_squared = (sphere_center(sphere, time) - origin).length_squared();
+    let radius_squared = sphere.radius * sphere.radius;
+    if distance_squared <= radius_squared {
+        return 0.0;
+    }
+
+    // 1 - cos_theta_max without cancellation for small or distant spheres
+    let sin_theta_max_squared = radius_squared / distance_squared;
+    let cos_theta_max = (1.0 - sin_theta_max_squared).sqrt();
+    (1.0 + cos_theta_max) / (2.0 * PI * sin_theta_max_squared)
+}
+
+fn sphere_light_sample(
+    sphere: &InputTypeSphere,
+    origin: glam::Vec3,
+    time: f32,
+    random: &mut Random,
+) -> LightSample {
+    let r1 = random.random();
+    let r2 = random.random();
+
+    let pdf = sphere_light_pdf(sphere, origin, time);
+    if pdf <= 0.0 {
+        return LightSample::none();
+    }
+
+    // uniform in the cone of directions towards the sphere
+    let center = sphere_center(sphere, time);
+    let cos_theta_max =
+        (1.0 - sphere.radius * sphere.radius / (center - origin).length_squared()).sqrt();
+    let z = 1.0 + r2 * (cos_theta_max - 1.0);
+    let phi = 2.0 * PI * r1;
+    let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+    let direction = onb_local(
+        (center - origin).normalize(),
+        phi.cos() * sin_theta,
+        phi.sin() * sin_theta,
+        z,
+    );
+
+    LightSample {
+        direction: direction.normalize(),
+        pdf,
+        primitive: InputTypePrimitive::default(),
+    }
+}
+
+fn triangle_light_pdf(
+    triangle: &InputTypeTriangle,
+    vertices: &[InputTypeVertex],
+    origin: glam::Vec3,
+    point: glam::Vec3,
+) -> f32 {
+    let v0 = vertices[triangle.indices.x as usize].position;
+    let v1 = vertices[triangle.indices.y as usize].position;
+    let v2 = vertices[triangle.indices.z as usize].position;
+
+    let normal = (v1 - v0).cross(v2 - v0);
+    let area = 0.5 * normal.length();
+    let to_point = point - origin;
+    let distance_squared = to_point.length_squared();
+    let cosine = normal.dot(to_point).abs() / (2.0 * area * distance_squared.sqrt());
+    if area <= 0.0 || cosine < 0.000_001 {
+        return 0.0;
+    }
+
+    distance_squared / (cosine * area)
+}
+
+fn triangle_light_sample(
+    triangle: &InputTypeTriangle,
+    vertices: &[InputTypeVertex],
+    origin: glam::Vec3,
+    random: &mut Random,
+) -> LightSample {
+    let r1 = random.random();
+    let r2 = random.random();
+
+    let v0 = vertices[triangle.indices.x as usize].position;
+    let v1 = vertices[triangle.indices.y as usize].position;
+    let v2 = vertices[triangle.indices.z as usize].position;
+
+    // uniform over the area of the triangle
+    let s = r1.sqrt();
+    let point = (1.0 - s) * v0 + s * (1.0 - r2) * v1 + s * r2 * v2;
+
+    let pdf = triangle_light_pdf(triangle, vertices, origin, point);
+    if pdf <= 0.0 {
+        return LightSample::none();
+    }
+
+    LightSample {
+        direction: (point - origin).normalize(),
+        pdf,
+        primitive: InputTypePrimitive::default(),
+    }
+}
+
+fn quad_light_pdf(quad: &InputTypeQuad, origin: glam::Vec3, point: glam::Vec3) -> f32 {
+    let normal = quad.u.cross(quad.v);
+    let area = normal.length();
+    let to_point = point - origin;
+    let distance_squared = to_point.length_squared();
+    let cosine = normal.dot(to_point).abs() / (area * distance_squared.sqrt());
+    if area <= 0.0 || cosine < 0.000_001 {
+        return 0.0;
+    }
+
+    distance_squared / (cosine * area)
+}
+
+fn quad_light_sample(quad: &InputTypeQuad, origin: glam::Vec3, random: &mut Random) -> LightSample {
+    let r1 = random.random();
+    let r2 = random.random();
+
+    // uniform over the area of the quad
+    let point = quad.corner + r1 * quad.u + r2 * quad.v;
+
+    let pdf = quad_light_pdf(quad, origin, point);
+    if pdf <= 0.0 {
+        return LightSample::none();
+    }
+
+    LightSample {
+        direction: (point - origin).normalize(),
+        pdf,
+        primitive: InputTypePrimitive::default(),
+    }
+}
+
+fn disk_light_pdf(disk: &InputTypeDisk, origin: glam::Vec3, point: glam::Vec3) -> f32 {
+    let area = PI * disk.radius * disk.radius;
+    let to_point = point - origin;
+    let distance_squared = to_point.length_squared();
+    let cosine = disk.normal.normalize().dot(to_point).abs() / distance_squared.sqrt();
+    if area <= 0.0 || cosine < 0.000_001 {
+        return 0.0;
+    }
+
+    distance_squared / (cosine * area)
+}
+
+fn disk_light_sample(disk: &InputTypeDisk, origin: glam::Vec3, random: &mut Random) -> LightSample {
+    let r1 = random.random();
+    let r2 = random.random();
+
+    // uniform over the area of the disk
+    let r = disk.radius * r1.sqrt();
+    let phi = 2.0 * PI * r2;
+    let point = disk.center + onb_local(disk.normal.normalize(), r * phi.cos(), r * phi.sin(), 0.0);
+
+    let pdf = disk_light_pdf(disk, origin, point);
+    if pdf <= 0.0 {
+        return LightSample::none();
+    }
+
+    LightSample {
+        direction: (point - origin).normalize(),
+        pdf,
+        primitive: InputTypePrimitive::default(),
+    }
+}
+
+/// Solid angle probability density of `environment_light_sample` choosing `direction`.
+#[allow(clippy::cast_precision_loss)]
+fn environment_light_pdf(in_value: &InputType, direction: glam::Vec3) -> f32 {
+    let size = in_value.environment.size;
+    if size.x == 0 || size.y == 0 {
+        return 0.0;
+    }
+
+    let index = environment_texel(in_value, direction);
+    let mut probability = in_value.environment.texels[index].cdf;
+    if index > 0 {
+        probability -= in_value.environment.texels[index - 1].cdf;
+    }
+
+    // texels cover less solid angle towards the poles, which the rotation leaves in place
+    let unit_direction = direction.normalize();
+    let sin_theta = (1.0 - unit_direction.y * unit_direction.y).max(0.0).sqrt();
+    if sin_theta <= 0.0 {
+        return 0.0;
+    }
+
+    probability * (size.x * size.y) as f32 / (2.0 * PI * PI * sin_theta)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn environment_light_sample(in_value: &InputType, random: &mut Random) -> LightSample {
+    let r1 = random.random();
+    let r2 = random.random();
+    let r3 = random.random();
+
+    let size = in_value.environment.size;
+    let texel_length = size.x * size.y;
+    if texel_length == 0 {
+        return LightSample::none();
+    }
+
+    // first texel whose cumulative probability is above r1
+    let mut low = 0;
+    let mut high = texel_length - 1;
+    while low < high {
+        let middle = u32::midpoint(low, high);
+        if in_value.environment.texels[middle as usize].cdf > r1 {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+
+    // uniform within the texel
+    let phi = (((low % size.x) as f32 + r2) / size.x as f32 - 0.5) * 2.0 * PI;
+    let theta = ((low / size.x) as f32 + r3) / size.y as f32 * PI;
+    let direction = rotate_y(
+        glam::Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        ),
+        in_value.background.rotation,
+    );
+
+    let pdf = environment_light_pdf(in_value, direction);
+    if pdf <= 0.0 {
+        return LightSample::none();
+    }
+
+    LightSample {
+        direction,
+        pdf,
+        primitive: InputTypePrimitive::default(),
+    }
+}
+
+fn sun_light_pdf(in_value: &InputType, direction: glam::Vec3) -> f32 {
+    let sky = &in_value.background.sky;
+    if sky.sun_radiance.max_element() <= 0.0
+        || direction.normalize().dot(sky.sun_direction) < sky.sun_cos_radius
+    {
+        return 0.0;
+    }
+
+    1.0 / sky.sun_solid_angle
+}
+
+fn sun_light_sample(in_value: &InputType, random: &mut Random) -> LightSample {
+    let r1 = random.random();
+    let r2 = random.random();
+
+    let sky = &in_value.background.sky;
+    if sky.sun_radiance.max_element() <= 0.0 {
+        return LightSample::none();
+    }
+
+    // uniform in the cone of directions towards the sun disk
+    let z = 1.0 + r2 * (sky.sun_cos_radius - 1.0);
+    let phi = 2.0 * PI * r1;
+    let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+    let direction = onb_local(
+        sky.sun_direction,
+        phi.cos() * sin_theta,
+        phi.sin() * sin_theta,
+        z,
+    );
+
+    LightSample {
+        direction: direction.normalize(),
+        pdf: 1.0 / sky.sun_solid_angle,
+        primitive: InputTypePrimitive::default(),
+    }
+}
+
+/// Solid angle probability density of `background_light_sample` choosing `direction`.
+fn background_light_pdf(in_value: &InputType, direction: glam::Vec3) -> f32 {
+    match in_value.background.type_ {
+        3 => environment_light_pdf(in_value, direction),
+        4 => sun_light_pdf(in_value, direction),
+        _ => 0.0,
+    }
+}
+
+fn background_light_sample(in_value: &InputType, random: &mut Random) -> LightSample {
+    match in_value.background.type_ {
+        3 => environment_light_sample(in_value, random),
+        4 => sun_light_sample(in_value, random),
+        _ => LightSample::none(),
+    }
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn light_sample(
+    in_value: &InputType,
+    origin: glam::Vec3,
+    time: f32,
+    random: &mut Random,
+) -> LightSample {
+    let light_length = in_value.lights.len();
+    let light =
+        in_value.lights[((random.random() * light_length as f32) as usize).min(light_length - 1)];
+
+    let mut sampled = match light.type_ {
+        InputTypePrimitive::SPHERE => sphere_light_sample(
+            &in_value.spheres[light.index as usize],
+            origin,
+            time,
+            random,
+        ),
+        InputTypePrimitive::TRIANGLE => triangle_light_sample(
+            &in_value.triangles[light.index as usize],
+            &in_value.vertices,
+            origin,
+            random,
+        ),
+        InputTypePrimitive::ENVIRONMENT => background_light_sample(in_value, random),
+        InputTypePrimitive::QUAD => {
+            quad_light_sample(&in_value.quads[light.index as usize], origin, random)
+        }
+        InputTypePrimitive::DISK => {
+            disk_light_sample(&in_value.disks[light.index as usize], origin, random)
+        }
+        _ => LightSample::none(),
+    };
+
+    sampled.pdf /= light_length as f32;
+    sampled.primitive = light;
+    sampled
+}
+
+/// Probability density of `light_sample` choosing the direction from `origin` to the light hit by `hit_record`.
+#[allow(clippy::cast_precision_loss)]
+fn light_pdf(in_value: &InputType, origin: glam::Vec3, time: f32, hit_record: &HitRecord) -> f32 {
+    let light_length = in_value.lights.len();
+    if light_length == 0 {
+        return 0.0;
+    }
+
+    let pdf = match hit_record.primitive.type_ {
+        InputTypePrimitive::SPHERE => sphere_light_pdf(
+            &in_value.spheres[hit_record.primitive.index as usize],
+            origin,
+            time,
+        ),
+        InputTypePrimitive::TRIANGLE => triangle_light_pdf(
+            &in_value.triangles[hit_record.primitive.index as usize],
+            &in_value.vertices,
+            origin,
+            hit_record.point,
+        ),
+        InputTypePrimitive::QUAD => quad_light_pdf(
+            &in_value.quads[hit_record.primitive.index as usize],
+            origin,
+            hit_record.point,
+        ),
+        InputTypePrimitive::DISK => disk_light_pdf(
+            &in_value.disks[hit_record.primitive.index as usize],
+            origin,
+            hit_record.point,
+        ),
+        _ => 0.0,
+    };
+
+    pdf / light_length as f32
+}
+
+/// Probability density of `light_sample` choosing `direction` towards the background.
+#[allow(clippy::cast_precision_loss)]
+fn light_pdf_background(in_value: &InputType, direction: glam::Vec3) -> f32 {
+    let light_length = in_value.lights.len();
+    if light_length == 0 {
+        return 0.0;
+    }
+
+    background_light_pdf(in_value, direction) / light_length as f32
+}
+
+/// Radiance reaching a lambertian hit directly from a sampled light, weighted against bounces.
+fn light_direct(
+    in_value: &InputType,
+    hit_record: &HitRecord,
+    time: f32,
+    random: &mut Random,
+) -> glam::Vec3 {
+    if in_value.lights.is_empty() {
+        return glam::Vec3::ZERO;
+    }
+
+    let sampled = light_sample(in_value, hit_record.point, time, random);
+    let cosine = hit_record.normal.dot(sampled.direction);
+    if sampled.pdf <= 0.0 || cosine <= 0.0 {
+        return glam::Vec3::ZERO;
+    }
+
+    let shadow_ray = Ray {
+        origin: hit_record.point,
+        direction: sampled.direction,
+        time,
+    };
+    let shadow = world_hit(in_value, &shadow_ray, 0.001, 10000.0, random);
+
+    let emitted = if sampled.primitive.type_ == InputTypePrimitive::ENVIRONMENT {
+        // the background is only visible when nothing is in the way
+        if shadow.is_some() {
+            return glam::Vec3::ZERO;
+        }
+        background_color(in_value, &shadow_ray)
+    } else {
+        // the light is only visible when it is the closest hit
+        let Some(shadow) = shadow.filter(|shadow| shadow.primitive == sampled.primitive) else {
+            return glam::Vec3::ZERO;
+        };
+        material_emitted(shadow.material)
+    };
+
+    let weight = power_heuristic(sampled.pdf, cosine / PI);
+    material_texture_albedo(in_value, hit_record.material, hit_record) / PI
+        * emitted
+        * cosine
+        * weight
+        / sampled.pdf
+}
+
+/*
+ * ============================================================================
+ * AOV
+ * ============================================================================
+ */
+fn aov_new(in_value: &InputType, ray: &Ray, hit_record: &HitRecord) -> OutputTypeAov {
+    OutputTypeAov {
+        normal: hit_record.normal,
+        depth: hit_record.t * ray.direction.length(),
+        albedo: material_albedo(in_value, hit_record.material, hit_record),
+        material: hit_record.material.type_,
+        primitive: hit_record.primitive,
+    }
+}
+
+/*
+ * ============================================================================
+ * Adaptive Sampling
+ * ============================================================================
+ */
+fn luminance(color: glam::Vec3) -> f32 {
+    color.dot(glam::Vec3::new(0.2126, 0.7152, 0.0722))
+}
+
+/// Standard error of the mean luminance, relative to the mean luminance.
+#[allow(clippy::cast_precision_loss)]
+fn pixel_noise(pixel: &AccumulationPixel) -> f32 {
+    let n = pixel.samples as f32;
+    let mean = pixel.luminance / n;
+    let variance = (pixel.luminance_squared - n * mean * mean).max(0.0) / (n - 1.0).max(1.0);
+    (variance / n).sqrt() / mean.max(0.001)
+}
+
+/// Samples this dispatch adds to the pixel, converged pixels take none.
+fn pixel_samples(in_value: &InputType, dispatch: &DispatchType, pixel: &AccumulationPixel) -> u32 {
+    if let Some(noise_threshold) = in_value.adaptive_noise_threshold() {
+        if pixel.samples >= in_value.samples_per_pixel
+            && (pixel.samples >= in_value.max_samples.max(in_value.samples_per_pixel)
+                || pixel_noise(pixel) <= noise_threshold)
+        {
+            return 0;
+        }
+    }
+    dispatch.samples
+}
+
+/*
+ * ============================================================================
+ * Main
+ * ============================================================================
+ */
+#[allow(clippy::cast_precision_loss)]
+fn main(
+    in_value: &InputType,
+    dispatch: &DispatchType,
+    global_id: glam::UVec2,
+    pixel: &mut AccumulationPixel,
+) {
+    // Invocation
+    let i = dispatch.view_box_position.x + global_id.x;
+    let j = dispatch.view_box_position.y + global_id.y;
+
+    // Image
+    let image_width = in_value.screen_size.x;
+    let image_height = in_value.screen_size.y;
+    let aspect_ratio = image_width as f32 / image_height as f32;
+
+    // Camera
+    let camera = camera_new(
+        in_value.camera.look_from,
+        in_value.camera.look_at,
+        in_value.camera.up,
+        in_value.camera.vertical_fov,
+        aspect_ratio,
+        in_value.camera.aperture,
+        in_value.camera.focus_distance,
+        in_value.camera.shutter_open,
+        in_value.camera.shutter_close,
+    );
+
+    // Calculate
+    let samples = pixel_samples(in_value, dispatch, pixel);
+
+    for s in pixel.samples..pixel.samples + samples {
+        let mut random = Random::new(glam::UVec2::new(i, j), s, in_value.seed);
+        let u = (i as f32 + random.random()) / (image_width - 1) as f32;
+        let v = (j as f32 + random.random()) / (image_height - 1) as f32;
+        let ray = camera_get_ray(&camera, u, v, &mut random);
+        let mut primary_aov = OutputTypeAov::default();
+        let color = ray_color(ray, in_value, &mut random, &mut primary_aov);
+        pixel.radiance += color;
+        pixel.luminance += luminance(color);
+        pixel.luminance_squared += luminance(color) * luminance(color);
+
+        if s == 0 {
+            pixel.aov = primary_aov;
+        }
+    }
+    pixel.samples += samples;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{scene::Camera, shaders::ray_tracer};
+
+    /// A diffuse sphere in front of the sky gradient, seen through the middle of the screen.
+    fn sphere() -> ray_tracer::InputType {
+        ray_tracer::InputType {
+            samples_per_pixel: 4,
+            seed: 0,
+            screen_size: glam::UVec2 { x: 32, y: 32 },
+            view_box_position: glam::UVec2 { x: 8, y: 8 },
+            view_box_size: glam::UVec2 { x: 16, y: 8 },
+            camera: (&Camera::default()).into(),
+            spheres: vec![ray_tracer::InputTypeSphere {
+                center: glam::Vec3::ZERO,
+                radius: 1.0,
+                end_center: glam::Vec3::ZERO,
+                material: ray_tracer::InputTypeMaterial::new_lambertian(glam::Vec3::splat(0.5)),
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// The sphere in the dark, with enough samples of its centre for the integrators to converge.
+    fn dark_sphere() -> ray_tracer::InputType {
+        ray_tracer::InputType {
+            samples_per_pixel: 1024,
+            view_box_position: glam::UVec2 { x: 12, y: 12 },
+            view_box_size: glam::UVec2 { x: 8, y: 8 },
+            background: ray_tracer::InputTypeBackground::new_black(),
+            ..sphere()
+        }
+    }
+
+    /// The mean red of the 8 by 8 pixels of `dark_sphere`.
+    fn mean(input: &ray_tracer::InputType) -> f32 {
+        let output = super::Shader::new().execute(input);
+        output.pixels.iter().sum::<glam::Vec4>().x / 64.0
+    }
+
+    /// Asserts sampling the lights of `input` converges to the same image as waiting for
+    /// bounces to find them, and returns the mean of the image.
+    fn assert_next_event_estimation(mut input: ray_tracer::InputType, minimum: f32) -> f32 {
+        input.lights = ray_tracer::lights(&input);
+        input.next_event_estimation = false;
+        let naive = mean(&input);
+        input.next_event_estimation = true;
+        let next_event_estimation = mean(&input);
+        assert!(naive > minimum);
+        assert!((next_event_estimation / naive - 1.0).abs() < 0.1);
+        next_event_estimation
+    }
+
+    #[test]
+    fn render() {
+        let output = super::Shader::new().execute(&sphere());
+
+        assert_eq!(output.pixels.len(), 16 * 8);
+        // the sky gradient is never redder than it is blue
+        assert!(output.pixels.iter().all(|pixel| pixel.z >= pixel.x));
+    }
+
+    #[test]
+    fn seed() {
+        // the same seed renders the same image
+        let shader = super::Shader::new();
+        let output = shader.execute(&sphere());
+        assert_eq!(shader.execute(&sphere()).pixels, output.pixels);
+
+        let mut input = sphere();
+        input.seed = 1;
+        assert_ne!(shader.execute(&input).pixels, output.pixels);
+    }
+
+    #[test]
+    fn sphere_light() {
+        let mut input = dark_sphere();
+        input.spheres.push(ray_tracer::InputTypeSphere {
+            center: glam::Vec3::new(0.0, 4.0, 0.0),
+            radius: 2.0,
+            end_center: glam::Vec3::new(0.0, 4.0, 0.0),
+            material: ray_tracer::InputTypeMaterial::new_diffuse_light(glam::Vec3::splat(1.0)),
+        });
+        assert_next_event_estimation(input, 0.0);
+    }
+
+    #[test]
+    fn russian_roulette() {
+        let mut input = dark_sphere();
+        input.spheres.push(ray_tracer::InputTypeSphere {
+            center: glam::Vec3::new(0.0, 4.0, 0.0),
+            radius: 2.0,
+            end_center: glam::Vec3::new(0.0, 4.0, 0.0),
+            material: ray_tracer::InputTypeMaterial::new_diffuse_light(glam::Vec3::splat(1.0)),
+        });
+        let next_event_estimation = assert_next_event_estimation(input.clone(), 0.0);
+
+        // russian roulette only trades noise for shorter paths
+        input.lights = ray_tracer::lights(&input);
+        input.next_event_estimation = true;
+        input.russian_roulette_depth = Some(1);
+        assert!((mean(&input) / next_event_estimation - 1.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn adaptive_sampling() {
+        // the smooth sky converges long before the diffuse sphere
+        let output = super::Shader::new().execute(&ray_tracer::InputType {
+            noise_threshold: Some(0.01),
+            max_samples: 64,
+            ..sphere()
+        });
+        assert_eq!(output.samples.iter().min(), Some(&16));
+        assert_eq!(output.samples.iter().max(), Some(&64));
+    }
+
+    #[test]
+    fn aovs() {
+        let input = sphere();
+        let output = super::Shader::new().execute(&input);
+
+        // the centre of the image sees the sphere, the corner sees the sky
+        let aov = &output.aovs[7 * 16 + 8];
+        assert_eq!(aov.material, 1);
+        assert!(aov.albedo.abs_diff_eq(glam::Vec3::splat(0.5), 1e-6));
+        assert!(aov.depth > 12.0 && aov.depth < 14.0);
+        assert!(aov.normal.dot(input.camera.look_from.normalize()) > 0.9);
+        assert_eq!(output.aovs[0], ray_tracer::OutputTypeAov::default());
+    }
+
+    #[test]
+    fn environment() {
+        // a bright patch of the environment lighting the side of the sphere facing the camera
+        let mut radiance = vec![glam::Vec3::splat(0.1); 16 * 8];
+        radiance[2 * 16 + 13] = glam::Vec3::splat(20.0);
+        let environment = ray_tracer::InputTypeEnvironment::new(glam::UVec2::new(16, 8), &radiance);
+        assert!((environment.texels[16 * 8 - 1].cdf - 1.0).abs() < 1e-5);
+        assert!(environment.texels[2 * 16 + 13].cdf - environment.texels[2 * 16 + 12].cdf > 0.5);
+
+        // importance sampling it converges to the same image as waiting for bounces to escape
+        let input = ray_tracer::InputType {
+            background: ray_tracer::InputTypeBackground::new_environment(1.0, 0.3),
+            environment,
+            ..dark_sphere()
+        };
+        assert_eq!(ray_tracer::lights(&input).len(), 1);
+        assert_next_event_estimation(input, 0.2);
+    }
+
+    #[test]
+    fn sky() {
+        // the sky is blue overhead, and the ground reflects the light of the sky and the sun
+        let mut sky = ray_tracer::InputTypeSky::new(
+            glam::Vec3::new(1.0, 1.0, 0.5),
+            3.0,
+            glam::Vec3::splat(0.3),
+        );
+        let mut input = ray_tracer::InputType {
+            background: ray_tracer::InputTypeBackground::new_sky(sky.clone()),
+            ..dark_sphere()
+        };
+        let zenith = super::sky_color(&input, glam::Vec3::Y);
+        assert!(zenith.z > zenith.x && zenith.x > 0.0);
+        assert!(super::sky_color(&input, -glam::Vec3::Y).x > 0.0);
+
+        // sampling a sun large enough for bounces to find converges to the same image
+        sky.sun_cos_radius = 0.2_f32.cos();
+        sky.sun_solid_angle = 2.0 * super::PI * (1.0 - sky.sun_cos_radius);
+        sky.sun_radiance /= 1000.0;
+        input.background = ray_tracer::InputTypeBackground::new_sky(sky);
+        assert_next_event_estimation(input, 0.2);
+    }
+
+    #[test]
+    fn quad_and_disk_lights() {
+        // a quad and a disk light the sphere standing on a plane
+        let input = ray_tracer::InputType {
+            planes: vec![ray_tracer::InputTypePlane {
+                point: -glam::Vec3::Y,
+                normal: glam::Vec3::Y,
+                material: ray_tracer::InputTypeMaterial::new_lambertian(glam::Vec3::splat(0.5)),
+            }],
+            quads: vec![ray_tracer::InputTypeQuad {
+                corner: glam::Vec3::new(-1.0, 3.0, -1.0),
+                u: glam::Vec3::X * 2.0,
+                v: glam::Vec3::Z * 2.0,
+                material: ray_tracer::InputTypeMaterial::new_diffuse_light(glam::Vec3::splat(4.0)),
+            }],
+            disks: vec![ray_tracer::InputTypeDisk {
+                center: glam::Vec3::new(3.0, 0.0, 2.0),
+                radius: 1.0,
+                normal: glam::Vec3::new(-1.0, 0.0, -1.0),
+                material: ray_tracer::InputTypeMaterial::new_diffuse_light(glam::Vec3::splat(4.0)),
+            }],
+            ..dark_sphere()
+        };
+        assert_eq!(ray_tracer::lights(&input).len(), 2);
+        assert_next_event_estimation(input, 0.1);
+    }
+
+    #[test]
+    fn motion_blur() {
+        let shader = super::Shader::new();
+        let input = sphere();
+        let output = shader.execute(&input);
+
+        // a moving sphere is where the shutter catches it, and blurred while the shutter is open
+        let mut moving = input.clone();
+        moving.spheres[0].end_center = glam::Vec3::new(0.0, 0.5, 0.0);
+        moving.camera.shutter_open = 1.0;
+        moving.camera.shutter_close = 1.0;
+        let mut still = moving.clone();
+        still.spheres[0].center = still.spheres[0].end_center;
+        assert_eq!(
+            shader.execute(&moving).pixels,
+            shader.execute(&still).pixels
+        );
+        moving.camera.shutter_open = 0.0;
+        let blurred = shader.execute(&moving).pixels;
+        assert_ne!(blurred, output.pixels);
+        assert_ne!(blurred, shader.execute(&still).pixels);
+    }
+
+    #[test]
+    fn volumes() {
+        let shader = super::Shader::new();
+        let input = sphere();
+        let output = shader.execute(&input);
+
+        // fog without density is invisible, thick fog hides the sphere it surrounds
+        let mut foggy = input.clone();
+        foggy.volumes.push(ray_tracer::InputTypeVolume::new_box(
+            glam::Vec3::splat(-1.5),
+            glam::Vec3::splat(1.5),
+            0.0,
+            glam::Vec3::splat(0.8),
+        ));
+        assert_eq!(shader.execute(&foggy).pixels, output.pixels);
+        foggy.volumes[0] = ray_tracer::InputTypeVolume::new_sphere(
+            glam::Vec3::ZERO,
+            1.5,
+            100.0,
+            glam::Vec3::splat(0.8),
+        );
+        let fog = shader.execute(&foggy);
+        let aov = &fog.aovs[7 * 16 + 8];
+        assert_eq!(aov.material, 5);
+        assert!(aov.albedo.abs_diff_eq(glam::Vec3::splat(0.8), 1e-6));
+        assert!(aov.depth < output.aovs[7 * 16 + 8].depth);
+    }
+
+    #[test]
+    fn textures() {
+        let shader = super::Shader::new();
+        let input = sphere();
+        let output = shader.execute(&input);
+
+        // a solid texture is the same as a constant albedo, a checker varies across the sphere
+        let mut textured = input.clone();
+        textured.textures = vec![
+            ray_tracer::InputTypeTexture::new_solid(glam::Vec3::splat(0.5)),
+            ray_tracer::InputTypeTexture::new_uv_checker(glam::Vec3::ONE, glam::Vec3::ZERO, 8.0),
+        ];
+        textured.spheres[0].material =
+            ray_tracer::InputTypeMaterial::new_lambertian(glam::Vec3::ONE).with_texture(0);
+        assert_eq!(shader.execute(&textured).pixels, output.pixels);
+        textured.spheres[0].material =
+            ray_tracer::InputTypeMaterial::new_lambertian(glam::Vec3::ONE).with_texture(1);
+        let checker = shader.execute(&textured);
+        let albedos = (0..16).map(|x| checker.aovs[7 * 16 + x].albedo.x);
+        assert!(albedos.clone().any(|albedo| albedo > 0.9));
+        assert!(albedos.clone().any(|albedo| albedo < 0.1));
+
+        // images wrap around the uv coordinates, with the top row first
+        textured.texels = vec![0x0000_00ff, 0x0000_ff00, 0x00ff_0000, 0x00ff_ffff];
+        textured.textures = vec![ray_tracer::InputTypeTexture::new_image(
+            glam::UVec2::new(2, 1),
+            2,
+        )];
+        let texel =
+            |u, v| super::texture_value(&textured, 0, glam::Vec2::new(u, v), glam::Vec3::ZERO);
+        assert_eq!(texel(0.25, 0.5), glam::Vec3::Z);
+        assert_eq!(texel(0.75, 0.0), glam::Vec3::ONE);
+        assert_eq!(texel(-0.75, 1.5), glam::Vec3::Z);
+    }
+
+    #[test]
+    fn perlin_noise() {
+        // noise vanishes at the corners of its lattice and stays in range in between
+        assert!(super::perlin_noise(glam::Vec3::new(3.0, -2.0, 7.0)).abs() < 1e-6);
+        let step = glam::Vec3::new(0.137, -0.071, 0.029);
+        for p in std::iter::successors(Some(glam::Vec3::ZERO), |p| Some(*p + step)).take(1000) {
+            assert!(super::perlin_noise(p).abs() <= 1.0);
+            assert!(super::perlin_turbulence(p, 7) <= 2.0);
+        }
+    }
+}
@@ -1,44 +1,506 @@
+pub mod bvh;
+pub mod cpu;
+mod types;
+
+use std::{collections::HashSet, time::Instant};
+
 use chrono::Utc;
 use encase::ShaderType;
-use rand::Rng;
 use wgpu::util::DeviceExt;
 
-use crate::gpu::GPU;
+use super::{storage_buffer_layout_entry, uniform_buffer_layout_entry, uniform_contents};
+use crate::{gpu::GPU, Error};
+
+use types::{
+    AccumulationPixel, AccumulationType, BvhNodeType, BvhPrimitiveType, DiskType, DispatchType,
+    EnvironmentType, InputTypeBuffer, Instance, InstanceType, LightType, PlaneType, QuadType,
+    TexelType, TextureType, TriangleType, VertexType, VolumeType,
+};
+pub use types::{
+    InputTypeBackground, InputTypeCamera, InputTypeDisk, InputTypeEnvironmentTexel,
+    InputTypeMaterial, InputTypePlane, InputTypePrimitive, InputTypeQuad, InputTypeSky,
+    InputTypeSphere, InputTypeTexture, InputTypeTriangle, InputTypeVertex, InputTypeVolume,
+    OutputTypeAov,
+};
 
-#[derive(Clone, Debug, Default, encase::ShaderType)]
+#[derive(Clone, Debug)]
 pub struct InputType {
+    /// Samples taken of every pixel, unless `time_limit` is exceeded first.
     pub samples_per_pixel: u32,
 
+    /// Pixels whose relative standard error is above it keep taking samples past
+    /// `samples_per_pixel`, up to `max_samples`. `None`, or a threshold which is not positive,
+    /// samples every pixel equally.
+    pub noise_threshold: Option<f32>,
+
+    /// Samples after which a pixel stops even when it is still above `noise_threshold`.
+    pub max_samples: u32,
+
+    /// Samples added to every pixel by one dispatch, which keeps each dispatch short.
+    pub samples_per_dispatch: u32,
+
+    /// Stops adding samples once exceeded, the image then averages the samples taken so far.
+    pub time_limit: Option<std::time::Duration>,
+
+    /// Bounces after which a path is terminated without picking up the background.
+    pub max_depth: u32,
+
+    /// Bounces after which paths are randomly terminated by Russian roulette, `None` never does.
+    pub russian_roulette_depth: Option<u32>,
+
+    pub seed: u32,
+
     pub screen_size: glam::UVec2,
 
     pub view_box_position: glam::UVec2,
 
     pub view_box_size: glam::UVec2,
 
-    #[size(runtime)]
+    pub camera: InputTypeCamera,
+
+    pub background: InputTypeBackground,
+
+    /// Radiance of an environment background, see [`InputTypeBackground::new_environment`].
+    pub environment: InputTypeEnvironment,
+
     pub spheres: Vec<InputTypeSphere>,
+
+    pub vertices: Vec<InputTypeVertex>,
+
+    pub triangles: Vec<InputTypeTriangle>,
+
+    /// Tested for every ray, as they are unbounded.
+    pub planes: Vec<InputTypePlane>,
+
+    pub quads: Vec<InputTypeQuad>,
+
+    pub disks: Vec<InputTypeDisk>,
+
+    pub volumes: Vec<InputTypeVolume>,
+
+    /// Looked up by materials in place of their constant albedo, see [`InputTypeMaterial::with_texture`].
+    pub textures: Vec<InputTypeTexture>,
+
+    /// sRGB colours of every image texture packed as rgba8, red in the lowest byte, see
+    /// [`InputTypeTexture::new_image`].
+    pub texels: Vec<u32>,
+
+    /// Primitives which are only drawn where `instances` place them.
+    pub objects: Vec<InputTypeObject>,
+
+    pub instances: Vec<InputTypeInstance>,
+
+    /// `None` tests every primitive for every ray.
+    pub bvh: Option<bvh::Bvh>,
+
+    /// Samples `lights` at every diffuse bounce, combined with bounces through multiple importance sampling.
+    pub next_event_estimation: bool,
+
+    /// Every emissive primitive and the environment, see [`lights`].
+    pub lights: Vec<InputTypePrimitive>,
+}
+
+impl Default for InputType {
+    fn default() -> Self {
+        Self {
+            samples_per_pixel: 0,
+            noise_threshold: None,
+            max_samples: 0,
+            samples_per_dispatch: 16,
+            time_limit: None,
+            max_depth: 50,
+            russian_roulette_depth: None,
+            seed: 0,
+            screen_size: glam::UVec2::ZERO,
+            view_box_position: glam::UVec2::ZERO,
+            view_box_size: glam::UVec2::ZERO,
+            camera: InputTypeCamera::default(),
+            background: InputTypeBackground::default(),
+            environment: InputTypeEnvironment::default(),
+            spheres: Vec::new(),
+            vertices: Vec::new(),
+            triangles: Vec::new(),
+            planes: Vec::new(),
+            quads: Vec::new(),
+            disks: Vec::new(),
+            volumes: Vec::new(),
+            textures: Vec::new(),
+            texels: Vec::new(),
+            objects: Vec::new(),
+            instances: Vec::new(),
+            bvh: None,
+            next_event_estimation: false,
+            lights: Vec::new(),
+        }
+    }
+}
+
+impl InputType {
+    /// References every sphere, triangle, quad, disk and volume which is not part of an object.
+    #[allow(clippy::cast_possible_truncation)]
+    #[must_use]
+    pub fn primitives(&self) -> Vec<InputTypePrimitive> {
+        let objects = self
+            .objects
+            .iter()
+            .flat_map(|object| &object.primitives)
+            .collect::<HashSet<_>>();
+
+        [
+            (InputTypePrimitive::SPHERE, self.spheres.len()),
+            (InputTypePrimitive::TRIANGLE, self.triangles.len()),
+            (InputTypePrimitive::QUAD, self.quads.len()),
+            (InputTypePrimitive::DISK, self.disks.len()),
+            (InputTypePrimitive::VOLUME, self.volumes.len()),
+        ]
+        .into_iter()
+        .flat_map(|(type_, length)| {
+            (0..length as u32).map(move |index| InputTypePrimitive { type_, index })
+        })
+        .filter(|primitive| !objects.contains(primitive))
+        .collect()
+    }
+
+    /// Threshold of adaptive sampling, `None` when every pixel is sampled equally.
+    fn adaptive_noise_threshold(&self) -> Option<f32> {
+        self.noise_threshold
+            .filter(|&noise_threshold| noise_threshold > 0.0)
+    }
+
+    /// Material of a sphere, triangle, plane, quad, disk or volume.
+    fn material(&self, primitive: InputTypePrimitive) -> Option<&InputTypeMaterial> {
+        let index = primitive.index as usize;
+        match primitive.type_ {
+            InputTypePrimitive::SPHERE => Some(&self.spheres[index].material),
+            InputTypePrimitive::TRIANGLE => Some(&self.triangles[index].material),
+            InputTypePrimitive::PLANE => Some(&self.planes[index].material),
+            InputTypePrimitive::QUAD => Some(&self.quads[index].material),
+            InputTypePrimitive::DISK => Some(&self.disks[index].material),
+            InputTypePrimitive::VOLUME => Some(&self.volumes[index].material),
+            _ => None,
+        }
+    }
+}
+
+impl InputTypeBuffer {
+    #[allow(clippy::cast_possible_truncation)]
+    fn new(value: &InputType, bvh: &bvh::Bvh) -> Self {
+        Self {
+            max_depth: value.max_depth,
+            russian_roulette: u32::from(value.russian_roulette_depth.is_some()),
+            russian_roulette_depth: value.russian_roulette_depth.unwrap_or(0),
+            seed: value.seed,
+            brute_force: u32::from(bvh.nodes.is_empty()),
+            primitive_length: bvh.world_primitives().len() as u32,
+            next_event_estimation: u32::from(value.next_event_estimation),
+            noise_threshold: value.adaptive_noise_threshold().unwrap_or(0.0),
+            min_samples: value.samples_per_pixel,
+            max_samples: value.max_samples.max(value.samples_per_pixel),
+            screen_size: value.screen_size,
+            camera: value.camera.clone(),
+            background: value.background.clone(),
+            spheres: value.spheres.clone(),
+        }
+    }
+}
+
+impl InputTypeBackground {
+    #[must_use]
+    pub fn new_gradient() -> Self {
+        Self {
+            color: glam::Vec3::ZERO,
+            type_: 0,
+            rotation: 0.0,
+            sky: InputTypeSky::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn new_solid(color: glam::Vec3) -> Self {
+        Self {
+            color,
+            type_: 1,
+            rotation: 0.0,
+            sky: InputTypeSky::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn new_black() -> Self {
+        Self {
+            color: glam::Vec3::ZERO,
+            type_: 2,
+            rotation: 0.0,
+            sky: InputTypeSky::default(),
+        }
+    }
+
+    /// Looks up the radiance of escaped rays in `InputType::environment`, scaled by `intensity`.
+    #[must_use]
+    pub fn new_environment(intensity: f32, rotation: f32) -> Self {
+        Self {
+            color: glam::Vec3::splat(intensity),
+            type_: 3,
+            rotation,
+            sky: InputTypeSky::default(),
+        }
+    }
+
+    /// Preetham daylight sky with a sun disk, see [`InputTypeSky::new`].
+    #[must_use]
+    pub fn new_sky(sky: InputTypeSky) -> Self {
+        Self {
+            color: glam::Vec3::ONE,
+            type_: 4,
+            rotation: 0.0,
+            sky,
+        }
+    }
+}
+
+/// Converts the luminance of the Preetham model, in kcd/m², to the radiance of the scene.
+const SKY_SCALE: f32 = 0.05;
+
+/// Angular radius of the sun in radians.
+const SUN_RADIUS: f64 = 0.004_65;
+
+/// Luminance of the sun above the atmosphere in kcd/m².
+const SUN_LUMINANCE: f32 = 2.0e6;
+
+impl InputTypeSky {
+    /// `turbidity` ranges from 2 for a clear sky to 10 for a hazy one.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn new(sun_direction: glam::Vec3, turbidity: f32, ground_albedo: glam::Vec3) -> Self {
+        let t = turbidity;
+        let sun_direction = sun_direction.normalize();
+        let theta_s = sun_direction.y.clamp(0.0, 1.0).acos();
+
+        let mut sky = Self {
+            a: glam::Vec3::new(
+                0.1787 * t - 1.4630,
+                -0.0193 * t - 0.2592,
+                -0.0167 * t - 0.2608,
+            ),
+            b: glam::Vec3::new(
+                -0.3554 * t + 0.4275,
+                -0.0665 * t + 0.0008,
+                -0.0950 * t + 0.0092,
+            ),
+            c: glam::Vec3::new(
+                -0.0227 * t + 5.3251,
+                -0.0004 * t + 0.2125,
+                -0.0079 * t + 0.2102,
+            ),
+            d: glam::Vec3::new(
+                0.1206 * t - 2.5771,
+                -0.0641 * t - 0.8989,
+                -0.0441 * t - 1.6537,
+            ),
+            e: glam::Vec3::new(
+                -0.0670 * t + 0.3703,
+                -0.0033 * t + 0.0452,
+                -0.0109 * t + 0.0529,
+            ),
+            zenith: glam::Vec3::ZERO,
+            sun_direction,
+            sun_cos_radius: SUN_RADIUS.cos() as f32,
+            sun_radiance: glam::Vec3::ZERO,
+            // from the cosine the shader samples the disk with
+            sun_solid_angle: (2.0
+                * std::f64::consts::PI
+                * (1.0 - f64::from(SUN_RADIUS.cos() as f32))) as f32,
+            ground: glam::Vec3::ZERO,
+        };
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f32::consts::PI - 2.0 * theta_s);
+        let theta = glam::Vec3::new(theta_s.powi(3), theta_s.powi(2), theta_s);
+        let zenith = glam::Vec3::new(
+            ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0),
+            t * t * theta.dot(glam::Vec3::new(0.00166, -0.00375, 0.00209))
+                + t * (theta.dot(glam::Vec3::new(-0.02903, 0.06377, -0.03202)) + 0.00394)
+                + theta.dot(glam::Vec3::new(0.11693, -0.21196, 0.06052))
+                + 0.25886,
+            t * t * theta.dot(glam::Vec3::new(0.00275, -0.00610, 0.00317))
+                + t * (theta.dot(glam::Vec3::new(-0.04214, 0.08970, -0.04153)) + 0.00516)
+                + theta.dot(glam::Vec3::new(0.15346, -0.26756, 0.06670))
+                + 0.26688,
+        );
+        sky.zenith = zenith / sky.perez(1.0, theta_s.cos());
+
+        // sunlight is scattered by air and aerosols along the way through the atmosphere
+        if sun_direction.y > 0.0 {
+            let elevation = 90.0 - theta_s.to_degrees();
+            let air_mass = 1.0 / (sun_direction.y + 0.50572 * (elevation + 6.07995).powf(-1.6364));
+            let wavelengths = glam::Vec3::new(0.68, 0.55, 0.44);
+            let rayleigh = 0.0088 * wavelengths.powf(-4.05);
+            let aerosol = (0.04608 * t - 0.04586) * wavelengths.powf(-1.3);
+            let transmittance = (-(rayleigh + aerosol) * air_mass).exp();
+            sky.sun_radiance = SUN_LUMINANCE * SKY_SCALE * transmittance;
+        }
+
+        // light the ground with the sky above it, summed over a grid of directions
+        let steps = 64;
+        let mut irradiance = sky.sun_radiance * sky.sun_solid_angle * sun_direction.y.max(0.0);
+        for i in 0..steps {
+            for j in 0..steps * 4 {
+                let cos_theta = (i as f32 + 0.5) / steps as f32;
+                let phi = 2.0 * std::f32::consts::PI * (j as f32 + 0.5) / (steps * 4) as f32;
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let direction =
+                    glam::Vec3::new(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+                // uniform in cos_theta, so every direction covers the same solid angle
+                let solid_angle = 2.0 * std::f32::consts::PI / (steps * steps * 4) as f32;
+                irradiance += sky.sky_radiance(direction) * cos_theta * solid_angle;
+            }
+        }
+        sky.ground = ground_albedo / std::f32::consts::PI * irradiance;
+
+        sky
+    }
+
+    /// Perez function of each channel, for the cosine of the angle to the zenith and to the sun.
+    fn perez(&self, cos_theta: f32, cos_gamma: f32) -> glam::Vec3 {
+        let gamma = cos_gamma.clamp(-1.0, 1.0).acos();
+        (1.0 + self.a * (self.b / cos_theta.max(0.001)).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * cos_gamma * cos_gamma)
+    }
+
+    /// Radiance of the sky above the horizon, without the sun.
+    fn sky_radiance(&self, direction: glam::Vec3) -> glam::Vec3 {
+        let yxy = self.zenith * self.perez(direction.y, direction.dot(self.sun_direction));
+        xyz_to_rgb(glam::Vec3::new(
+            yxy.y * yxy.x / yxy.z,
+            yxy.x,
+            (1.0 - yxy.y - yxy.z) * yxy.x / yxy.z,
+        )) * SKY_SCALE
+    }
 }
 
-#[derive(Clone, Debug, Default, encase::ShaderType)]
-pub struct InputTypeSphere {
-    pub center: glam::Vec3,
-    pub radius: f32,
-    pub material: InputTypeMaterial,
+/// CIE XYZ to linear sRGB.
+fn xyz_to_rgb(xyz: glam::Vec3) -> glam::Vec3 {
+    glam::Vec3::new(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    )
+    .max(glam::Vec3::ZERO)
 }
 
-#[derive(Clone, Debug, Default, encase::ShaderType)]
-pub struct InputTypeMaterial {
-    albedo: glam::Vec3,
-    // 0. background
-    // 1. lambertian
-    // 2. metal
-    // 3. dielectric
-    type_: u32,
-    fuzz: f32,
-    index_of_refraction: f32,
+/// Equirectangular map of the radiance arriving from every direction.
+///
+/// The top row looks straight up, the middle column looks down -z.
+#[derive(Clone, Debug, Default)]
+pub struct InputTypeEnvironment {
+    pub size: glam::UVec2,
+
+    /// Top row first.
+    pub texels: Vec<InputTypeEnvironmentTexel>,
+}
+
+impl InputTypeEnvironment {
+    /// Texels are importance sampled in proportion to their luminance and the solid angle they cover.
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn new(size: glam::UVec2, radiance: &[glam::Vec3]) -> Self {
+        let weights = (0..size.y)
+            .flat_map(|y| {
+                // texels shrink towards the poles
+                let sin_theta = (std::f32::consts::PI * (y as f32 + 0.5) / size.y as f32).sin();
+                (0..size.x).map(move |x| (x, y, sin_theta))
+            })
+            .map(|(x, y, sin_theta)| {
+                let color = radiance[(y * size.x + x) as usize];
+                (0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z).max(0.0) * sin_theta
+            })
+            .collect::<Vec<_>>();
+
+        // a black environment is sampled uniformly
+        let total = weights.iter().sum::<f32>();
+        let uniform = total <= 0.0;
+        let total = if uniform { weights.len() as f32 } else { total };
+
+        let mut cdf = 0.0;
+        let texels = radiance
+            .iter()
+            .zip(&weights)
+            .map(|(&radiance, &weight)| {
+                cdf += if uniform { 1.0 } else { weight } / total;
+                InputTypeEnvironmentTexel { radiance, cdf }
+            })
+            .collect();
+
+        Self { size, texels }
+    }
+}
+
+impl InputTypeVolume {
+    #[must_use]
+    pub fn new_sphere(center: glam::Vec3, radius: f32, density: f32, albedo: glam::Vec3) -> Self {
+        Self {
+            center,
+            radius,
+            half_size: glam::Vec3::ZERO,
+            density,
+            boundary: 0,
+            material: InputTypeMaterial::new_isotropic(albedo),
+        }
+    }
+
+    #[must_use]
+    pub fn new_box(min: glam::Vec3, max: glam::Vec3, density: f32, albedo: glam::Vec3) -> Self {
+        Self {
+            center: (min + max) * 0.5,
+            radius: 0.0,
+            half_size: (max - min).abs() * 0.5,
+            density,
+            boundary: 1,
+            material: InputTypeMaterial::new_isotropic(albedo),
+        }
+    }
+}
+
+/// Primitives of `InputType`, in the space of the object, which are only drawn where an instance
+/// places them.
+#[derive(Clone, Debug, Default)]
+pub struct InputTypeObject {
+    /// spheres, triangles, quads and disks, planes are unbounded and cannot be instanced
+    pub primitives: Vec<InputTypePrimitive>,
+}
+
+/// Places `InputType::objects[object]` in the world.
+#[derive(Clone, Debug, Default)]
+pub struct InputTypeInstance {
+    pub object_to_world: glam::Mat4,
+    pub world_to_object: glam::Mat4,
+    pub object: u32,
+}
+
+impl InputTypeInstance {
+    #[must_use]
+    pub fn new(object: u32, object_to_world: glam::Mat4) -> Self {
+        Self {
+            object_to_world,
+            world_to_object: object_to_world.inverse(),
+            object,
+        }
+    }
+}
+
+impl InputTypePrimitive {
+    pub const SPHERE: u32 = 0;
+    pub const TRIANGLE: u32 = 1;
+    pub const ENVIRONMENT: u32 = 2;
+    pub const PLANE: u32 = 3;
+    pub const QUAD: u32 = 4;
+    pub const DISK: u32 = 5;
+    pub const INSTANCE: u32 = 6;
+    pub const VOLUME: u32 = 7;
 }
 
 impl InputTypeMaterial {
+    const NO_TEXTURE: u32 = u32::MAX;
+
     #[must_use]
     pub fn new_lambertian(albedo: glam::Vec3) -> Self {
         Self {
@@ -46,6 +508,7 @@ impl InputTypeMaterial {
             type_: 1,
             fuzz: 0.0,
             index_of_refraction: 0.0,
+            texture: Self::NO_TEXTURE,
         }
     }
 
@@ -56,6 +519,7 @@ impl InputTypeMaterial {
             type_: 2,
             fuzz,
             index_of_refraction: 0.0,
+            texture: Self::NO_TEXTURE,
         }
     }
 
@@ -70,21 +534,215 @@ impl InputTypeMaterial {
             type_: 3,
             fuzz: 0.0,
             index_of_refraction,
+            texture: Self::NO_TEXTURE,
+        }
+    }
+
+    #[must_use]
+    pub fn new_diffuse_light(emit: glam::Vec3) -> Self {
+        Self {
+            albedo: emit,
+            type_: 4,
+            fuzz: 0.0,
+            index_of_refraction: 0.0,
+            texture: Self::NO_TEXTURE,
+        }
+    }
+
+    #[must_use]
+    pub fn new_isotropic(albedo: glam::Vec3) -> Self {
+        Self {
+            albedo,
+            type_: 5,
+            fuzz: 0.0,
+            index_of_refraction: 0.0,
+            texture: Self::NO_TEXTURE,
+        }
+    }
+
+    /// Multiplies the albedo of a lambertian or metal material by `InputType::textures[texture]`
+    /// at every hit.
+    #[must_use]
+    pub fn with_texture(self, texture: u32) -> Self {
+        Self { texture, ..self }
+    }
+}
+
+impl InputTypeTexture {
+    #[must_use]
+    pub fn new_solid(color: glam::Vec3) -> Self {
+        Self {
+            color,
+            type_: 0,
+            ..Default::default()
+        }
+    }
+
+    #[must_use]
+    pub fn new_checker(even: glam::Vec3, odd: glam::Vec3, scale: f32) -> Self {
+        Self {
+            color: even,
+            type_: 1,
+            odd,
+            scale,
+            ..Default::default()
+        }
+    }
+
+    #[must_use]
+    pub fn new_uv_checker(even: glam::Vec3, odd: glam::Vec3, scale: f32) -> Self {
+        Self {
+            color: even,
+            type_: 2,
+            odd,
+            scale,
+            ..Default::default()
+        }
+    }
+
+    /// `color` scaled by smooth noise between 0 and 1.
+    #[must_use]
+    pub fn new_noise(color: glam::Vec3, scale: f32) -> Self {
+        Self {
+            color,
+            type_: 3,
+            scale,
+            ..Default::default()
+        }
+    }
+
+    /// `color` scaled by the magnitude of `octaves` of noise, each at twice the frequency and half
+    /// the amplitude of the one before.
+    #[must_use]
+    pub fn new_turbulence(color: glam::Vec3, scale: f32, octaves: u32) -> Self {
+        Self {
+            color,
+            type_: 4,
+            scale,
+            octaves,
+            ..Default::default()
+        }
+    }
+
+    /// Wraps the texels `offset..offset + size.x * size.y` of `InputType::texels`, top row first,
+    /// around the uv coordinates.
+    #[must_use]
+    pub fn new_image(size: glam::UVec2, offset: u32) -> Self {
+        Self {
+            type_: 5,
+            size,
+            offset,
+            ..Default::default()
         }
     }
 }
 
-#[derive(Debug, Default, encase::ShaderType)]
+#[derive(Debug, Default)]
 pub struct OutputType {
-    pub pixel_length: encase::ArrayLength,
-    #[size(runtime)]
-    pub pixels: Vec<glam::UVec3>,
+    pub pixels: Vec<glam::Vec4>,
+    /// number of samples taken of each pixel
+    pub samples: Vec<u32>,
+    /// first hit of the camera rays of each pixel
+    pub aovs: Vec<OutputTypeAov>,
+}
+
+/// References every sphere, triangle, quad and disk of `in_value` with a diffuse light material,
+/// and an environment or sky background. Planes are unbounded and the primitives of objects are
+/// only reached by bounces, neither is sampled.
+#[must_use]
+pub fn lights(in_value: &InputType) -> Vec<InputTypePrimitive> {
+    let primitives = in_value.primitives().into_iter().filter(|&primitive| {
+        in_value
+            .material(primitive)
+            .is_some_and(|material| material.type_ == 4)
+    });
+
+    let background = &in_value.background;
+    let environment =
+        (background.type_ == 3 || background.type_ == 4).then_some(InputTypePrimitive {
+            type_: InputTypePrimitive::ENVIRONMENT,
+            index: 0,
+        });
+
+    primitives.chain(environment).collect()
 }
 
-#[derive(Debug, Default, encase::ShaderType)]
-struct RandomType {
-    #[size(runtime)]
-    values: Vec<f32>,
+/// Averages the radiance summed in `pixels`, pixels without samples are black.
+#[allow(clippy::cast_precision_loss)]
+fn estimate(pixels: &[AccumulationPixel]) -> OutputType {
+    OutputType {
+        pixels: pixels
+            .iter()
+            .map(|pixel| {
+                if pixel.samples > 0 {
+                    (pixel.radiance / pixel.samples as f32).extend(1.0)
+                } else {
+                    glam::Vec4::W
+                }
+            })
+            .collect(),
+        samples: pixels.iter().map(|pixel| pixel.samples).collect(),
+        aovs: pixels.iter().map(|pixel| pixel.aov.clone()).collect(),
+    }
+}
+
+/// Samples taken of every pixel once the budget of `in_value` is spent, unless `time_limit` is exceeded first.
+fn target_samples(in_value: &InputType) -> u32 {
+    if in_value.adaptive_noise_threshold().is_some() {
+        in_value.max_samples.max(in_value.samples_per_pixel)
+    } else {
+        in_value.samples_per_pixel
+    }
+}
+
+/// Number of samples the next dispatch adds once `samples` were taken since `start`.
+///
+/// Returns `None` when the sample or time budget of `in_value` is spent, at least one dispatch is always made.
+fn next_dispatch(in_value: &InputType, samples: u32, start: Instant) -> Option<u32> {
+    let target_samples = target_samples(in_value);
+    if samples >= target_samples || (samples > 0 && time_limit_exceeded(in_value, start)) {
+        return None;
+    }
+
+    Some(
+        in_value
+            .samples_per_dispatch
+            .max(1)
+            .min(target_samples - samples),
+    )
+}
+
+/// Whether the time limit of `in_value` has passed since `start`.
+fn time_limit_exceeded(in_value: &InputType, start: Instant) -> bool {
+    in_value
+        .time_limit
+        .is_some_and(|time_limit| start.elapsed() >= time_limit)
+}
+
+/// Radiance of one view box summed over every dispatch so far, see [`Shader::accumulate`].
+pub struct Accumulation {
+    radiance_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    dispatch_buffer: wgpu::Buffer,
+    active_pixels_buffer: wgpu::Buffer,
+    samples: u32,
+    active_pixels: u32,
+    view_box_position: glam::UVec2,
+    view_box_size: glam::UVec2,
+}
+
+impl Accumulation {
+    /// Number of samples taken of every pixel which has not converged yet.
+    #[must_use]
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Number of pixels the next dispatch adds samples to, none once every pixel has converged.
+    #[must_use]
+    pub fn active_pixels(&self) -> u32 {
+        self.active_pixels
+    }
 }
 
 pub struct Shader {
@@ -95,8 +753,10 @@ pub struct Shader {
 }
 
 impl Shader {
-    #[must_use]
-    pub fn new(gpu: GPU) -> Self {
+    /// # Errors
+    ///
+    /// Will return `Err` if the device cannot bind as many storage buffers as the shader uses.
+    pub fn new(gpu: GPU) -> crate::Result<Self> {
         // create the shader
         let workgroup_size = glam::UVec3::new(8, 8, 1);
 
@@ -118,42 +778,49 @@ impl Shader {
             });
 
         // create the interface for the shader
+        let entries = [
+            storage_buffer_layout_entry(0, true, InputTypeBuffer::min_size()),
+            storage_buffer_layout_entry(1, false, AccumulationType::min_size()),
+            storage_buffer_layout_entry(2, true, VertexType::min_size()),
+            storage_buffer_layout_entry(3, true, TriangleType::min_size()),
+            storage_buffer_layout_entry(4, true, BvhNodeType::min_size()),
+            storage_buffer_layout_entry(5, true, BvhPrimitiveType::min_size()),
+            storage_buffer_layout_entry(6, true, LightType::min_size()),
+            uniform_buffer_layout_entry(7, DispatchType::min_size()),
+            storage_buffer_layout_entry(8, true, EnvironmentType::min_size()),
+            storage_buffer_layout_entry(9, true, PlaneType::min_size()),
+            storage_buffer_layout_entry(10, true, QuadType::min_size()),
+            storage_buffer_layout_entry(11, true, DiskType::min_size()),
+            storage_buffer_layout_entry(12, true, InstanceType::min_size()),
+            storage_buffer_layout_entry(13, true, VolumeType::min_size()),
+            storage_buffer_layout_entry(14, true, TextureType::min_size()),
+            storage_buffer_layout_entry(15, true, TexelType::min_size()),
+        ];
+
+        // fail clearly rather than on a validation error, as many devices only bind the default of 8
+        #[allow(clippy::cast_possible_truncation)]
+        let required = entries
+            .iter()
+            .filter(|entry| {
+                matches!(
+                    entry.ty,
+                    wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { .. },
+                        ..
+                    }
+                )
+            })
+            .count() as u32;
+        let limit = gpu.device().limits().max_storage_buffers_per_shader_stage;
+        if required > limit {
+            return Err(Error::WgpuStorageBuffers { required, limit });
+        }
+
         let bind_group_layout =
             gpu.device()
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                     label: None,
-                    entries: &[
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: wgpu::ShaderStages::COMPUTE,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: Some(InputType::min_size()),
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 1,
-                            visibility: wgpu::ShaderStages::COMPUTE,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: false },
-                                has_dynamic_offset: false,
-                                min_binding_size: Some(OutputType::min_size()),
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 2,
-                            visibility: wgpu::ShaderStages::COMPUTE,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: Some(RandomType::min_size()),
-                            },
-                            count: None,
-                        },
-                    ],
+                    entries: &entries,
                 });
 
         let pipeline_layout =
@@ -173,94 +840,294 @@ impl Shader {
                 entry_point: "main",
             });
 
-        Self {
+        Ok(Self {
             bind_group_layout,
             gpu,
             pipeline,
             workgroup_size,
-        }
+        })
     }
 
-    #[allow(clippy::too_many_lines)]
-    #[allow(clippy::missing_panics_doc)]
-    pub async fn execute(&self, in_value: &InputType) -> OutputType {
-        // create a buffer for the shader input
-        let mut in_byte_buffer = Vec::new();
-        let mut in_buffer = encase::StorageBuffer::new(&mut in_byte_buffer);
+    /// Renders the view box of `in_value`, one dispatch of `samples_per_dispatch` samples at a time.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a buffer of the scene is larger than the device can bind.
+    pub async fn execute(&self, in_value: &InputType) -> crate::Result<OutputType> {
+        let mut accumulations = [self.accumulation(in_value)?];
+        self.accumulate_within_budget(in_value, &mut accumulations)
+            .await;
+        Ok(self.estimate(&accumulations[0]).await)
+    }
+
+    /// Creates an accumulation of the view box of `in_value` without any samples.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a buffer of the scene is larger than the device can bind.
+    pub fn accumulation(&self, in_value: &InputType) -> crate::Result<Accumulation> {
+        Ok(self
+            .accumulations(
+                in_value,
+                &[(in_value.view_box_position, in_value.view_box_size)],
+            )?
+            .remove(0))
+    }
 
-        in_buffer.write(in_value).unwrap();
+    /// Creates accumulations of the `(position, size)` view boxes, which share the buffers of the scene.
+    #[allow(clippy::too_many_lines)]
+    fn accumulations(
+        &self,
+        in_value: &InputType,
+        view_boxes: &[(glam::UVec2, glam::UVec2)],
+    ) -> crate::Result<Vec<Accumulation>> {
+        // without a hierarchy, the shader tests every primitive in the list of one without nodes
+        let bvh = in_value
+            .bvh
+            .clone()
+            .unwrap_or_else(|| bvh::Bvh::flat(in_value));
 
+        // create buffers for the shader input
         let input_buffer =
-            self.gpu
-                .device()
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Input Buffer"),
-                    contents: &in_byte_buffer,
-                    usage: wgpu::BufferUsages::STORAGE,
-                });
+            self.create_storage_buffer("Input Buffer", &InputTypeBuffer::new(in_value, &bvh))?;
 
-        // create a buffer for the shader output
-        let output_buffer = self.gpu.device().create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Output Buffer"),
-            size: u64::from(OutputType::min_size())
-                * u64::from(in_value.view_box_size.x)
-                * u64::from(in_value.view_box_size.y),
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
-            mapped_at_creation: false,
-        });
+        let vertex_buffer = self.create_storage_buffer(
+            "Vertex Buffer",
+            &VertexType {
+                vertices: in_value.vertices.clone(),
+            },
+        )?;
 
-        // create a buffer for the shader random
-        let mut rng = rand::thread_rng();
-        let random_value = RandomType {
-            values: (0..1_000_000).map(|_| rng.gen()).collect(),
-        };
+        let triangle_buffer = self.create_storage_buffer(
+            "Triangle Buffer",
+            &TriangleType {
+                triangles: in_value.triangles.clone(),
+            },
+        )?;
+
+        let plane_buffer = self.create_storage_buffer(
+            "Plane Buffer",
+            &PlaneType {
+                planes: in_value.planes.clone(),
+            },
+        )?;
+
+        let quad_buffer = self.create_storage_buffer(
+            "Quad Buffer",
+            &QuadType {
+                quads: in_value.quads.clone(),
+            },
+        )?;
+
+        let disk_buffer = self.create_storage_buffer(
+            "Disk Buffer",
+            &DiskType {
+                disks: in_value.disks.clone(),
+            },
+        )?;
+
+        let volume_buffer = self.create_storage_buffer(
+            "Volume Buffer",
+            &VolumeType {
+                volumes: in_value.volumes.clone(),
+            },
+        )?;
+
+        let texture_buffer = self.create_storage_buffer(
+            "Texture Buffer",
+            &TextureType {
+                textures: in_value.textures.clone(),
+            },
+        )?;
+
+        let texel_buffer = self.create_storage_buffer(
+            "Texel Buffer",
+            &TexelType {
+                texels: in_value.texels.clone(),
+            },
+        )?;
+
+        let instance_buffer = self.create_storage_buffer(
+            "Instance Buffer",
+            &InstanceType {
+                instances: in_value
+                    .instances
+                    .iter()
+                    .map(|instance| Instance {
+                        object_to_world: instance.object_to_world,
+                        world_to_object: instance.world_to_object,
+                        object: bvh.objects[instance.object as usize].clone(),
+                    })
+                    .collect(),
+            },
+        )?;
+
+        let bvh_node_buffer =
+            self.create_storage_buffer("BVH Node Buffer", &BvhNodeType { nodes: bvh.nodes })?;
+
+        let bvh_primitive_buffer = self.create_storage_buffer(
+            "BVH Primitive Buffer",
+            &BvhPrimitiveType {
+                primitives: bvh.primitives,
+            },
+        )?;
 
-        let mut random_byte_buffer = Vec::new();
-        let mut random_buffer = encase::StorageBuffer::new(&mut random_byte_buffer);
+        let light_buffer = self.create_storage_buffer(
+            "Light Buffer",
+            &LightType {
+                light_length: encase::ArrayLength,
+                lights: in_value.lights.clone(),
+            },
+        )?;
 
-        random_buffer.write(&random_value).unwrap();
+        let environment_buffer = self.create_storage_buffer(
+            "Environment Buffer",
+            &EnvironmentType {
+                size: in_value.environment.size,
+                texels: in_value.environment.texels.clone(),
+            },
+        )?;
 
-        let random_buffer =
-            self.gpu
-                .device()
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Random Buffer"),
-                    contents: &random_byte_buffer,
-                    usage: wgpu::BufferUsages::STORAGE,
+        view_boxes
+            .iter()
+            .map(|&(view_box_position, view_box_size)| {
+                // create a zeroed buffer for the radiance summed by every dispatch
+                let size = accumulation_size(view_box_size);
+                self.check_buffer_size("Accumulation Buffer", size)?;
+                let radiance_buffer = self.gpu.device().create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Accumulation Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::STORAGE
+                        | wgpu::BufferUsages::COPY_SRC
+                        | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
                 });
 
-        // create a buffer for the result
-        let mapping_buffer = self.gpu.device().create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Mapping Buffer"),
-            size: u64::from(OutputType::min_size())
-                * u64::from(in_value.view_box_size.x)
-                * u64::from(in_value.view_box_size.y),
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-            mapped_at_creation: false,
-        });
+                // create a buffer to read back the pixels counted as active by each dispatch
+                let active_pixels_buffer =
+                    self.gpu.device().create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("Active Pixels Buffer"),
+                        size: ACTIVE_PIXELS_SIZE,
+                        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                        mapped_at_creation: false,
+                    });
 
-        // bind the resources to the interface
-        let bind_group = self
-            .gpu
-            .device()
-            .create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Bind Group"),
-                layout: &self.bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: input_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: output_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: random_buffer.as_entire_binding(),
-                    },
-                ],
-            });
+                // create a buffer for the samples and pixels of each dispatch
+                let dispatch_buffer =
+                    self.gpu
+                        .device()
+                        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Dispatch Buffer"),
+                            contents: &uniform_contents(&DispatchType::default()),
+                            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                        });
+
+                // bind the resources to the interface
+                let bind_group = self
+                    .gpu
+                    .device()
+                    .create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("Bind Group"),
+                        layout: &self.bind_group_layout,
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: input_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: radiance_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 2,
+                                resource: vertex_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 3,
+                                resource: triangle_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 4,
+                                resource: bvh_node_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 5,
+                                resource: bvh_primitive_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 6,
+                                resource: light_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 7,
+                                resource: dispatch_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 8,
+                                resource: environment_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 9,
+                                resource: plane_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 10,
+                                resource: quad_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 11,
+                                resource: disk_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 12,
+                                resource: instance_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 13,
+                                resource: volume_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 14,
+                                resource: texture_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 15,
+                                resource: texel_buffer.as_entire_binding(),
+                            },
+                        ],
+                    });
+
+                Ok(Accumulation {
+                    radiance_buffer,
+                    bind_group,
+                    dispatch_buffer,
+                    active_pixels_buffer,
+                    samples: 0,
+                    active_pixels: view_box_size.x * view_box_size.y,
+                    view_box_position,
+                    view_box_size,
+                })
+            })
+            .collect()
+    }
+
+    /// Adds `samples` samples to every pixel of `accumulation` in one dispatch, and waits for it to finish.
+    #[allow(clippy::missing_panics_doc)]
+    pub async fn accumulate(&self, accumulation: &mut Accumulation, samples: u32) {
+        self.gpu.queue().write_buffer(
+            &accumulation.dispatch_buffer,
+            0,
+            &uniform_contents(&DispatchType {
+                samples,
+                view_box_position: accumulation.view_box_position,
+                view_box_size: accumulation.view_box_size,
+            }),
+        );
+
+        // the dispatch counts the active pixels from zero
+        self.gpu
+            .queue()
+            .write_buffer(&accumulation.radiance_buffer, 0, &0u32.to_le_bytes());
 
         // create the command for the graphics card to execute
         let mut encoder = self
@@ -271,34 +1138,69 @@ impl Shader {
         {
             let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
             pass.set_pipeline(&self.pipeline);
-            pass.set_bind_group(0, &bind_group, &[]);
+            pass.set_bind_group(0, &accumulation.bind_group, &[]);
 
-            let view_box_size = in_value.view_box_size.extend(1);
+            let view_box_size = accumulation.view_box_size.extend(1);
             let mut workgroups = view_box_size / self.workgroup_size;
-            if view_box_size.x % self.workgroup_size.x > 0 {
+            if !view_box_size.x.is_multiple_of(self.workgroup_size.x) {
                 workgroups.x += 1;
             }
-            if view_box_size.y % self.workgroup_size.y > 0 {
+            if !view_box_size.y.is_multiple_of(self.workgroup_size.y) {
                 workgroups.y += 1;
             }
-            if view_box_size.z % self.workgroup_size.z > 0 {
+            if !view_box_size.z.is_multiple_of(self.workgroup_size.z) {
                 workgroups.z += 1;
             }
 
             pass.dispatch_workgroups(workgroups.x, workgroups.y, workgroups.z);
         }
 
-        // create the command for the output gpu buffer to be copied to the output cpu buffer
         encoder.copy_buffer_to_buffer(
-            &output_buffer,
+            &accumulation.radiance_buffer,
             0,
-            &mapping_buffer,
+            &accumulation.active_pixels_buffer,
             0,
-            u64::from(OutputType::min_size())
-                * u64::from(in_value.view_box_size.x)
-                * u64::from(in_value.view_box_size.y),
+            ACTIVE_PIXELS_SIZE,
         );
 
+        // submit the command for processing and wait for it, so the next dispatch starts fresh
+        let submission_index = self.gpu.queue().submit(core::iter::once(encoder.finish()));
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let active_pixels_slice = accumulation.active_pixels_buffer.slice(..);
+        active_pixels_slice.map_async(wgpu::MapMode::Read, |v| sender.send(v).unwrap());
+        self.gpu.poll(submission_index).await.unwrap();
+        receiver.await.unwrap().unwrap();
+
+        let mut active_pixels = 0u32;
+        encase::StorageBuffer::new(active_pixels_slice.get_mapped_range().as_ref())
+            .read(&mut active_pixels)
+            .unwrap();
+        accumulation.active_pixels_buffer.unmap();
+
+        accumulation.samples += samples;
+        accumulation.active_pixels = active_pixels;
+    }
+
+    /// Reads the current estimate of `accumulation`, the average of the samples taken so far.
+    #[allow(clippy::missing_panics_doc)]
+    pub async fn estimate(&self, accumulation: &Accumulation) -> OutputType {
+        let size = accumulation_size(accumulation.view_box_size);
+
+        // create a buffer for the result
+        let mapping_buffer = self.gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Mapping Buffer"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        // create the command for the accumulation gpu buffer to be copied to the mapping cpu buffer
+        let mut encoder = self
+            .gpu
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_buffer_to_buffer(&accumulation.radiance_buffer, 0, &mapping_buffer, 0, size);
+
         // submit the command for processing
         let submission_index = self.gpu.queue().submit(core::iter::once(encoder.finish()));
 
@@ -317,23 +1219,111 @@ impl Shader {
         let mapping_slice_buffer_view = mapping_slice.get_mapped_range();
 
         // read the result from the view
-        let mut out_value = OutputType::default();
+        let mut accumulated = AccumulationType::default();
         encase::StorageBuffer::new(mapping_slice_buffer_view.as_ref())
-            .read(&mut out_value)
+            .read(&mut accumulated)
             .unwrap();
 
         // clean up buffer views and cpu buffer
         drop(mapping_slice_buffer_view);
         mapping_buffer.unmap();
 
-        out_value
+        #[allow(clippy::cast_possible_truncation)]
+        accumulated
+            .pixels
+            .truncate((accumulation.view_box_size.x * accumulation.view_box_size.y) as usize);
+
+        estimate(&accumulated.pixels)
+    }
+
+    /// Adds samples to every accumulation in turn, until the sample or time budget of `in_value` is spent.
+    ///
+    /// Accumulations whose pixels have all converged are skipped, and the budget counts as spent once
+    /// every one has. The time limit is checked between accumulations, so once the first pass is done
+    /// some may end up with one dispatch more than others.
+    async fn accumulate_within_budget(
+        &self,
+        in_value: &InputType,
+        accumulations: &mut [Accumulation],
+    ) {
+        let start = Instant::now();
+        let mut samples = 0;
+
+        'budget: while let Some(count) = next_dispatch(in_value, samples, start) {
+            if accumulations
+                .iter()
+                .all(|accumulation| accumulation.active_pixels == 0)
+            {
+                break;
+            }
+
+            for accumulation in accumulations
+                .iter_mut()
+                .filter(|accumulation| accumulation.active_pixels > 0)
+            {
+                if samples > 0 && time_limit_exceeded(in_value, start) {
+                    break 'budget;
+                }
+                self.accumulate(accumulation, count).await;
+            }
+            samples += count;
+
+            println!(
+                "[{:?}] accumulated {samples}/{} samples per pixel",
+                Utc::now().to_string(),
+                target_samples(in_value)
+            );
+        }
+    }
+
+    /// Creates a storage buffer holding `value`, padded to the minimum binding size of `T`.
+    ///
+    /// An empty runtime sized array is therefore seen by the shader as one zeroed element.
+    fn create_storage_buffer<T>(
+        &self,
+        label: &'static str,
+        value: &T,
+    ) -> crate::Result<wgpu::Buffer>
+    where
+        T: ShaderType + encase::internal::WriteInto,
+    {
+        let mut contents = Vec::new();
+        encase::StorageBuffer::new(&mut contents)
+            .write(value)
+            .unwrap();
+        #[allow(clippy::cast_possible_truncation)]
+        contents.resize(contents.len().max(T::min_size().get() as usize), 0);
+        self.check_buffer_size(label, contents.len() as u64)?;
+
+        Ok(self
+            .gpu
+            .device()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: &contents,
+                usage: wgpu::BufferUsages::STORAGE,
+            }))
+    }
+
+    /// Fails if a storage buffer of `size` bytes is larger than the device can create or bind, which
+    /// wgpu would otherwise only report once the buffer is used.
+    fn check_buffer_size(&self, label: &'static str, size: u64) -> crate::Result<()> {
+        let limits = self.gpu.device().limits();
+        let limit = u64::from(limits.max_storage_buffer_binding_size).min(limits.max_buffer_size);
+        if size > limit {
+            return Err(Error::WgpuBufferSize { label, size, limit });
+        }
+        Ok(())
     }
 
+    /// # Errors
+    ///
+    /// Will return `Err` if a buffer of the scene is larger than the device can bind.
     pub async fn execute_in_chunks(
         &self,
         in_value: &InputType,
         chunk_size: glam::UVec2,
-    ) -> OutputType {
+    ) -> crate::Result<OutputType> {
         println!("[{:?}] executing in chunks", Utc::now().to_string());
 
         let chunks = in_value.view_box_size / chunk_size;
@@ -342,14 +1332,7 @@ impl Shader {
         println!("[{:?}] chuck size {:?}", Utc::now().to_string(), chunk_size);
         println!("[{:?}] total chucks {:?}", Utc::now().to_string(), chunks);
 
-        let mut output = OutputType {
-            pixel_length: encase::ArrayLength,
-            pixels: vec![
-                glam::UVec3::default();
-                (in_value.screen_size.y * in_value.screen_size.x) as usize
-            ],
-        };
-
+        let mut view_boxes = Vec::new();
         for chunk_y in 0..=chunks.y {
             for chunk_x in 0..=chunks.x {
                 let view_box_position = glam::UVec2 {
@@ -370,68 +1353,195 @@ impl Shader {
                 };
 
                 if view_box_size.x > 0 && view_box_size.y > 0 {
-                    let i = InputType {
-                        view_box_position,
-                        view_box_size,
-                        ..in_value.clone()
-                    };
-
-                    println!(
-                        "[{:?}] executing chuck {chunk_x}/{} {chunk_y}/{}",
-                        Utc::now().to_string(),
-                        chunks.x,
-                        chunks.y
-                    );
-
-                    let output_chunk = self.execute(&i).await;
-
-                    println!(
-                        "[{:?}] executed chuck {chunk_x}/{} {chunk_y}/{}",
-                        Utc::now().to_string(),
-                        chunks.x,
-                        chunks.y
-                    );
-
-                    let x_offset = chunk_x * chunk_size.x;
-                    let y_offset = chunk_y * chunk_size.y;
-                    let x_max = in_value.view_box_size.x;
-
-                    for y in 0..view_box_size.y {
-                        for x in 0..view_box_size.x {
-                            output.pixels[((y + y_offset) * x_max + (x + x_offset)) as usize] =
-                                output_chunk.pixels[(y * view_box_size.x + x) as usize];
-                        }
-                    }
+                    view_boxes.push((view_box_position, view_box_size));
+                }
+            }
+        }
+
+        // every pass adds samples to each chunk, so a time limit leaves the image evenly sampled up to
+        // the one dispatch of the pass it interrupts
+        let mut accumulations = self.accumulations(in_value, &view_boxes)?;
+        self.accumulate_within_budget(in_value, &mut accumulations)
+            .await;
+
+        let mut output = OutputType {
+            pixels: vec![
+                glam::Vec4::default();
+                (in_value.screen_size.y * in_value.screen_size.x) as usize
+            ],
+            samples: vec![0; (in_value.screen_size.y * in_value.screen_size.x) as usize],
+            aovs: vec![
+                OutputTypeAov::default();
+                (in_value.screen_size.y * in_value.screen_size.x) as usize
+            ],
+        };
+
+        for accumulation in &accumulations {
+            let output_chunk = self.estimate(accumulation).await;
+
+            let offset = accumulation.view_box_position - in_value.view_box_position;
+            let view_box_size = accumulation.view_box_size;
+            let x_max = in_value.view_box_size.x;
+
+            for y in 0..view_box_size.y {
+                for x in 0..view_box_size.x {
+                    let index = ((y + offset.y) * x_max + (x + offset.x)) as usize;
+                    let chunk_index = (y * view_box_size.x + x) as usize;
+                    output.pixels[index] = output_chunk.pixels[chunk_index];
+                    output.samples[index] = output_chunk.samples[chunk_index];
+                    output.aovs[index] = output_chunk.aovs[chunk_index].clone();
                 }
             }
         }
 
         println!("[{:?}] executed in chunks", Utc::now().to_string());
 
-        output
+        Ok(output)
     }
 }
 
+/// Size in bytes of the count of active pixels at the start of an accumulation.
+const ACTIVE_PIXELS_SIZE: u64 = 4;
+
+/// Size in bytes of the accumulation of a view box, at least one pixel so the buffer can be bound.
+fn accumulation_size(view_box_size: glam::UVec2) -> u64 {
+    AccumulationType::min_size().get()
+        + AccumulationPixel::min_size().get()
+            * (u64::from(view_box_size.x * view_box_size.y).max(1) - 1)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{gpu::GPU, shaders::ray_tracer};
+    use crate::{
+        gpu::{GpuOptions, GPU},
+        scene::Camera,
+        shaders::ray_tracer,
+    };
 
     #[tokio::test]
     async fn test() {
-        let gpu = GPU::new().await.unwrap();
+        let gpu = GPU::new(&GpuOptions::default()).await.unwrap();
 
-        let shader = ray_tracer::Shader::new(gpu);
+        let shader = ray_tracer::Shader::new(gpu).unwrap();
 
-        let output = shader
-            .execute(&ray_tracer::InputType {
-                samples_per_pixel: 100,
-                screen_size: glam::UVec2 { x: 256, y: 256 },
-                view_box_position: glam::UVec2 { x: 0, y: 0 },
-                view_box_size: glam::UVec2 { x: 256, y: 256 },
-                spheres: Vec::new(),
-            })
-            .await;
+        let input = ray_tracer::InputType {
+            samples_per_pixel: 100,
+            seed: 0,
+            screen_size: glam::UVec2 { x: 256, y: 256 },
+            view_box_position: glam::UVec2 { x: 0, y: 0 },
+            view_box_size: glam::UVec2 { x: 256, y: 256 },
+            camera: (&Camera::default()).into(),
+            ..Default::default()
+        };
+        let output = shader.execute(&input).await.unwrap();
+
+        // the cpu draws the same random numbers, so only rounding may differ
+        let expected = ray_tracer::cpu::Shader::new().execute(&input);
+        assert_eq!(output.pixels.len(), expected.pixels.len());
+        for (pixel, expected) in output.pixels.iter().zip(&expected.pixels) {
+            assert!(pixel.abs_diff_eq(*expected, 1e-4));
+        }
+
+        // how the samples are split between dispatches makes no difference
+        let mut accumulation = shader.accumulation(&input).unwrap();
+        shader.accumulate(&mut accumulation, 70).await;
+        shader.accumulate(&mut accumulation, 30).await;
+        assert_eq!(accumulation.samples(), 100);
+        let estimate = shader.estimate(&accumulation).await;
+        for (pixel, expected) in estimate.pixels.iter().zip(&output.pixels) {
+            assert!(pixel.abs_diff_eq(*expected, 1e-4));
+        }
 
-        println!("{:?}", output);
+        // pixels below the noise threshold stop, and view boxes without any active pixels are skipped
+        let converged = ray_tracer::InputType {
+            noise_threshold: Some(f32::MAX),
+            max_samples: 1000,
+            samples_per_dispatch: 100,
+            ..input.clone()
+        };
+        let mut accumulation = shader.accumulation(&converged).unwrap();
+        shader.accumulate(&mut accumulation, 70).await;
+        assert_eq!(accumulation.active_pixels(), 256 * 256);
+        shader.accumulate(&mut accumulation, 30).await;
+        assert_eq!(accumulation.active_pixels(), 0);
+        let adaptive = shader.execute(&converged).await.unwrap();
+        assert!(adaptive.samples.iter().all(|&samples| samples == 100));
+
+        println!("{output:?}");
+    }
+
+    #[tokio::test]
+    async fn parity() {
+        let gpu = GPU::new(&GpuOptions::default()).await.unwrap();
+
+        let shader = ray_tracer::Shader::new(gpu).unwrap();
+
+        let vertex = |x, y| ray_tracer::InputTypeVertex {
+            position: glam::Vec3::new(x, y, -0.5),
+            normal: glam::Vec3::ZERO,
+        };
+        let mut input = ray_tracer::InputType {
+            samples_per_pixel: 16,
+            seed: 1,
+            screen_size: glam::UVec2 { x: 32, y: 32 },
+            view_box_position: glam::UVec2 { x: 0, y: 0 },
+            view_box_size: glam::UVec2 { x: 32, y: 32 },
+            camera: ray_tracer::InputTypeCamera {
+                look_from: glam::Vec3::new(0.0, 0.0, 5.0),
+                look_at: glam::Vec3::ZERO,
+                up: glam::Vec3::Y,
+                vertical_fov: 40.0,
+                aperture: 0.1,
+                focus_distance: 5.0,
+                shutter_open: 0.0,
+                shutter_close: 0.0,
+            },
+            background: ray_tracer::InputTypeBackground::new_black(),
+            spheres: vec![
+                ray_tracer::InputTypeSphere {
+                    center: glam::Vec3::new(-0.6, 0.0, 0.0),
+                    radius: 0.5,
+                    end_center: glam::Vec3::new(-0.6, 0.0, 0.0),
+                    material: ray_tracer::InputTypeMaterial::new_lambertian(glam::Vec3::splat(0.7)),
+                },
+                ray_tracer::InputTypeSphere {
+                    center: glam::Vec3::new(0.6, 0.0, 0.0),
+                    radius: 0.5,
+                    end_center: glam::Vec3::new(0.6, 0.0, 0.0),
+                    material: ray_tracer::InputTypeMaterial::new_metal(
+                        glam::Vec3::new(0.8, 0.6, 0.2),
+                        0.3,
+                    ),
+                },
+                ray_tracer::InputTypeSphere {
+                    center: glam::Vec3::new(0.0, 2.0, 1.0),
+                    radius: 0.5,
+                    end_center: glam::Vec3::new(0.0, 2.0, 1.0),
+                    material: ray_tracer::InputTypeMaterial::new_diffuse_light(glam::Vec3::splat(
+                        4.0,
+                    )),
+                },
+            ],
+            vertices: vec![vertex(0.2, -0.8), vertex(1.4, -0.8), vertex(0.8, 0.8)],
+            triangles: vec![ray_tracer::InputTypeTriangle {
+                indices: glam::UVec3::new(0, 1, 2),
+                material: ray_tracer::InputTypeMaterial::new_lambertian(glam::Vec3::new(
+                    0.2, 0.6, 0.3,
+                )),
+            }],
+            ..Default::default()
+        };
+        input.lights = ray_tracer::lights(&input);
+
+        // bounces and the lens draw the same random numbers on both, with and without sampling the
+        // light
+        for next_event_estimation in [false, true] {
+            input.next_event_estimation = next_event_estimation;
+            let output = shader.execute(&input).await.unwrap();
+            let expected = ray_tracer::cpu::Shader::new().execute(&input);
+            for (pixel, expected) in output.pixels.iter().zip(&expected.pixels) {
+                assert!(pixel.abs_diff_eq(*expected, 1e-4));
+            }
+        }
     }
 }
@@ -4,46 +4,288 @@
  * ============================================================================
  */
 struct InputType {
-    samples_per_pixel: u32,
+    max_depth: u32,
+    russian_roulette: u32,
+    russian_roulette_depth: u32,
+    seed: u32,
+    brute_force: u32,
+    // primitives and instances at the start of the bvh primitives, which brute force tests
+    primitive_length: u32,
+    next_event_estimation: u32,
+    // zero samples every pixel equally
+    noise_threshold: f32,
+    min_samples: u32,
+    max_samples: u32,
     screen_size: vec2<u32>,
-    view_box_position: vec2<u32>,
-    view_box_size: vec2<u32>,
+    camera: InputTypeCamera,
+    background: InputTypeBackground,
     spheres: array<Sphere>,
 }
 
+struct InputTypeCamera {
+    look_from: vec3<f32>,
+    look_at: vec3<f32>,
+    up: vec3<f32>,
+    vertical_fov: f32,
+    aperture: f32,
+    focus_distance: f32,
+    // times every camera ray is sampled between, moving spheres are at their center at 0 and
+    // their end center at 1
+    shutter_open: f32,
+    shutter_close: f32,
+}
+
+struct InputTypeBackground {
+    // solid colour, or what the environment is multiplied by
+    color: vec3<f32>,
+    // 0. gradient
+    // 1. solid
+    // 2. black
+    // 3. environment
+    // 4. sky
+    type_: u32,
+    // radians the environment is turned by around the y axis
+    rotation: f32,
+    sky: InputTypeSky,
+}
+
+// preetham daylight sky, with the ground below the horizon
+struct InputTypeSky {
+    // perez coefficients of the luminance and the x and y chromaticity
+    a: vec3<f32>,
+    b: vec3<f32>,
+    c: vec3<f32>,
+    d: vec3<f32>,
+    e: vec3<f32>,
+    // luminance and chromaticity at the zenith, divided by the perez function at the zenith
+    zenith: vec3<f32>,
+    // unit vector towards the sun
+    sun_direction: vec3<f32>,
+    sun_cos_radius: f32,
+    // zero when the sun is below the horizon
+    sun_radiance: vec3<f32>,
+    sun_solid_angle: f32,
+    // radiance of the ground, lit by the sky and the sun
+    ground: vec3<f32>,
+}
+
 @group(0) @binding(0)
 var<storage> in: InputType;
 
 /*
  * ============================================================================
- * Output Storage Buffer
+ * Dispatch Uniform Buffer
+ * ============================================================================
+ */
+struct DispatchType {
+    samples: u32,
+    view_box_position: vec2<u32>,
+    view_box_size: vec2<u32>,
+}
+
+@group(0) @binding(7)
+var<uniform> dispatch: DispatchType;
+
+/*
+ * ============================================================================
+ * Accumulation Storage Buffer
  * ============================================================================
  */
-struct OutputType {
-    pixel_length: u32,
-    pixel: array<vec3<u32>>,
+struct AccumulationPixel {
+    // summed over every sample
+    radiance: vec3<f32>,
+    samples: u32,
+    // summed over every sample along with its square, to estimate the variance of the pixel
+    luminance: f32,
+    luminance_squared: f32,
+    // first hit of the first sample
+    aov: Aov,
+}
+
+struct AccumulationType {
+    // pixels the next dispatch adds samples to, counted by this one
+    active_pixels: atomic<u32>,
+    pixels: array<AccumulationPixel>,
 }
 
 @group(0) @binding(1)
-var<storage, read_write> out: OutputType;
+var<storage, read_write> accumulation: AccumulationType;
 
 /*
  * ============================================================================
- * Random Storage Buffer
+ * Vertex Storage Buffer
  * ============================================================================
  */
-struct RandomType {
-    values: array<f32>,
+struct VertexType {
+    vertices: array<Vertex>,
 }
 
 @group(0) @binding(2)
-var<storage> random_type: RandomType;
+var<storage> vertex_type: VertexType;
+
+/*
+ * ============================================================================
+ * Triangle Storage Buffer
+ * ============================================================================
+ */
+struct TriangleType {
+    triangles: array<Triangle>,
+}
+
+@group(0) @binding(3)
+var<storage> triangle_type: TriangleType;
+
+/*
+ * ============================================================================
+ * BVH Node Storage Buffer
+ * ============================================================================
+ */
+struct BvhNodeType {
+    nodes: array<BvhNode>,
+}
+
+@group(0) @binding(4)
+var<storage> bvh_node_type: BvhNodeType;
+
+/*
+ * ============================================================================
+ * BVH Primitive Storage Buffer
+ * ============================================================================
+ */
+struct BvhPrimitiveType {
+    primitives: array<Primitive>,
+}
+
+@group(0) @binding(5)
+var<storage> bvh_primitive_type: BvhPrimitiveType;
+
+/*
+ * ============================================================================
+ * Light Storage Buffer
+ * ============================================================================
+ */
+struct LightType {
+    light_length: u32,
+    lights: array<Primitive>,
+}
+
+@group(0) @binding(6)
+var<storage> light_type: LightType;
+
+/*
+ * ============================================================================
+ * Environment Storage Buffer
+ * ============================================================================
+ */
+struct EnvironmentTexel {
+    radiance: vec3<f32>,
+    // probability of importance sampling this texel or one before it
+    cdf: f32,
+}
+
+struct EnvironmentType {
+    size: vec2<u32>,
+    // top row first
+    texels: array<EnvironmentTexel>,
+}
+
+@group(0) @binding(8)
+var<storage> environment_type: EnvironmentType;
+
+/*
+ * ============================================================================
+ * Plane Storage Buffer
+ * ============================================================================
+ */
+struct PlaneType {
+    planes: array<Plane>,
+}
+
+@group(0) @binding(9)
+var<storage> plane_type: PlaneType;
+
+/*
+ * ============================================================================
+ * Quad Storage Buffer
+ * ============================================================================
+ */
+struct QuadType {
+    quads: array<Quad>,
+}
+
+@group(0) @binding(10)
+var<storage> quad_type: QuadType;
+
+/*
+ * ============================================================================
+ * Disk Storage Buffer
+ * ============================================================================
+ */
+struct DiskType {
+    disks: array<Disk>,
+}
+
+@group(0) @binding(11)
+var<storage> disk_type: DiskType;
+
+/*
+ * ============================================================================
+ * Instance Storage Buffer
+ * ============================================================================
+ */
+struct InstanceType {
+    instances: array<Instance>,
+}
+
+@group(0) @binding(12)
+var<storage> instance_type: InstanceType;
+
+/*
+ * ============================================================================
+ * Volume Storage Buffer
+ * ============================================================================
+ */
+struct VolumeType {
+    volumes: array<Volume>,
+}
+
+@group(0) @binding(13)
+var<storage> volume_type: VolumeType;
+
+/*
+ * ============================================================================
+ * Texture Storage Buffer
+ * ============================================================================
+ */
+struct TextureType {
+    textures: array<Texture>,
+}
+
+@group(0) @binding(14)
+var<storage> texture_type: TextureType;
+
+/*
+ * ============================================================================
+ * Texel Storage Buffer
+ * ============================================================================
+ */
+struct TexelType {
+    // srgb colours of every image texture packed as rgba8, top row first
+    texels: array<u32>,
+}
+
+@group(0) @binding(15)
+var<storage> texel_type: TexelType;
 
 /*
  * ============================================================================
  * Mathematical Functions
  * ============================================================================
  */
+const PI: f32 = 3.14159265358979323846264338327950288;
+// luminance of the preetham model in kcd/m² to the radiance of the scene
+const SKY_SCALE: f32 = 0.05;
+
 fn length_squared(e: vec3<f32>) -> f32 {
     return e.x * e.x + e.y * e.y + e.z * e.z;
 }
@@ -54,7 +296,7 @@ fn near_zero(e: vec3<f32>) -> bool {
     return a.x < s && a.y < s && a.z < s;
 }
 
-fn reflect(v: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
+fn vec3_reflect(v: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
     return v - 2.0 * dot(v, n) * n;
 }
 
@@ -63,7 +305,24 @@ fn reflectance(cosine: f32, ref_idx: f32) -> f32 {
     return r0 + (1.0 - r0) * pow((1.0 - cosine), 5.0);
 }
 
-fn refract(uv: vec3<f32>, n: vec3<f32>, etai_over_etat: f32) -> vec3<f32> {
+// orthonormal basis around w, returns a * u + b * v + c * w
+fn onb_local(w: vec3<f32>, a: f32, b: f32, c: f32) -> vec3<f32> {
+    var up = vec3<f32>(1.0, 0.0, 0.0);
+    if abs(w.x) > 0.9 {
+        up = vec3<f32>(0.0, 1.0, 0.0);
+    }
+    let v = normalize(cross(w, up));
+    let u = cross(w, v);
+    return a * u + b * v + c * w;
+}
+
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let a = pdf * pdf;
+    let b = other_pdf * other_pdf;
+    return a / (a + b);
+}
+
+fn vec3_refract(uv: vec3<f32>, n: vec3<f32>, etai_over_etat: f32) -> vec3<f32> {
     let cos_theta = min(dot(-uv, n), 1.0);
     let r_out_perp =  etai_over_etat * (uv + cos_theta * n);
     let r_out_parallel = -sqrt(abs(1.0 - length_squared(r_out_perp))) * n;
@@ -75,44 +334,41 @@ fn refract(uv: vec3<f32>, n: vec3<f32>, etai_over_etat: f32) -> vec3<f32> {
  * Random Functions
  * ============================================================================
  */
-var<private> random_index : u32 = 0;
+var<private> random_state : u32 = 0u;
+
+// PCG hash, https://www.reedbeta.com/blog/hash-functions-for-gpu-rendering/
+fn pcg_hash(input: u32) -> u32 {
+    let state = input * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
 
 fn random() -> f32 {
-    if random_index >= arrayLength(&random_type.values) {
-        random_index = 0u;
-    }
-    let value = random_type.values[random_index];
-    random_index += 1u;
-    return value;
+    random_state = pcg_hash(random_state);
+    return f32(random_state >> 8u) / 16777216.0;
 }
 
 fn random_between(min: f32, max: f32) -> f32 {
     return min + (max - min) * random();
 }
 
-fn random_init(index: u32) {
-    random_index = index;
-    random_index = u32(random() * f32(arrayLength(&random_type.values)));
+fn random_init(pixel: vec2<u32>, sample: u32, seed: u32) {
+    random_state = pcg_hash(pixel.x ^ pcg_hash(pixel.y ^ pcg_hash(sample ^ pcg_hash(seed))));
 }
 
+// the result of a `loop` left by `break` is not reliably carried out of it on every backend
 fn random_in_unit_disk() -> vec3<f32> {
-    var p: vec3<f32>;
-    loop {
+    var p = vec3<f32>(random_between(-1.0, 1.0), random_between(-1.0, 1.0), 0.0);
+    while length_squared(p) >= 1.0 {
         p = vec3<f32>(random_between(-1.0, 1.0), random_between(-1.0, 1.0), 0.0);
-        if length_squared(p) < 1.0 {
-            break;
-        }
     }
     return p;
 }
 
 fn random_in_unit_sphere() -> vec3<f32> {
-    var p: vec3<f32>;
-    loop {
+    var p = random_vec3_between(-1.0, 1.0);
+    while length_squared(p) >= 1.0 {
         p = random_vec3_between(-1.0, 1.0);
-        if length_squared(p) < 1.0 {
-            break;
-        }
     }
     return p;
 }
@@ -143,6 +399,8 @@ struct Camera {
     v: vec3<f32>,
     w: vec3<f32>,
     lens_radius: f32,
+    shutter_open: f32,
+    shutter_close: f32,
 }
 
 fn camera_new(
@@ -153,6 +411,8 @@ fn camera_new(
     aspect_ratio: f32,
     aperture: f32,
     focus_dist: f32,
+    shutter_open: f32,
+    shutter_close: f32,
 ) -> Camera {
     let theta = radians(vfov);
     let h = tan(theta / 2.0);
@@ -170,16 +430,18 @@ fn camera_new(
 
     let lens_radius = aperture / 2.0;
 
-    return Camera(origin, horizontal, vertical, lower_left_corner, u, v, w, lens_radius);
+    return Camera(origin, horizontal, vertical, lower_left_corner, u, v, w, lens_radius, shutter_open, shutter_close);
 }
 
 fn camera_get_ray(camera: Camera, s: f32, t: f32) -> Ray {
     let rd = camera.lens_radius * random_in_unit_disk();
     let offset = camera.u * rd.x + camera.v * rd.y;
+    let time = random_between(camera.shutter_open, camera.shutter_close);
 
     return ray_new(
         camera.origin + offset,
-        camera.lower_left_corner + s * camera.horizontal + t * camera.vertical - camera.origin - offset
+        camera.lower_left_corner + s * camera.horizontal + t * camera.vertical - camera.origin - offset,
+        time
     );
 }
 
@@ -188,6 +450,19 @@ fn camera_get_ray(camera: Camera, s: f32, t: f32) -> Ray {
  * Hit Record
  * ============================================================================
  */
+struct Primitive {
+    // 0. sphere
+    // 1. triangle
+    // 2. environment, the map or the sun of the background
+    // 3. plane
+    // 4. quad
+    // 5. disk
+    // 6. instance, hits report it instead of the primitive of the object
+    // 7. volume
+    type_: u32,
+    index: u32,
+}
+
 struct HitRecord {
     some: bool,
     point: vec3<f32>,
@@ -195,14 +470,17 @@ struct HitRecord {
     t: f32,
     front_face: bool,
     material: Material,
+    primitive: Primitive,
+    // texture coordinates of the point on the surface
+    uv: vec2<f32>,
 }
 
-fn hit_record_new_some(point: vec3<f32>, normal: vec3<f32>, t: f32, front_face: bool, material: Material) -> HitRecord {
-    return HitRecord(true, point, normal, t, front_face, material);
+fn hit_record_new_some(point: vec3<f32>, normal: vec3<f32>, t: f32, front_face: bool, material: Material, primitive: Primitive, uv: vec2<f32>) -> HitRecord {
+    return HitRecord(true, point, normal, t, front_face, material, primitive, uv);
 }
 
 fn hit_record_new_none() -> HitRecord {
-    return HitRecord(false, vec3<f32>(), vec3<f32>(), 0.0, false, material_default());
+    return HitRecord(false, vec3<f32>(), vec3<f32>(), 0.0, false, material_default(), Primitive(0u, 0u), vec2<f32>());
 }
 
 fn hit_record_set_face_normal(hit_record: HitRecord, ray: Ray, outward_normal: vec3<f32>) -> HitRecord {
@@ -213,7 +491,7 @@ fn hit_record_set_face_normal(hit_record: HitRecord, ray: Ray, outward_normal: v
     } else {
         normal = -outward_normal;
     }
-    return HitRecord(hit_record.some, hit_record.point, normal, hit_record.t, front_face, hit_record.material);
+    return HitRecord(hit_record.some, hit_record.point, normal, hit_record.t, front_face, hit_record.material, hit_record.primitive, hit_record.uv);
 }
 
 /*
@@ -227,25 +505,72 @@ struct Material {
     // 1. lambertian
     // 2. metal
     // 3. dielectric
+    // 4. diffuse light, albedo is the emitted radiance
+    // 5. isotropic, scatters into every direction alike inside volumes
     type_: u32,
     fuzz: f32,
     index_of_refraction: f32,
+    // texture the albedo is multiplied by, or NO_TEXTURE
+    texture: u32,
 }
 
+const NO_TEXTURE: u32 = 0xffffffffu;
+
 fn material_default() -> Material {
-    return Material(vec3<f32>(), 0u, 0.0, 0.0);
+    return Material(vec3<f32>(), 0u, 0.0, 0.0, NO_TEXTURE);
 }
 
 fn material_new_lambertian(albedo: vec3<f32>) -> Material {
-    return Material(albedo, 1u, 0.0, 0.0);
+    return Material(albedo, 1u, 0.0, 0.0, NO_TEXTURE);
 }
 
 fn material_new_metal(albedo: vec3<f32>, fuzz: f32) -> Material {
-    return Material(albedo, 2u, fuzz, 0.0);
+    return Material(albedo, 2u, fuzz, 0.0, NO_TEXTURE);
 }
 
 fn material_new_dielectric(index_of_refraction: f32) -> Material {
-    return Material(vec3<f32>(1.0, 1.0, 1.0), 3u, 0.0, index_of_refraction);
+    return Material(vec3<f32>(1.0, 1.0, 1.0), 3u, 0.0, index_of_refraction, NO_TEXTURE);
+}
+
+fn material_new_diffuse_light(emit: vec3<f32>) -> Material {
+    return Material(emit, 4u, 0.0, 0.0, NO_TEXTURE);
+}
+
+fn material_new_isotropic(albedo: vec3<f32>) -> Material {
+    return Material(albedo, 5u, 0.0, 0.0, NO_TEXTURE);
+}
+
+// albedo at the hit, multiplied by the texture of the material
+fn material_texture_albedo(material: Material, hit_record: HitRecord) -> vec3<f32> {
+    if material.texture == NO_TEXTURE {
+        return material.albedo;
+    }
+    return material.albedo * texture_value(material.texture, hit_record.uv, hit_record.point);
+}
+
+fn material_emitted(material: Material) -> vec3<f32> {
+    if material.type_ == 4u {
+        return material.albedo;
+    }
+    return vec3<f32>(0.0, 0.0, 0.0);
+}
+
+// reflectance seen by the camera, used by the albedo AOV
+fn material_albedo(material: Material, hit_record: HitRecord) -> vec3<f32> {
+    switch material.type_ {
+        case 1u, 2u, 5u: {
+            return material_texture_albedo(material, hit_record);
+        }
+        case 3u: {
+            return vec3<f32>(1.0, 1.0, 1.0);
+        }
+        case 4u: {
+            return min(material.albedo, vec3<f32>(1.0, 1.0, 1.0));
+        }
+        default: {
+            return vec3<f32>(0.0, 0.0, 0.0);
+        }
+    }
 }
 
 struct MaterialScatterResult {
@@ -263,14 +588,14 @@ fn material_scatter(material: Material, ray_in: Ray, hit_record: HitRecord) -> M
                 scatter_direction = hit_record.normal;
             }
 
-            let scattered = ray_new(hit_record.point, scatter_direction);
-            return MaterialScatterResult(true, material.albedo, scattered);
+            let scattered = ray_new(hit_record.point, scatter_direction, ray_in.time);
+            return MaterialScatterResult(true, material_texture_albedo(material, hit_record), scattered);
         }
         case 2u: {
-            let reflected = reflect(normalize(ray_in.direction), hit_record.normal);
-            let scattered = ray_new(hit_record.point, reflected + material.fuzz * random_in_unit_sphere());
+            let reflected = vec3_reflect(normalize(ray_in.direction), hit_record.normal);
+            let scattered = ray_new(hit_record.point, reflected + material.fuzz * random_in_unit_sphere(), ray_in.time);
             let some = dot(scattered.direction, hit_record.normal) >  0.0;
-            return MaterialScatterResult(some, material.albedo, scattered);
+            return MaterialScatterResult(some, material_texture_albedo(material, hit_record), scattered);
         }
         case 3u: {
             let attenuation = vec3<f32>(1.0, 1.0, 1.0);
@@ -287,20 +612,243 @@ fn material_scatter(material: Material, ray_in: Ray, hit_record: HitRecord) -> M
             var direction: vec3<f32>;
 
             if cannot_refract || reflectance(cos_theta, refraction_ratio) > random() {
-                direction = reflect(unit_direction, hit_record.normal);
+                direction = vec3_reflect(unit_direction, hit_record.normal);
             } else {
-                direction = refract(unit_direction, hit_record.normal, refraction_ratio);
+                direction = vec3_refract(unit_direction, hit_record.normal, refraction_ratio);
             }
 
-            let scattered = ray_new(hit_record.point, direction);
+            let scattered = ray_new(hit_record.point, direction, ray_in.time);
             return MaterialScatterResult(true, attenuation, scattered);
         }
+        case 5u: {
+            let scattered = ray_new(hit_record.point, random_unit_vector(), ray_in.time);
+            return MaterialScatterResult(true, material_texture_albedo(material, hit_record), scattered);
+        }
         default: {
             return MaterialScatterResult(false, vec3<f32>(0.0, 0.0, 0.0), ray_default());
         }
     }
 }
 
+/*
+ * ============================================================================
+ * Texture
+ * ============================================================================
+ */
+struct Texture {
+    color: vec3<f32>,
+    // 0. solid
+    // 1. checker of cubes in space, centred on the lattice
+    // 2. checker of squares over the uv coordinates
+    // 3. perlin noise
+    // 4. turbulence, perlin noise summed over octaves
+    // 5. image
+    type_: u32,
+    // colour of the odd cubes and squares of a checker
+    odd: vec3<f32>,
+    // checker cells or noise features per unit of distance or uv
+    scale: f32,
+    size: vec2<u32>,
+    // first texel of an image
+    offset: u32,
+    octaves: u32,
+}
+
+fn perlin_hash(cell: vec3<i32>) -> u32 {
+    let c = bitcast<vec3<u32>>(cell);
+    return pcg_hash(c.x ^ pcg_hash(c.y ^ pcg_hash(c.z)));
+}
+
+// offset to a corner of the lattice along one of the twelve gradients of improved perlin noise,
+// picked by the hash of the corner
+fn perlin_gradient(hash: u32, offset: vec3<f32>) -> f32 {
+    let h = hash & 15u;
+    var u = offset.y;
+    if h < 8u {
+        u = offset.x;
+    }
+    var v = offset.z;
+    if h < 4u {
+        v = offset.y;
+    } else if h == 12u || h == 14u {
+        v = offset.x;
+    }
+    if (h & 1u) != 0u {
+        u = -u;
+    }
+    if (h & 2u) != 0u {
+        v = -v;
+    }
+    return u + v;
+}
+
+// gradient noise between -1 and 1, hashed instead of looked up so that it needs no tables
+fn perlin_noise(p: vec3<f32>) -> f32 {
+    let floored = floor(p);
+    let cell = vec3<i32>(floored);
+    let f = p - floored;
+    // quintic fade, smooth up to the second derivative across cells
+    let w = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+
+    var noise = 0.0;
+    for (var corner = 0u; corner < 8u; corner = corner + 1u) {
+        let c = vec3<u32>(corner & 1u, (corner >> 1u) & 1u, corner >> 2u);
+        let weight = select(1.0 - w, w, c == vec3<u32>(1u, 1u, 1u));
+        noise += weight.x * weight.y * weight.z * perlin_gradient(perlin_hash(cell + vec3<i32>(c)), f - vec3<f32>(c));
+    }
+    return noise;
+}
+
+fn perlin_turbulence(p: vec3<f32>, octaves: u32) -> f32 {
+    var turbulence = 0.0;
+    var point = p;
+    var weight = 1.0;
+    for (var i = 0u; i < octaves; i = i + 1u) {
+        turbulence += weight * perlin_noise(point);
+        weight *= 0.5;
+        point *= 2.0;
+    }
+    return abs(turbulence);
+}
+
+// srgb electro-optical transfer function, decodes the texels of images to linear colours
+fn srgb_eotf(value: vec3<f32>) -> vec3<f32> {
+    return select(
+        pow((value + 0.055) / 1.055, vec3<f32>(2.4)),
+        value / 12.92,
+        value <= vec3<f32>(0.04045)
+    );
+}
+
+fn texture_value(index: u32, uv: vec2<f32>, point: vec3<f32>) -> vec3<f32> {
+    let texture = texture_type.textures[index];
+    switch texture.type_ {
+        case 0u: {
+            return texture.color;
+        }
+        case 1u: {
+            // centred on the lattice, so that planes through it are not split between two cubes
+            let cell = vec3<i32>(floor(texture.scale * point + 0.5));
+            if ((cell.x + cell.y + cell.z) & 1) == 0 {
+                return texture.color;
+            }
+            return texture.odd;
+        }
+        case 2u: {
+            let cell = vec2<i32>(floor(texture.scale * uv));
+            if ((cell.x + cell.y) & 1) == 0 {
+                return texture.color;
+            }
+            return texture.odd;
+        }
+        case 3u: {
+            return texture.color * 0.5 * (1.0 + perlin_noise(texture.scale * point));
+        }
+        case 4u: {
+            return texture.color * perlin_turbulence(texture.scale * point, texture.octaves);
+        }
+        case 5u: {
+            if texture.size.x == 0u || texture.size.y == 0u {
+                return vec3<f32>(0.0, 0.0, 0.0);
+            }
+            // repeats, with v going up the image
+            let u = uv.x - floor(uv.x);
+            let v = 1.0 - (uv.y - floor(uv.y));
+            let x = min(u32(u * f32(texture.size.x)), texture.size.x - 1u);
+            let y = min(u32(v * f32(texture.size.y)), texture.size.y - 1u);
+            let rgba = unpack4x8unorm(texel_type.texels[texture.offset + y * texture.size.x + x]);
+            return srgb_eotf(rgba.xyz);
+        }
+        default: {
+            return vec3<f32>(0.0, 0.0, 0.0);
+        }
+    }
+}
+
+/*
+ * ============================================================================
+ * Background
+ * ============================================================================
+ */
+fn background_color(ray: Ray) -> vec3<f32> {
+    switch in.background.type_ {
+        case 0u: {
+            let unit_direction = normalize(ray.direction);
+            let t = 0.5 * (unit_direction.y + 1.0);
+            return (1.0 - t) * vec3<f32>(1.0, 1.0, 1.0) + t * vec3<f32>(0.5, 0.7, 1.0);
+        }
+        case 1u: {
+            return in.background.color;
+        }
+        case 3u: {
+            return environment_color(ray.direction);
+        }
+        case 4u: {
+            return sky_color(ray.direction);
+        }
+        default: {
+            return vec3<f32>(0.0, 0.0, 0.0);
+        }
+    }
+}
+
+fn rotate_y(v: vec3<f32>, angle: f32) -> vec3<f32> {
+    let c = cos(angle);
+    let s = sin(angle);
+    return vec3<f32>(c * v.x + s * v.z, v.y, c * v.z - s * v.x);
+}
+
+// equirectangular texel seen in direction, the middle column looks down -z
+fn environment_texel(direction: vec3<f32>) -> u32 {
+    let size = environment_type.size;
+    let unit_direction = rotate_y(normalize(direction), -in.background.rotation);
+    let u = atan2(unit_direction.x, -unit_direction.z) / (2.0 * PI) + 0.5;
+    let v = acos(clamp(unit_direction.y, -1.0, 1.0)) / PI;
+    let x = min(u32(max(u, 0.0) * f32(size.x)), size.x - 1u);
+    let y = min(u32(max(v, 0.0) * f32(size.y)), size.y - 1u);
+    return y * size.x + x;
+}
+
+fn environment_color(direction: vec3<f32>) -> vec3<f32> {
+    if environment_type.size.x == 0u || environment_type.size.y == 0u {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+
+    return in.background.color * environment_type.texels[environment_texel(direction)].radiance;
+}
+
+// perez function of each channel, for the cosine of the angle to the zenith and to the sun
+fn sky_perez(cos_theta: f32, cos_gamma: f32) -> vec3<f32> {
+    let sky = in.background.sky;
+    let gamma = acos(clamp(cos_gamma, -1.0, 1.0));
+    return (1.0 + sky.a * exp(sky.b / max(cos_theta, 0.001)))
+        * (1.0 + sky.c * exp(sky.d * gamma) + sky.e * cos_gamma * cos_gamma);
+}
+
+fn xyz_to_rgb(xyz: vec3<f32>) -> vec3<f32> {
+    return max(vec3<f32>(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    ), vec3<f32>(0.0, 0.0, 0.0));
+}
+
+fn sky_color(direction: vec3<f32>) -> vec3<f32> {
+    let sky = in.background.sky;
+    let unit_direction = normalize(direction);
+    if unit_direction.y < 0.0 {
+        return sky.ground;
+    }
+
+    let cos_gamma = dot(unit_direction, sky.sun_direction);
+    let yxy = sky.zenith * sky_perez(unit_direction.y, cos_gamma);
+    var color = xyz_to_rgb(vec3<f32>(yxy.y * yxy.x / yxy.z, yxy.x, (1.0 - yxy.y - yxy.z) * yxy.x / yxy.z)) * SKY_SCALE;
+    if cos_gamma >= sky.sun_cos_radius {
+        color += sky.sun_radiance;
+    }
+    return color;
+}
+
 /*
  * ============================================================================
  * Ray
@@ -309,14 +857,16 @@ fn material_scatter(material: Material, ray_in: Ray, hit_record: HitRecord) -> M
 struct Ray {
     origin: vec3<f32>,
     direction: vec3<f32>,
+    // within the shutter interval of the camera, the same for every bounce of a path
+    time: f32,
 }
 
 fn ray_default() -> Ray {
-    return Ray(vec3<f32>(0.0), vec3<f32>(0.0));
+    return Ray(vec3<f32>(0.0), vec3<f32>(0.0), 0.0);
 }
 
-fn ray_new(origin: vec3<f32>, direction: vec3<f32>) -> Ray {
-    return Ray(origin, direction);
+fn ray_new(origin: vec3<f32>, direction: vec3<f32>, time: f32) -> Ray {
+    return Ray(origin, direction, time);
 }
 
 fn ray_at(ray: Ray, t: f32) -> vec3<f32> {
@@ -324,34 +874,64 @@ fn ray_at(ray: Ray, t: f32) -> vec3<f32> {
 }
 
 fn ray_color(ray: Ray, world: World) -> vec3<f32> {
+    primary_aov = Aov();
+
     var current_ray = ray;
-    var depth = 0i;
-    var material_scatter_results = array<MaterialScatterResult, 50>();
+    var throughput = vec3<f32>(1.0, 1.0, 1.0);
+    var color = vec3<f32>(0.0, 0.0, 0.0);
 
-    for (; depth < 50i; depth = depth + 1i){
-        let hit_record = world_hit(world, current_ray, 0.001, 10000.0);
-        if hit_record.some {
-            let material_scatter_result = material_scatter(hit_record.material, current_ray, hit_record);
-            material_scatter_results[depth] = material_scatter_result;
+    // probability density of the previous diffuse bounce, zero when emission is not weighted
+    var previous_pdf = 0.0;
+    var previous_point = vec3<f32>();
 
-            if material_scatter_result.some {
-                current_ray = material_scatter_result.scattered;
-            } else {
-                break;
+    for (var depth = 0u; depth < in.max_depth; depth = depth + 1u) {
+        let hit_record = world_hit(world, current_ray, 0.001, 10000.0);
+        if !hit_record.some {
+            var radiance = background_color(current_ray);
+            if previous_pdf > 0.0 {
+                radiance *= power_heuristic(previous_pdf, light_pdf_background(current_ray.direction));
             }
-        } else {
-            break;
+            return color + throughput * radiance;
+        }
+
+        if depth == 0u {
+            primary_aov = aov_new(current_ray, hit_record);
+        }
+
+        var radiance = material_emitted(hit_record.material);
+        if previous_pdf > 0.0 && hit_record.material.type_ == 4u {
+            radiance *= power_heuristic(previous_pdf, light_pdf(previous_point, current_ray.time, hit_record));
+        }
+
+        let next_event_estimation = in.next_event_estimation != 0u && hit_record.material.type_ == 1u;
+        if next_event_estimation {
+            radiance += light_direct(world, hit_record, current_ray.time);
+        }
+
+        color += throughput * radiance;
+
+        let material_scatter_result = material_scatter(hit_record.material, current_ray, hit_record);
+        if !material_scatter_result.some {
+            // keep the emission of the absorbing hit
+            return color;
         }
-    }
 
-    let unit_direction = normalize(current_ray.direction);
-    let t = 0.5 * (unit_direction.y + 1.0);
-    var color = (1.0 - t) * vec3<f32>(1.0, 1.0, 1.0) + t * vec3<f32>(0.5, 0.7, 1.0);
-    depth -= 1i;
+        throughput *= material_scatter_result.attenuation;
+        current_ray = material_scatter_result.scattered;
+        previous_pdf = 0.0;
+        if next_event_estimation {
+            previous_pdf = max(dot(hit_record.normal, normalize(current_ray.direction)), 0.0) / PI;
+        }
+        previous_point = hit_record.point;
 
-    for (; depth >= 0i; depth = depth - 1i) {
-        let material_scatter_results = material_scatter_results[depth];
-        color = material_scatter_results.attenuation * color;
+        // terminate dim paths, the survivors carry their energy
+        if in.russian_roulette != 0u && depth + 1u >= in.russian_roulette_depth {
+            let survival = min(max(throughput.x, max(throughput.y, throughput.z)), 1.0);
+            if random() >= survival {
+                return color;
+            }
+            throughput /= survival;
+        }
     }
 
     return color;
@@ -363,17 +943,31 @@ fn ray_color(ray: Ray, world: World) -> vec3<f32> {
  * ============================================================================
  */
 struct Sphere {
+    // at time 0
     center: vec3<f32>,
     radius: f32,
+    // at time 1, the same as center for spheres standing still
+    end_center: vec3<f32>,
     material: Material,
 }
 
-fn sphere_new(center: vec3<f32>, radius: f32, material: Material) -> Sphere {
-    return Sphere(center, radius, material);
+fn sphere_new(center: vec3<f32>, radius: f32, end_center: vec3<f32>, material: Material) -> Sphere {
+    return Sphere(center, radius, end_center, material);
+}
+
+// moves linearly, and stays put before and after, where the hierarchy stops bounding it
+fn sphere_center(sphere: Sphere, time: f32) -> vec3<f32> {
+    return sphere.center + clamp(time, 0.0, 1.0) * (sphere.end_center - sphere.center);
 }
 
-fn sphere_hit(sphere: Sphere, ray: Ray, t_min: f32, t_max: f32) -> HitRecord {
-    let oc = ray.origin - sphere.center;
+fn sphere_hit(sphere: Sphere, primitive: Primitive, ray: Ray, t_min: f32, t_max: f32) -> HitRecord {
+    // the zeroed padding of an empty sphere array
+    if sphere.radius == 0.0 {
+        return hit_record_new_none();
+    }
+
+    let center = sphere_center(sphere, ray.time);
+    let oc = ray.origin - center;
     let a = length_squared(ray.direction);
     let half_b = dot(oc, ray.direction);
     let c = length_squared(oc) - (sphere.radius * sphere.radius);
@@ -394,55 +988,936 @@ fn sphere_hit(sphere: Sphere, ray: Ray, t_min: f32, t_max: f32) -> HitRecord {
 
     let rec_t = root;
     let rec_p = ray_at(ray, rec_t);
-    let outward_normal = (rec_p - sphere.center) / sphere.radius;
-    var hit_record = HitRecord(true, rec_p, vec3(0.0), rec_t, false, sphere.material);
+    let outward_normal = (rec_p - center) / sphere.radius;
+    // longitude from -x around through +z, and latitude from the bottom
+    let uv = vec2<f32>(
+        (atan2(-outward_normal.z, outward_normal.x) + PI) / (2.0 * PI),
+        acos(clamp(-outward_normal.y, -1.0, 1.0)) / PI
+    );
+    var hit_record = HitRecord(true, rec_p, vec3(0.0), rec_t, false, sphere.material, primitive, uv);
     hit_record = hit_record_set_face_normal(hit_record, ray, outward_normal);
     return hit_record;
 }
 
 /*
  * ============================================================================
- * World
+ * Triangle
  * ============================================================================
  */
-struct World {
-    a: u32,
+struct Vertex {
+    position: vec3<f32>,
+    // zero uses the face normal
+    normal: vec3<f32>,
 }
 
-fn world_hit(world: World, ray: Ray, t_min: f32, t_max: f32) -> HitRecord {
-    var hit_record = hit_record_new_none();
-    var closest_so_far = t_max;
+struct Triangle {
+    indices: vec3<u32>,
+    material: Material,
+}
 
-    for (var index = 0u; index < arrayLength(&in.spheres); index = index + 1u) {
-        let h = sphere_hit(in.spheres[index], ray, t_min, closest_so_far);
-        if h.some {
-            hit_record = h;
-            closest_so_far = h.t;
-        }
-    }
+// Möller–Trumbore, https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+fn triangle_hit(triangle: Triangle, primitive: Primitive, ray: Ray, t_min: f32, t_max: f32) -> HitRecord {
+    let v0 = vertex_type.vertices[triangle.indices.x];
+    let v1 = vertex_type.vertices[triangle.indices.y];
+    let v2 = vertex_type.vertices[triangle.indices.z];
 
-    return hit_record;
+    let edge1 = v1.position - v0.position;
+    let edge2 = v2.position - v0.position;
+    let h = cross(ray.direction, edge2);
+    let a = dot(edge1, h);
+
+    // parallel to the ray, or degenerate
+    if abs(a) < 0.0000000001 {
+        return hit_record_new_none();
+    }
+
+    let f = 1.0 / a;
+    let s = ray.origin - v0.position;
+    let u = f * dot(s, h);
+    if u < 0.0 || u > 1.0 {
+        return hit_record_new_none();
+    }
+
+    let q = cross(s, edge1);
+    let v = f * dot(ray.direction, q);
+    if v < 0.0 || u + v > 1.0 {
+        return hit_record_new_none();
+    }
+
+    let rec_t = f * dot(edge2, q);
+    if rec_t < t_min || t_max < rec_t {
+        return hit_record_new_none();
+    }
+
+    var outward_normal = (1.0 - u - v) * v0.normal + u * v1.normal + v * v2.normal;
+    if near_zero(outward_normal) {
+        outward_normal = cross(edge1, edge2);
+    }
+
+    let rec_p = ray_at(ray, rec_t);
+    var hit_record = HitRecord(true, rec_p, vec3(0.0), rec_t, false, triangle.material, primitive, vec2<f32>(u, v));
+    hit_record = hit_record_set_face_normal(hit_record, ray, normalize(outward_normal));
+    return hit_record;
+}
+
+/*
+ * ============================================================================
+ * Plane
+ * ============================================================================
+ */
+struct Plane {
+    point: vec3<f32>,
+    // zero for the padding of an empty plane array
+    normal: vec3<f32>,
+    material: Material,
+}
+
+// distance along the ray to the plane through point facing the unit normal,
+// or -1.0 when it is parallel or outside of [t_min, t_max]
+fn plane_distance(point: vec3<f32>, normal: vec3<f32>, ray: Ray, t_min: f32, t_max: f32) -> f32 {
+    let denominator = dot(normal, ray.direction);
+    if abs(denominator) < 0.00000001 {
+        return -1.0;
+    }
+
+    let t = dot(point - ray.origin, normal) / denominator;
+    if t < t_min || t_max < t {
+        return -1.0;
+    }
+    return t;
+}
+
+fn plane_hit(plane: Plane, primitive: Primitive, ray: Ray, t_min: f32, t_max: f32) -> HitRecord {
+    // the zeroed padding of an empty plane array
+    if near_zero(plane.normal) {
+        return hit_record_new_none();
+    }
+
+    let normal = normalize(plane.normal);
+    let rec_t = plane_distance(plane.point, normal, ray, t_min, t_max);
+    if rec_t < 0.0 {
+        return hit_record_new_none();
+    }
+
+    // distances along two axes of the plane
+    let rec_p = ray_at(ray, rec_t);
+    let planar = rec_p - plane.point;
+    let uv = vec2<f32>(dot(planar, onb_local(normal, 1.0, 0.0, 0.0)), dot(planar, onb_local(normal, 0.0, 1.0, 0.0)));
+    var hit_record = HitRecord(true, rec_p, vec3(0.0), rec_t, false, plane.material, primitive, uv);
+    hit_record = hit_record_set_face_normal(hit_record, ray, normal);
+    return hit_record;
+}
+
+/*
+ * ============================================================================
+ * Quad
+ * ============================================================================
+ */
+// parallelogram with the corners corner, corner + u, corner + u + v and corner + v
+struct Quad {
+    corner: vec3<f32>,
+    u: vec3<f32>,
+    v: vec3<f32>,
+    material: Material,
+}
+
+fn quad_hit(quad: Quad, primitive: Primitive, ray: Ray, t_min: f32, t_max: f32) -> HitRecord {
+    // degenerate, or the zeroed padding of an empty quad array
+    let n = cross(quad.u, quad.v);
+    if near_zero(n) {
+        return hit_record_new_none();
+    }
+
+    let normal = normalize(n);
+    let rec_t = plane_distance(quad.corner, normal, ray, t_min, t_max);
+    if rec_t < 0.0 {
+        return hit_record_new_none();
+    }
+
+    // coordinates of the hit along the edges
+    let rec_p = ray_at(ray, rec_t);
+    let planar = rec_p - quad.corner;
+    let w = n / dot(n, n);
+    let alpha = dot(w, cross(planar, quad.v));
+    let beta = dot(w, cross(quad.u, planar));
+    if alpha < 0.0 || alpha > 1.0 || beta < 0.0 || beta > 1.0 {
+        return hit_record_new_none();
+    }
+
+    var hit_record = HitRecord(true, rec_p, vec3(0.0), rec_t, false, quad.material, primitive, vec2<f32>(alpha, beta));
+    hit_record = hit_record_set_face_normal(hit_record, ray, normal);
+    return hit_record;
+}
+
+/*
+ * ============================================================================
+ * Disk
+ * ============================================================================
+ */
+struct Disk {
+    center: vec3<f32>,
+    radius: f32,
+    normal: vec3<f32>,
+    material: Material,
+}
+
+fn disk_hit(disk: Disk, primitive: Primitive, ray: Ray, t_min: f32, t_max: f32) -> HitRecord {
+    // the zeroed padding of an empty disk array
+    if disk.radius == 0.0 {
+        return hit_record_new_none();
+    }
+
+    let normal = normalize(disk.normal);
+    let rec_t = plane_distance(disk.center, normal, ray, t_min, t_max);
+    if rec_t < 0.0 {
+        return hit_record_new_none();
+    }
+
+    let rec_p = ray_at(ray, rec_t);
+    let planar = rec_p - disk.center;
+    if length_squared(planar) > disk.radius * disk.radius {
+        return hit_record_new_none();
+    }
+
+    // the square around the disk spans 0..1
+    let uv = 0.5 + 0.5 * vec2<f32>(dot(planar, onb_local(normal, 1.0, 0.0, 0.0)), dot(planar, onb_local(normal, 0.0, 1.0, 0.0))) / disk.radius;
+    var hit_record = HitRecord(true, rec_p, vec3(0.0), rec_t, false, disk.material, primitive, uv);
+    hit_record = hit_record_set_face_normal(hit_record, ray, normal);
+    return hit_record;
+}
+
+/*
+ * ============================================================================
+ * Volume
+ * ============================================================================
+ */
+struct Volume {
+    center: vec3<f32>,
+    radius: f32,
+    half_size: vec3<f32>,
+    // chance of scattering per unit of distance, zero for the padding of an empty volume array
+    density: f32,
+    // 0. sphere of radius around center
+    // 1. box reaching half_size from center
+    boundary: u32,
+    material: Material,
+}
+
+// distances the ray enters and leaves the boundary at, the first is larger when it misses
+fn volume_boundary(volume: Volume, ray: Ray) -> vec2<f32> {
+    if volume.boundary == 1u {
+        let inverse_direction = 1.0 / ray.direction;
+        let t0 = (volume.center - volume.half_size - ray.origin) * inverse_direction;
+        let t1 = (volume.center + volume.half_size - ray.origin) * inverse_direction;
+        let t_small = min(t0, t1);
+        let t_big = max(t0, t1);
+        return vec2<f32>(max(max(t_small.x, t_small.y), t_small.z), min(min(t_big.x, t_big.y), t_big.z));
+    }
+
+    let oc = ray.origin - volume.center;
+    let a = length_squared(ray.direction);
+    let half_b = dot(oc, ray.direction);
+    let c = length_squared(oc) - volume.radius * volume.radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return vec2<f32>(1.0, 0.0);
+    }
+
+    let sqrtd = sqrt(discriminant);
+    return vec2<f32>((-half_b - sqrtd) / a, (-half_b + sqrtd) / a);
+}
+
+// scatters at an exponentially distributed distance into the volume, rays starting inside it too
+fn volume_hit(volume: Volume, primitive: Primitive, ray: Ray, t_min: f32, t_max: f32) -> HitRecord {
+    if volume.density <= 0.0 {
+        return hit_record_new_none();
+    }
+
+    let boundary = volume_boundary(volume, ray);
+    let t_enter = max(boundary.x, t_min);
+    let t_exit = min(boundary.y, t_max);
+    if t_enter >= t_exit {
+        return hit_record_new_none();
+    }
+
+    let ray_length = length(ray.direction);
+    let distance_inside = (t_exit - t_enter) * ray_length;
+    let hit_distance = -log(1.0 - random()) / volume.density;
+    if hit_distance > distance_inside {
+        return hit_record_new_none();
+    }
+
+    // the normal is arbitrary, it faces the ray for the aovs
+    let rec_t = t_enter + hit_distance / ray_length;
+    return HitRecord(true, ray_at(ray, rec_t), -ray.direction / ray_length, rec_t, true, volume.material, primitive, vec2<f32>());
 }
 
 /*
  * ============================================================================
- * Write
+ * Instance
  * ============================================================================
  */
-fn write_color(color: vec3<f32>, samples_per_pixel: u32) -> vec3<u32> {
-    var r = color.x;
-    var g = color.y;
-    var b = color.z;
+// the primitives of an object, and the hierarchy over them
+struct BvhObject {
+    // root node
+    node: u32,
+    // index of the first primitive
+    offset: u32,
+    count: u32,
+}
+
+struct Instance {
+    object_to_world: mat4x4<f32>,
+    world_to_object: mat4x4<f32>,
+    object: BvhObject,
+}
 
-    let scale = 1.0 / f32(samples_per_pixel);
-    r = sqrt(scale * r);
-    g = sqrt(scale * g);
-    b = sqrt(scale * b);
+const NO_INSTANCE: u32 = 0xffffffffu;
 
-    let ir : u32 = u32(255.999 * clamp(r, 0.0, 0.999));
-    let ig : u32 = u32(255.999 * clamp(g, 0.0, 0.999));
-    let ib : u32 = u32(255.999 * clamp(b, 0.0, 0.999));
-    return vec3<u32>(ir, ig, ib);
+// the direction is left unnormalised, so distances along the ray are the same in both spaces
+fn instance_ray(instance: Instance, ray: Ray) -> Ray {
+    let origin = (instance.world_to_object * vec4<f32>(ray.origin, 1.0)).xyz;
+    let direction = (instance.world_to_object * vec4<f32>(ray.direction, 0.0)).xyz;
+    return ray_new(origin, direction, ray.time);
+}
+
+// moves a hit in the space of the object of an instance into the world
+fn instance_hit_record(hit_record: HitRecord, index: u32) -> HitRecord {
+    let instance = instance_type.instances[index];
+    let point = (instance.object_to_world * vec4<f32>(hit_record.point, 1.0)).xyz;
+    // the inverse transpose keeps normals perpendicular under scaling and shearing
+    let normal = normalize((transpose(instance.world_to_object) * vec4<f32>(hit_record.normal, 0.0)).xyz);
+    return HitRecord(true, point, normal, hit_record.t, hit_record.front_face, hit_record.material, Primitive(6u, index), hit_record.uv);
+}
+
+fn instance_hit(index: u32, ray: Ray, t_min: f32, t_max: f32) -> HitRecord {
+    let instance = instance_type.instances[index];
+    let object_ray = instance_ray(instance, ray);
+
+    var hit_record = hit_record_new_none();
+    var closest_so_far = t_max;
+
+    for (var i = instance.object.offset; i < instance.object.offset + instance.object.count; i = i + 1u) {
+        let h = primitive_hit(bvh_primitive_type.primitives[i], object_ray, t_min, closest_so_far);
+        if h.some {
+            hit_record = h;
+            closest_so_far = h.t;
+        }
+    }
+
+    if !hit_record.some {
+        return hit_record;
+    }
+    return instance_hit_record(hit_record, index);
+}
+
+/*
+ * ============================================================================
+ * Bounding Volume Hierarchy
+ * ============================================================================
+ */
+struct BvhNode {
+    min: vec3<f32>,
+    // interior: index of the second child, the first child follows the node
+    // leaf: index of the first primitive
+    offset: u32,
+    max: vec3<f32>,
+    // interior: 0
+    // leaf: number of primitives
+    count: u32,
+}
+
+// slab test, returns the distance the ray enters the box, or -1.0 when it misses within [t_min, t_max]
+fn aabb_hit(box_min: vec3<f32>, box_max: vec3<f32>, ray: Ray, t_min: f32, t_max: f32) -> f32 {
+    let inverse_direction = 1.0 / ray.direction;
+    let t0 = (box_min - ray.origin) * inverse_direction;
+    let t1 = (box_max - ray.origin) * inverse_direction;
+    let t_small = min(t0, t1);
+    let t_big = max(t0, t1);
+    let t_enter = max(max(t_small.x, t_small.y), max(t_small.z, t_min));
+    let t_exit = min(min(t_big.x, t_big.y), min(t_big.z, t_max));
+
+    if t_enter > t_exit {
+        return -1.0;
+    }
+    return t_enter;
+}
+
+fn primitive_hit(primitive: Primitive, ray: Ray, t_min: f32, t_max: f32) -> HitRecord {
+    switch primitive.type_ {
+        case 0u: {
+            return sphere_hit(in.spheres[primitive.index], primitive, ray, t_min, t_max);
+        }
+        case 1u: {
+            return triangle_hit(triangle_type.triangles[primitive.index], primitive, ray, t_min, t_max);
+        }
+        case 4u: {
+            return quad_hit(quad_type.quads[primitive.index], primitive, ray, t_min, t_max);
+        }
+        case 5u: {
+            return disk_hit(disk_type.disks[primitive.index], primitive, ray, t_min, t_max);
+        }
+        case 7u: {
+            return volume_hit(volume_type.volumes[primitive.index], primitive, ray, t_min, t_max);
+        }
+        default: {
+            return hit_record_new_none();
+        }
+    }
+}
+
+// the stack holds a node along with the instance whose object it belongs to, NO_INSTANCE for the world,
+// the depth of the hierarchy is limited so that it never needs more than the 64 entries of bvh::STACK_SIZE
+fn bvh_hit(ray: Ray, t_min: f32, t_max: f32) -> HitRecord {
+    var hit_record = hit_record_new_none();
+    var closest_so_far = t_max;
+
+    var stack = array<vec2<u32>, 64>();
+    var stack_length = 0u;
+
+    let root = bvh_node_type.nodes[0];
+    if aabb_hit(root.min, root.max, ray, t_min, closest_so_far) >= 0.0 {
+        stack[0] = vec2<u32>(0u, NO_INSTANCE);
+        stack_length = 1u;
+    }
+
+    // the ray in the space of the nodes being visited
+    var instance = NO_INSTANCE;
+    var space_ray = ray;
+
+    while stack_length > 0u {
+        stack_length -= 1u;
+        let index = stack[stack_length].x;
+        if stack[stack_length].y != instance {
+            instance = stack[stack_length].y;
+            if instance == NO_INSTANCE {
+                space_ray = ray;
+            } else {
+                space_ray = instance_ray(instance_type.instances[instance], ray);
+            }
+        }
+        let node = bvh_node_type.nodes[index];
+
+        if node.count > 0u {
+            for (var i = node.offset; i < node.offset + node.count; i = i + 1u) {
+                let primitive = bvh_primitive_type.primitives[i];
+                if primitive.type_ == 6u {
+                    // visit the hierarchy of the object later, in the space of the object
+                    let object_ray = instance_ray(instance_type.instances[primitive.index], ray);
+                    let object = instance_type.instances[primitive.index].object;
+                    let object_root = bvh_node_type.nodes[object.node];
+                    if aabb_hit(object_root.min, object_root.max, object_ray, t_min, closest_so_far) >= 0.0 {
+                        stack[stack_length] = vec2<u32>(object.node, primitive.index);
+                        stack_length += 1u;
+                    }
+                    continue;
+                }
+
+                let h = primitive_hit(primitive, space_ray, t_min, closest_so_far);
+                if h.some {
+                    hit_record = h;
+                    if instance != NO_INSTANCE {
+                        hit_record = instance_hit_record(h, instance);
+                    }
+                    closest_so_far = h.t;
+                }
+            }
+            continue;
+        }
+
+        // push the farther child first so the nearer child is visited first
+        let left = bvh_node_type.nodes[index + 1u];
+        let right = bvh_node_type.nodes[node.offset];
+        let t_left = aabb_hit(left.min, left.max, space_ray, t_min, closest_so_far);
+        let t_right = aabb_hit(right.min, right.max, space_ray, t_min, closest_so_far);
+
+        var first = index + 1u;
+        var second = node.offset;
+        var t_first = t_left;
+        var t_second = t_right;
+        if t_left < 0.0 || (t_right >= 0.0 && t_right < t_left) {
+            first = node.offset;
+            second = index + 1u;
+            t_first = t_right;
+            t_second = t_left;
+        }
+
+        if t_second >= 0.0 {
+            stack[stack_length] = vec2<u32>(second, instance);
+            stack_length += 1u;
+        }
+        if t_first >= 0.0 {
+            stack[stack_length] = vec2<u32>(first, instance);
+            stack_length += 1u;
+        }
+    }
+
+    return hit_record;
+}
+
+/*
+ * ============================================================================
+ * World
+ * ============================================================================
+ */
+struct World {
+    a: u32,
+}
+
+fn world_hit(world: World, ray: Ray, t_min: f32, t_max: f32) -> HitRecord {
+    var hit_record = hit_record_new_none();
+    var closest_so_far = t_max;
+
+    // planes are unbounded, so the hierarchy leaves them out
+    for (var index = 0u; index < arrayLength(&plane_type.planes); index = index + 1u) {
+        let h = plane_hit(plane_type.planes[index], Primitive(3u, index), ray, t_min, closest_so_far);
+        if h.some {
+            hit_record = h;
+            closest_so_far = h.t;
+        }
+    }
+
+    if in.brute_force == 0u {
+        let h = bvh_hit(ray, t_min, closest_so_far);
+        if h.some {
+            return h;
+        }
+        return hit_record;
+    }
+
+    for (var index = 0u; index < in.primitive_length; index = index + 1u) {
+        let primitive = bvh_primitive_type.primitives[index];
+        var h: HitRecord;
+        if primitive.type_ == 6u {
+            h = instance_hit(primitive.index, ray, t_min, closest_so_far);
+        } else {
+            h = primitive_hit(primitive, ray, t_min, closest_so_far);
+        }
+        if h.some {
+            hit_record = h;
+            closest_so_far = h.t;
+        }
+    }
+
+    return hit_record;
+}
+
+/*
+ * ============================================================================
+ * Light
+ * ============================================================================
+ */
+struct LightSample {
+    direction: vec3<f32>,
+    // solid angle probability density, zero when there is no sample
+    pdf: f32,
+    primitive: Primitive,
+}
+
+fn sphere_light_pdf(sphere: Sphere, origin: vec3<f32>, time: f32) -> f32 {
+    let distance_squared = length_squared(sphere_center(sphere, time) - origin);
+    let radius_squared = sphere.radius * sphere.radius;
+    if distance_squared <= radius_squared {
+        return 0.0;
+    }
+
+    // 1 - cos_theta_max without cancellation for small or distant spheres
+    let sin_theta_max_squared = radius_squared / distance_squared;
+    let cos_theta_max = sqrt(1.0 - sin_theta_max_squared);
+    return (1.0 + cos_theta_max) / (2.0 * PI * sin_theta_max_squared);
+}
+
+fn sphere_light_sample(sphere: Sphere, origin: vec3<f32>, time: f32) -> LightSample {
+    let r1 = random();
+    let r2 = random();
+
+    let pdf = sphere_light_pdf(sphere, origin, time);
+    if pdf <= 0.0 {
+        return LightSample(vec3<f32>(), 0.0, Primitive(0u, 0u));
+    }
+
+    // uniform in the cone of directions towards the sphere
+    let center = sphere_center(sphere, time);
+    let cos_theta_max = sqrt(1.0 - sphere.radius * sphere.radius / length_squared(center - origin));
+    let z = 1.0 + r2 * (cos_theta_max - 1.0);
+    let phi = 2.0 * PI * r1;
+    let sin_theta = sqrt(max(1.0 - z * z, 0.0));
+    let direction = onb_local(normalize(center - origin), cos(phi) * sin_theta, sin(phi) * sin_theta, z);
+
+    return LightSample(normalize(direction), pdf, Primitive(0u, 0u));
+}
+
+fn triangle_light_pdf(triangle: Triangle, origin: vec3<f32>, point: vec3<f32>) -> f32 {
+    let v0 = vertex_type.vertices[triangle.indices.x].position;
+    let v1 = vertex_type.vertices[triangle.indices.y].position;
+    let v2 = vertex_type.vertices[triangle.indices.z].position;
+
+    let normal = cross(v1 - v0, v2 - v0);
+    let area = 0.5 * length(normal);
+    let to_point = point - origin;
+    let distance_squared = length_squared(to_point);
+    let cosine = abs(dot(normal, to_point)) / (2.0 * area * sqrt(distance_squared));
+    if area <= 0.0 || cosine < 0.000001 {
+        return 0.0;
+    }
+
+    return distance_squared / (cosine * area);
+}
+
+fn triangle_light_sample(triangle: Triangle, origin: vec3<f32>) -> LightSample {
+    let r1 = random();
+    let r2 = random();
+
+    let v0 = vertex_type.vertices[triangle.indices.x].position;
+    let v1 = vertex_type.vertices[triangle.indices.y].position;
+    let v2 = vertex_type.vertices[triangle.indices.z].position;
+
+    // uniform over the area of the triangle
+    let s = sqrt(r1);
+    let point = (1.0 - s) * v0 + s * (1.0 - r2) * v1 + s * r2 * v2;
+
+    let pdf = triangle_light_pdf(triangle, origin, point);
+    if pdf <= 0.0 {
+        return LightSample(vec3<f32>(), 0.0, Primitive(0u, 0u));
+    }
+
+    return LightSample(normalize(point - origin), pdf, Primitive(0u, 0u));
+}
+
+fn quad_light_pdf(quad: Quad, origin: vec3<f32>, point: vec3<f32>) -> f32 {
+    let normal = cross(quad.u, quad.v);
+    let area = length(normal);
+    let to_point = point - origin;
+    let distance_squared = length_squared(to_point);
+    let cosine = abs(dot(normal, to_point)) / (area * sqrt(distance_squared));
+    if area <= 0.0 || cosine < 0.000001 {
+        return 0.0;
+    }
+
+    return distance_squared / (cosine * area);
+}
+
+fn quad_light_sample(quad: Quad, origin: vec3<f32>) -> LightSample {
+    let r1 = random();
+    let r2 = random();
+
+    // uniform over the area of the quad
+    let point = quad.corner + r1 * quad.u + r2 * quad.v;
+
+    let pdf = quad_light_pdf(quad, origin, point);
+    if pdf <= 0.0 {
+        return LightSample(vec3<f32>(), 0.0, Primitive(0u, 0u));
+    }
+
+    return LightSample(normalize(point - origin), pdf, Primitive(0u, 0u));
+}
+
+fn disk_light_pdf(disk: Disk, origin: vec3<f32>, point: vec3<f32>) -> f32 {
+    let area = PI * disk.radius * disk.radius;
+    let to_point = point - origin;
+    let distance_squared = length_squared(to_point);
+    let cosine = abs(dot(normalize(disk.normal), to_point)) / sqrt(distance_squared);
+    if area <= 0.0 || cosine < 0.000001 {
+        return 0.0;
+    }
+
+    return distance_squared / (cosine * area);
+}
+
+fn disk_light_sample(disk: Disk, origin: vec3<f32>) -> LightSample {
+    let r1 = random();
+    let r2 = random();
+
+    // uniform over the area of the disk
+    let r = disk.radius * sqrt(r1);
+    let phi = 2.0 * PI * r2;
+    let point = disk.center + onb_local(normalize(disk.normal), r * cos(phi), r * sin(phi), 0.0);
+
+    let pdf = disk_light_pdf(disk, origin, point);
+    if pdf <= 0.0 {
+        return LightSample(vec3<f32>(), 0.0, Primitive(0u, 0u));
+    }
+
+    return LightSample(normalize(point - origin), pdf, Primitive(0u, 0u));
+}
+
+// solid angle probability density of environment_light_sample choosing direction
+fn environment_light_pdf(direction: vec3<f32>) -> f32 {
+    let size = environment_type.size;
+    if size.x == 0u || size.y == 0u {
+        return 0.0;
+    }
+
+    let index = environment_texel(direction);
+    var probability = environment_type.texels[index].cdf;
+    if index > 0u {
+        probability -= environment_type.texels[index - 1u].cdf;
+    }
+
+    // texels cover less solid angle towards the poles, which the rotation leaves in place
+    let unit_direction = normalize(direction);
+    let sin_theta = sqrt(max(1.0 - unit_direction.y * unit_direction.y, 0.0));
+    if sin_theta <= 0.0 {
+        return 0.0;
+    }
+
+    return probability * f32(size.x * size.y) / (2.0 * PI * PI * sin_theta);
+}
+
+fn environment_light_sample() -> LightSample {
+    let r1 = random();
+    let r2 = random();
+    let r3 = random();
+
+    let size = environment_type.size;
+    let texel_length = size.x * size.y;
+    if texel_length == 0u {
+        return LightSample(vec3<f32>(), 0.0, Primitive(0u, 0u));
+    }
+
+    // first texel whose cumulative probability is above r1
+    var low = 0u;
+    var high = texel_length - 1u;
+    while low < high {
+        let middle = (low + high) / 2u;
+        if environment_type.texels[middle].cdf > r1 {
+            high = middle;
+        } else {
+            low = middle + 1u;
+        }
+    }
+
+    // uniform within the texel
+    let phi = ((f32(low % size.x) + r2) / f32(size.x) - 0.5) * 2.0 * PI;
+    let theta = (f32(low / size.x) + r3) / f32(size.y) * PI;
+    let direction = rotate_y(
+        vec3<f32>(sin(theta) * sin(phi), cos(theta), -sin(theta) * cos(phi)),
+        in.background.rotation,
+    );
+
+    let pdf = environment_light_pdf(direction);
+    if pdf <= 0.0 {
+        return LightSample(vec3<f32>(), 0.0, Primitive(0u, 0u));
+    }
+
+    return LightSample(direction, pdf, Primitive(0u, 0u));
+}
+
+fn sun_light_pdf(direction: vec3<f32>) -> f32 {
+    let sky = in.background.sky;
+    if max(sky.sun_radiance.x, max(sky.sun_radiance.y, sky.sun_radiance.z)) <= 0.0
+        || dot(normalize(direction), sky.sun_direction) < sky.sun_cos_radius {
+        return 0.0;
+    }
+
+    return 1.0 / sky.sun_solid_angle;
+}
+
+fn sun_light_sample() -> LightSample {
+    let r1 = random();
+    let r2 = random();
+
+    let sky = in.background.sky;
+    if max(sky.sun_radiance.x, max(sky.sun_radiance.y, sky.sun_radiance.z)) <= 0.0 {
+        return LightSample(vec3<f32>(), 0.0, Primitive(0u, 0u));
+    }
+
+    // uniform in the cone of directions towards the sun disk
+    let z = 1.0 + r2 * (sky.sun_cos_radius - 1.0);
+    let phi = 2.0 * PI * r1;
+    let sin_theta = sqrt(max(1.0 - z * z, 0.0));
+    let direction = onb_local(sky.sun_direction, cos(phi) * sin_theta, sin(phi) * sin_theta, z);
+
+    return LightSample(normalize(direction), 1.0 / sky.sun_solid_angle, Primitive(0u, 0u));
+}
+
+// solid angle probability density of background_light_sample choosing direction
+fn background_light_pdf(direction: vec3<f32>) -> f32 {
+    switch in.background.type_ {
+        case 3u: {
+            return environment_light_pdf(direction);
+        }
+        case 4u: {
+            return sun_light_pdf(direction);
+        }
+        default: {
+            return 0.0;
+        }
+    }
+}
+
+fn background_light_sample() -> LightSample {
+    switch in.background.type_ {
+        case 3u: {
+            return environment_light_sample();
+        }
+        case 4u: {
+            return sun_light_sample();
+        }
+        default: {
+            return LightSample(vec3<f32>(), 0.0, Primitive(0u, 0u));
+        }
+    }
+}
+
+fn light_sample(origin: vec3<f32>, time: f32) -> LightSample {
+    let light_length = light_type.light_length;
+    let light = light_type.lights[min(u32(random() * f32(light_length)), light_length - 1u)];
+
+    var sampled: LightSample;
+    switch light.type_ {
+        case 0u: {
+            sampled = sphere_light_sample(in.spheres[light.index], origin, time);
+        }
+        case 1u: {
+            sampled = triangle_light_sample(triangle_type.triangles[light.index], origin);
+        }
+        case 2u: {
+            sampled = background_light_sample();
+        }
+        case 4u: {
+            sampled = quad_light_sample(quad_type.quads[light.index], origin);
+        }
+        case 5u: {
+            sampled = disk_light_sample(disk_type.disks[light.index], origin);
+        }
+        default: {
+            sampled = LightSample(vec3<f32>(), 0.0, Primitive(0u, 0u));
+        }
+    }
+
+    sampled.pdf /= f32(light_length);
+    sampled.primitive = light;
+    return sampled;
+}
+
+// probability density of light_sample choosing the direction from origin to the light hit by hit_record
+fn light_pdf(origin: vec3<f32>, time: f32, hit_record: HitRecord) -> f32 {
+    let light_length = light_type.light_length;
+    if light_length == 0u {
+        return 0.0;
+    }
+
+    var pdf = 0.0;
+    switch hit_record.primitive.type_ {
+        case 0u: {
+            pdf = sphere_light_pdf(in.spheres[hit_record.primitive.index], origin, time);
+        }
+        case 1u: {
+            pdf = triangle_light_pdf(triangle_type.triangles[hit_record.primitive.index], origin, hit_record.point);
+        }
+        case 4u: {
+            pdf = quad_light_pdf(quad_type.quads[hit_record.primitive.index], origin, hit_record.point);
+        }
+        case 5u: {
+            pdf = disk_light_pdf(disk_type.disks[hit_record.primitive.index], origin, hit_record.point);
+        }
+        default: {}
+    }
+
+    return pdf / f32(light_length);
+}
+
+// probability density of light_sample choosing direction towards the background
+fn light_pdf_background(direction: vec3<f32>) -> f32 {
+    let light_length = light_type.light_length;
+    if light_length == 0u {
+        return 0.0;
+    }
+
+    return background_light_pdf(direction) / f32(light_length);
+}
+
+// radiance reaching a lambertian hit directly from a sampled light, weighted against bounces
+fn light_direct(world: World, hit_record: HitRecord, time: f32) -> vec3<f32> {
+    if light_type.light_length == 0u {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+
+    let sampled = light_sample(hit_record.point, time);
+    let cosine = dot(hit_record.normal, sampled.direction);
+    if sampled.pdf <= 0.0 || cosine <= 0.0 {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+
+    let shadow_ray = ray_new(hit_record.point, sampled.direction, time);
+    let shadow = world_hit(world, shadow_ray, 0.001, 10000.0);
+
+    var emitted: vec3<f32>;
+    if sampled.primitive.type_ == 2u {
+        // the background is only visible when nothing is in the way
+        if shadow.some {
+            return vec3<f32>(0.0, 0.0, 0.0);
+        }
+        emitted = background_color(shadow_ray);
+    } else {
+        // the light is only visible when it is the closest hit
+        if !shadow.some
+            || shadow.primitive.type_ != sampled.primitive.type_
+            || shadow.primitive.index != sampled.primitive.index {
+            return vec3<f32>(0.0, 0.0, 0.0);
+        }
+        emitted = material_emitted(shadow.material);
+    }
+
+    let weight = power_heuristic(sampled.pdf, cosine / PI);
+    return material_texture_albedo(hit_record.material, hit_record) / PI * emitted * cosine * weight / sampled.pdf;
+}
+
+/*
+ * ============================================================================
+ * AOV
+ * ============================================================================
+ */
+// first hit of a camera ray, zero when it escapes
+struct Aov {
+    normal: vec3<f32>,
+    // distance along the ray
+    depth: f32,
+    albedo: vec3<f32>,
+    // material type, zero is the background
+    material: u32,
+    primitive: Primitive,
+}
+
+// written by `ray_color` for the sample being taken
+var<private> primary_aov: Aov;
+
+fn aov_new(ray: Ray, hit_record: HitRecord) -> Aov {
+    return Aov(
+        hit_record.normal,
+        hit_record.t * length(ray.direction),
+        material_albedo(hit_record.material, hit_record),
+        hit_record.material.type_,
+        hit_record.primitive
+    );
+}
+
+/*
+ * ============================================================================
+ * Adaptive Sampling
+ * ============================================================================
+ */
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// standard error of the mean luminance, relative to the mean luminance
+fn pixel_noise(pixel: AccumulationPixel) -> f32 {
+    let n = f32(pixel.samples);
+    let mean = pixel.luminance / n;
+    let variance = max(pixel.luminance_squared - n * mean * mean, 0.0) / max(n - 1.0, 1.0);
+    return sqrt(variance / n) / max(mean, 0.001);
+}
+
+// samples this dispatch adds to the pixel, converged pixels take none
+fn pixel_samples(pixel: AccumulationPixel) -> u32 {
+    if in.noise_threshold > 0.0 && pixel.samples >= in.min_samples {
+        if pixel.samples >= in.max_samples || pixel_noise(pixel) <= in.noise_threshold {
+            return 0u;
+        }
+    }
+    return dispatch.samples;
 }
 
 /*
@@ -455,63 +1930,59 @@ fn main(
     @builtin(global_invocation_id) global_id: vec3<u32>,
 ) {
     // Exit
-    if global_id.x >= in.view_box_size.x || global_id.y >= in.view_box_size.y || global_id.z >= 1u {
+    if global_id.x >= dispatch.view_box_size.x || global_id.y >= dispatch.view_box_size.y || global_id.z >= 1u {
         return;
     }
 
-    // Pixel Space
-    if global_id.x == 0u && global_id.y == 0u && global_id.z == 0u {
-        out.pixel_length = in.view_box_size.y * in.view_box_size.x;
-    }
-
     // Invocation
-    let i = in.view_box_position.x + global_id.x;
-    let j = in.view_box_position.y + global_id.y;
-    let index = in.view_box_size.x * global_id.y + global_id.x;
-
-    // Initialization
-    random_init(index);
+    let i = dispatch.view_box_position.x + global_id.x;
+    let j = dispatch.view_box_position.y + global_id.y;
+    let index = dispatch.view_box_size.x * global_id.y + global_id.x;
 
     // Image
     let image_width = in.screen_size.x;
     let image_height = in.screen_size.y;
     let aspect_ratio = f32(image_width) / f32(image_height);
-    let samples_per_pixel = in.samples_per_pixel;
 
     // World
     var world = World(0u);
 
     // Camera
-    let lookfrom = vec3<f32>(13.0, 2.0, 3.0);
-    let lookat = vec3<f32>(0.0, 0.0, 0.0);
-    let vup = vec3<f32>(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.1;
-
     let camera = camera_new(
-        lookfrom,
-        lookat,
-        vup,
-        20.0,
+        in.camera.look_from,
+        in.camera.look_at,
+        in.camera.up,
+        in.camera.vertical_fov,
         aspect_ratio,
-        aperture,
-        dist_to_focus
+        in.camera.aperture,
+        in.camera.focus_distance,
+        in.camera.shutter_open,
+        in.camera.shutter_close
     );
 
-    let viewport_height = 2.0;
-    let viewport_width = aspect_ratio * viewport_height;
-    let focal_length = 1.0;
-
     // Calculate
-    var pixel_color = vec3<f32>();
+    var pixel = accumulation.pixels[index];
+    let samples = pixel_samples(pixel);
 
-    for (var s = 0u; s < samples_per_pixel; s = s + 1u) {
+    for (var s = pixel.samples; s < pixel.samples + samples; s = s + 1u) {
+        random_init(vec2<u32>(i, j), s, in.seed);
         let u = (f32(i) + random()) / f32(image_width - 1u);
         let v = (f32(j) + random()) / f32(image_height - 1u);
         let ray = camera_get_ray(camera, u, v);
-        pixel_color += ray_color(ray, world);
+        let color = ray_color(ray, world);
+        pixel.radiance += color;
+        pixel.luminance += luminance(color);
+        pixel.luminance_squared += luminance(color) * luminance(color);
+
+        if s == 0u {
+            pixel.aov = primary_aov;
+        }
     }
+    pixel.samples += samples;
 
     // Save
-    out.pixel[index] = write_color(pixel_color, samples_per_pixel);
+    accumulation.pixels[index] = pixel;
+    if pixel_samples(pixel) > 0u {
+        atomicAdd(&accumulation.active_pixels, 1u);
+    }
 }
@@ -0,0 +1,355 @@
+//! Structs shared with `shader.wgsl`, laid out in its buffers by `encase::ShaderType`.
+//!
+//! Only the derived definitions live here, their constructors are in the parent module.
+
+// `encase::ShaderType` derives compile time checks which are never called.
+#![allow(dead_code)]
+
+#[derive(Clone, Debug, Default, PartialEq, encase::ShaderType)]
+pub struct InputTypeBvhNode {
+    pub min: glam::Vec3,
+    /// interior: index of the second child, leaf: index of the first primitive
+    pub offset: u32,
+    pub max: glam::Vec3,
+    /// interior: 0, leaf: number of primitives
+    pub count: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, encase::ShaderType)]
+pub struct InputTypeBvhObject {
+    /// root of the subtree over the primitives of the object
+    pub node: u32,
+    /// index of the first primitive of the object
+    pub offset: u32,
+    pub count: u32,
+}
+
+/// Layout of `InputType` in the input storage buffer.
+#[derive(Debug, encase::ShaderType)]
+pub(super) struct InputTypeBuffer {
+    pub(super) max_depth: u32,
+    pub(super) russian_roulette: u32,
+    pub(super) russian_roulette_depth: u32,
+    pub(super) seed: u32,
+    pub(super) brute_force: u32,
+    /// primitives and instances at the start of the BVH primitives, which brute force tests
+    pub(super) primitive_length: u32,
+    pub(super) next_event_estimation: u32,
+    /// zero samples every pixel equally
+    pub(super) noise_threshold: f32,
+    pub(super) min_samples: u32,
+    pub(super) max_samples: u32,
+    pub(super) screen_size: glam::UVec2,
+    pub(super) camera: InputTypeCamera,
+    pub(super) background: InputTypeBackground,
+    #[size(runtime)]
+    pub(super) spheres: Vec<InputTypeSphere>,
+}
+
+#[derive(Clone, Debug, Default, encase::ShaderType)]
+pub struct InputTypeCamera {
+    pub look_from: glam::Vec3,
+    pub look_at: glam::Vec3,
+    pub up: glam::Vec3,
+    pub vertical_fov: f32,
+    pub aperture: f32,
+    pub focus_distance: f32,
+    /// Times every camera ray is sampled between.
+    pub shutter_open: f32,
+    pub shutter_close: f32,
+}
+
+#[derive(Clone, Debug, Default, encase::ShaderType)]
+pub struct InputTypeBackground {
+    /// solid colour, or what the environment is multiplied by
+    pub(super) color: glam::Vec3,
+    // 0. gradient
+    // 1. solid
+    // 2. black
+    // 3. environment
+    // 4. sky
+    pub(super) type_: u32,
+    /// radians the environment is turned by around the y axis
+    pub(super) rotation: f32,
+    pub(super) sky: InputTypeSky,
+}
+
+/// Analytic daylight model of Preetham, Shirley and Smits, with the ground below the horizon.
+#[derive(Clone, Debug, Default, encase::ShaderType)]
+pub struct InputTypeSky {
+    /// Perez coefficients of the luminance and the x and y chromaticity
+    pub(super) a: glam::Vec3,
+    pub(super) b: glam::Vec3,
+    pub(super) c: glam::Vec3,
+    pub(super) d: glam::Vec3,
+    pub(super) e: glam::Vec3,
+    /// luminance and chromaticity at the zenith, divided by the Perez function at the zenith
+    pub(super) zenith: glam::Vec3,
+    /// unit vector towards the sun
+    pub(super) sun_direction: glam::Vec3,
+    pub(super) sun_cos_radius: f32,
+    /// zero when the sun is below the horizon
+    pub(super) sun_radiance: glam::Vec3,
+    pub(super) sun_solid_angle: f32,
+    /// radiance of the ground, lit by the sky and the sun
+    pub(super) ground: glam::Vec3,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, encase::ShaderType)]
+pub struct InputTypeEnvironmentTexel {
+    pub radiance: glam::Vec3,
+    /// probability of importance sampling this texel or one before it
+    pub cdf: f32,
+}
+
+#[derive(Clone, Debug, Default, encase::ShaderType)]
+pub struct InputTypeSphere {
+    /// At time 0.
+    pub center: glam::Vec3,
+    pub radius: f32,
+    /// At time 1, the same as `center` for spheres standing still.
+    pub end_center: glam::Vec3,
+    pub material: InputTypeMaterial,
+}
+
+#[derive(Clone, Debug, Default, encase::ShaderType)]
+pub struct InputTypeVertex {
+    pub position: glam::Vec3,
+    /// zero when the mesh has no normals, the face normal is used instead
+    pub normal: glam::Vec3,
+}
+
+#[derive(Clone, Debug, Default, encase::ShaderType)]
+pub struct InputTypeTriangle {
+    /// indices into `InputType::vertices`
+    pub indices: glam::UVec3,
+    pub material: InputTypeMaterial,
+}
+
+/// Infinite plane through `point`.
+#[derive(Clone, Debug, Default, encase::ShaderType)]
+pub struct InputTypePlane {
+    pub point: glam::Vec3,
+    /// zero for the padding of an empty plane array
+    pub normal: glam::Vec3,
+    pub material: InputTypeMaterial,
+}
+
+/// Parallelogram with the corners `corner`, `corner + u`, `corner + u + v` and `corner + v`,
+/// facing `u × v`.
+#[derive(Clone, Debug, Default, encase::ShaderType)]
+pub struct InputTypeQuad {
+    pub corner: glam::Vec3,
+    pub u: glam::Vec3,
+    pub v: glam::Vec3,
+    pub material: InputTypeMaterial,
+}
+
+#[derive(Clone, Debug, Default, encase::ShaderType)]
+pub struct InputTypeDisk {
+    pub center: glam::Vec3,
+    pub radius: f32,
+    pub normal: glam::Vec3,
+    pub material: InputTypeMaterial,
+}
+
+/// Fog or smoke of a constant density filling a sphere or an axis-aligned box, scattering light
+/// equally in every direction.
+#[derive(Clone, Debug, Default, encase::ShaderType)]
+pub struct InputTypeVolume {
+    pub(super) center: glam::Vec3,
+    pub(super) radius: f32,
+    pub(super) half_size: glam::Vec3,
+    /// chance of scattering per unit of distance
+    pub(super) density: f32,
+    // 0. sphere of `radius` around `center`
+    // 1. box reaching `half_size` from `center`
+    pub(super) boundary: u32,
+    pub(super) material: InputTypeMaterial,
+}
+
+/// Reference to a primitive of `InputType`, or to the environment among the lights.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, encase::ShaderType)]
+pub struct InputTypePrimitive {
+    // 0. sphere
+    // 1. triangle
+    // 2. environment, the map or the sun of the background
+    // 3. plane
+    // 4. quad
+    // 5. disk
+    // 6. instance, hits report it instead of the primitive of the object
+    // 7. volume
+    pub type_: u32,
+    /// index into the array of `InputType` holding primitives of `type_`
+    pub index: u32,
+}
+
+#[derive(Clone, Debug, Default, encase::ShaderType)]
+pub struct InputTypeMaterial {
+    pub(super) albedo: glam::Vec3,
+    // 0. background
+    // 1. lambertian
+    // 2. metal
+    // 3. dielectric
+    // 4. diffuse light, albedo is the emitted radiance
+    // 5. isotropic, scatters into every direction alike inside volumes
+    pub(super) type_: u32,
+    pub(super) fuzz: f32,
+    pub(super) index_of_refraction: f32,
+    /// index into `InputType::textures` the albedo is multiplied by, or `NO_TEXTURE`
+    pub(super) texture: u32,
+}
+
+/// Colour varying over the surface of a primitive, evaluated at the point and the uv coordinates
+/// of a hit.
+///
+/// Spheres are unwrapped by longitude and latitude, quads and disks span 0..1 across, triangles
+/// use their barycentric coordinates and planes the distance along them.
+#[derive(Clone, Debug, Default, PartialEq, encase::ShaderType)]
+pub struct InputTypeTexture {
+    pub(super) color: glam::Vec3,
+    // 0. solid
+    // 1. checker of cubes in space, centred on the lattice
+    // 2. checker of squares over the uv coordinates
+    // 3. perlin noise
+    // 4. turbulence, perlin noise summed over octaves
+    // 5. image
+    pub(super) type_: u32,
+    /// colour of the odd cubes and squares of a checker
+    pub(super) odd: glam::Vec3,
+    /// checker cells or noise features per unit of distance or uv
+    pub(super) scale: f32,
+    pub(super) size: glam::UVec2,
+    /// first texel of an image in `InputType::texels`
+    pub(super) offset: u32,
+    pub(super) octaves: u32,
+}
+
+/// First hit of the camera ray of the first sample of a pixel, zero when it escapes.
+#[derive(Clone, Debug, Default, PartialEq, encase::ShaderType)]
+pub struct OutputTypeAov {
+    pub normal: glam::Vec3,
+    /// distance along the camera ray
+    pub depth: f32,
+    pub albedo: glam::Vec3,
+    /// material type, zero is the background
+    pub material: u32,
+    pub primitive: InputTypePrimitive,
+}
+
+/// Layout of the dispatch uniform buffer, which selects the samples and pixels of one dispatch.
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct DispatchType {
+    /// samples added to pixels which have not converged yet
+    pub(super) samples: u32,
+    pub(super) view_box_position: glam::UVec2,
+    pub(super) view_box_size: glam::UVec2,
+}
+
+#[derive(Clone, Debug, Default, encase::ShaderType)]
+pub(super) struct AccumulationPixel {
+    /// summed over every sample
+    pub(super) radiance: glam::Vec3,
+    pub(super) samples: u32,
+    /// summed over every sample along with its square, to estimate the variance of the pixel
+    pub(super) luminance: f32,
+    pub(super) luminance_squared: f32,
+    /// first hit of the first sample
+    pub(super) aov: OutputTypeAov,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct AccumulationType {
+    /// pixels the next dispatch adds samples to, counted by the last one
+    pub(super) active_pixels: u32,
+    #[size(runtime)]
+    pub(super) pixels: Vec<AccumulationPixel>,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct VertexType {
+    #[size(runtime)]
+    pub(super) vertices: Vec<InputTypeVertex>,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct TriangleType {
+    #[size(runtime)]
+    pub(super) triangles: Vec<InputTypeTriangle>,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct PlaneType {
+    #[size(runtime)]
+    pub(super) planes: Vec<InputTypePlane>,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct QuadType {
+    #[size(runtime)]
+    pub(super) quads: Vec<InputTypeQuad>,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct DiskType {
+    #[size(runtime)]
+    pub(super) disks: Vec<InputTypeDisk>,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct VolumeType {
+    #[size(runtime)]
+    pub(super) volumes: Vec<InputTypeVolume>,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct TextureType {
+    #[size(runtime)]
+    pub(super) textures: Vec<InputTypeTexture>,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct TexelType {
+    #[size(runtime)]
+    pub(super) texels: Vec<u32>,
+}
+
+/// An instance along with the primitives and hierarchy of its object.
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct Instance {
+    pub(super) object_to_world: glam::Mat4,
+    pub(super) world_to_object: glam::Mat4,
+    pub(super) object: InputTypeBvhObject,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct InstanceType {
+    #[size(runtime)]
+    pub(super) instances: Vec<Instance>,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct EnvironmentType {
+    pub(super) size: glam::UVec2,
+    #[size(runtime)]
+    pub(super) texels: Vec<InputTypeEnvironmentTexel>,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct LightType {
+    pub(super) light_length: encase::ArrayLength,
+    #[size(runtime)]
+    pub(super) lights: Vec<InputTypePrimitive>,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct BvhNodeType {
+    #[size(runtime)]
+    pub(super) nodes: Vec<InputTypeBvhNode>,
+}
+
+#[derive(Debug, Default, encase::ShaderType)]
+pub(super) struct BvhPrimitiveType {
+    #[size(runtime)]
+    pub(super) primitives: Vec<InputTypePrimitive>,
+}